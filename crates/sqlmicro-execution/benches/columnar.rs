@@ -0,0 +1,102 @@
+use bigdecimal::BigDecimal;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sqlmicro_execution::executor::{ExecutionResponse, Executor};
+use sqlmicro_parser::{
+    expression::Expression, parse::Parse, query::SqlQuery, value::Value, Column, CreateStatement,
+    InsertSource, InsertStatement, SqlTypeInfo, TableLayout,
+};
+
+const ROWS: u64 = 1_000_000;
+
+/// `rows` and `columns` tables holding the same million rows
+fn setup() -> Executor {
+    let mut exec = Executor::new();
+
+    for (table, layout) in [
+        ("rows", TableLayout::Row),
+        ("columns", TableLayout::Columnar),
+    ] {
+        let columns = [
+            ("id", SqlTypeInfo::Int),
+            ("category", SqlTypeInfo::Int),
+            ("amount", SqlTypeInfo::Int),
+            ("label", SqlTypeInfo::String),
+        ]
+        .into_iter()
+        .map(|(name, type_info)| Column {
+            name: name.into(),
+            type_info,
+        })
+        .collect();
+
+        exec.run(SqlQuery::Create(CreateStatement {
+            table: table.into(),
+            columns,
+            keys: vec![],
+            layout,
+            query: None,
+        }))
+        .unwrap();
+
+        for id in 0..ROWS {
+            let values = [
+                Value::Number(BigDecimal::from(id)),
+                Value::Number(BigDecimal::from(id % 16)),
+                Value::Number(BigDecimal::from(id * 7 % 1000)),
+                Value::String(format!("label {}", id % 100)),
+            ]
+            .into_iter()
+            .map(|value| Expression::Literal(value.into()))
+            .collect();
+            exec.run(SqlQuery::Insert(InsertStatement {
+                table: table.into(),
+                columns: vec![],
+                source: InsertSource::Values(values),
+                on_conflict: None,
+                returning: vec![],
+            }))
+            .unwrap();
+        }
+    }
+
+    exec
+}
+
+fn run(exec: &mut Executor, query: &str) -> usize {
+    let query = SqlQuery::parse_format_error(query).unwrap();
+    match exec.run(query).unwrap() {
+        ExecutionResponse::Select(rows) => rows.len(),
+        _ => 0,
+    }
+}
+
+fn bench_layouts(c: &mut Criterion) {
+    let mut exec = setup();
+
+    let queries = [
+        ("scan", "select id, amount from {t} where amount < 10;"),
+        (
+            "aggregate",
+            "select count(*), sum(amount), min(amount), max(amount) from {t};",
+        ),
+        (
+            "group_by",
+            "select category, count(*), sum(amount) from {t} group by category;",
+        ),
+    ];
+
+    for (name, query) in queries {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(10);
+        for table in ["rows", "columns"] {
+            let query = query.replace("{t}", table);
+            group.bench_function(BenchmarkId::from_parameter(table), |b| {
+                b.iter(|| run(&mut exec, &query))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_layouts);
+criterion_main!(benches);
@@ -0,0 +1,1803 @@
+//! Semantic analysis.
+//!
+//! The binder checks a statement against the catalog before it is planned:
+//! tables and columns have to exist, operators get operands of the type they
+//! work on, functions get the right number of arguments and aggregates only
+//! appear in the select list and `ORDER BY`. It keeps going after a problem,
+//! so a statement reports every error it has at once, each pointing at the
+//! part of the source it is about.
+
+use std::collections::HashMap;
+
+use derive_more::Display;
+use sqlmicro_parser::{
+    expression::{BinaryOperator, Expression, Parameter, UnaryOperator, Window},
+    parse::{Span, Spanned},
+    query::SqlQuery,
+    value::Value,
+    visit::{walk_expression, Visitor},
+    ConflictAction, CreateStatement, Distinct, InsertSource, InsertStatement, KeyKind, OnConflict,
+    SelectItem, SelectStatement, SqlTypeInfo, TableRef, With,
+};
+
+use crate::{
+    functions::{FunctionRegistry, ScalarFunction},
+    operator::{window_frame, AggregateFunction, WindowFunction},
+    planner::{
+        column_span, conditional_arguments, output_field, position, recursive_step, resolve_output,
+    },
+    table::Table,
+    ExecutionError,
+};
+
+/// Type of an expression as far as the binder can tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DataType {
+    #[display(fmt = "INT")]
+    Int,
+    #[display(fmt = "STRING")]
+    String,
+    #[display(fmt = "BOOLEAN")]
+    Boolean,
+}
+
+impl From<SqlTypeInfo> for DataType {
+    fn from(type_info: SqlTypeInfo) -> Self {
+        match type_info {
+            SqlTypeInfo::Int => DataType::Int,
+            SqlTypeInfo::String => DataType::String,
+        }
+    }
+}
+
+/// The column type holding values of `ty`, booleans can not be stored
+pub(crate) fn sql_type(ty: DataType) -> Option<SqlTypeInfo> {
+    match ty {
+        DataType::Int => Some(SqlTypeInfo::Int),
+        DataType::String => Some(SqlTypeInfo::String),
+        DataType::Boolean => None,
+    }
+}
+
+/// Where an expression appears, aggregates are only allowed in the output
+/// and window functions only directly in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Place {
+    Row,
+    Output,
+    /// The arguments, partitions and sort keys of a window function, which
+    /// see the rows after grouping
+    Window,
+}
+
+/// Name and type of a column a table or subquery provides
+type Output = (String, Option<DataType>);
+
+/// Tables an expression can reference, by the name that qualifies them
+struct Scope<'s> {
+    tables: Vec<(String, Vec<Output>)>,
+    /// False when one of the tables does not exist, so a column that does not
+    /// resolve is not worth another error
+    complete: bool,
+    /// The query a subquery appears in, whose columns it can read when none
+    /// of its own tables has them
+    outer: Option<&'s Scope<'s>>,
+}
+
+impl<'s> Scope<'s> {
+    fn new(outer: Option<&'s Scope<'s>>) -> Self {
+        Self {
+            tables: Vec::new(),
+            complete: true,
+            outer,
+        }
+    }
+
+    /// Types of the columns of this scope's own tables a (possibly
+    /// qualified) name can refer to
+    fn local(
+        &self,
+        table: &Option<Spanned<String>>,
+        name: &Spanned<String>,
+    ) -> Vec<Option<DataType>> {
+        self.tables
+            .iter()
+            .filter(|(qualifier, _)| table.as_ref().is_none_or(|t| t.node == *qualifier))
+            .flat_map(|(_, columns)| columns.iter().filter(|(column, _)| *column == name.node))
+            .map(|(_, ty)| *ty)
+            .collect()
+    }
+
+    /// Like [`Scope::local`], falling back to the enclosing queries
+    fn lookup(
+        &self,
+        table: &Option<Spanned<String>>,
+        name: &Spanned<String>,
+    ) -> Vec<Option<DataType>> {
+        let matches = self.local(table, name);
+        match (matches.is_empty(), self.outer) {
+            (true, Some(outer)) => outer.lookup(table, name),
+            _ => matches,
+        }
+    }
+
+    /// Whether every table of this scope and the enclosing ones exists
+    fn is_complete(&self) -> bool {
+        self.complete && self.outer.is_none_or(Scope::is_complete)
+    }
+}
+
+pub(crate) struct Binder<'a> {
+    tables: &'a HashMap<String, Table>,
+    functions: &'a FunctionRegistry,
+    /// Common table expressions in scope, the innermost last
+    ctes: Vec<(String, Vec<Output>)>,
+    /// Type expected by each parameter of a prepared statement
+    parameters: Vec<Option<SqlTypeInfo>>,
+    errors: Vec<ExecutionError>,
+}
+
+impl<'a> Binder<'a> {
+    pub fn new(tables: &'a HashMap<String, Table>, functions: &'a FunctionRegistry) -> Self {
+        Self {
+            tables,
+            functions,
+            ctes: Vec::new(),
+            parameters: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Check a statement, on success returns the type of each column a select,
+    /// the `RETURNING` list of an insert or the query of `CREATE TABLE ... AS`
+    /// outputs, `None` when it is only known at run time
+    pub fn bind(mut self, query: &SqlQuery) -> Result<Vec<Option<DataType>>, ExecutionError> {
+        self.check(query)
+    }
+
+    /// Check a statement whose placeholders are numbered up to `count`, on
+    /// success returns the type each parameter expects, `None` when nothing
+    /// in the statement constrains it
+    pub fn parameters(
+        mut self,
+        query: &SqlQuery,
+        count: usize,
+    ) -> Result<Vec<Option<SqlTypeInfo>>, ExecutionError> {
+        self.parameters = vec![None; count];
+        self.check(query)?;
+        Ok(self.parameters)
+    }
+
+    fn check(&mut self, query: &SqlQuery) -> Result<Vec<Option<DataType>>, ExecutionError> {
+        let output = match query {
+            SqlQuery::Select(select) => self
+                .select(select, None)
+                .into_iter()
+                .map(|(_, ty)| ty)
+                .collect(),
+            SqlQuery::Insert(insert) => self.insert(insert).into_iter().map(|(_, ty)| ty).collect(),
+            SqlQuery::Create(CreateStatement {
+                table,
+                query: Some(query),
+                ..
+            }) => self.create_as(table, query),
+            SqlQuery::Create(create) => {
+                self.create(create);
+                Vec::new()
+            }
+            SqlQuery::Set(_) => Vec::new(),
+        };
+
+        match self.errors.len() {
+            0 => Ok(output),
+            1 => Err(self.errors.remove(0)),
+            _ => Err(ExecutionError::Semantic(std::mem::take(&mut self.errors))),
+        }
+    }
+
+    fn table(&mut self, table: &TableRef, scope: &mut Scope<'_>) {
+        let cte = |name: &Spanned<String>| {
+            self.ctes
+                .iter()
+                .rev()
+                .find(|(cte, _)| *cte == name.node)
+                .map(|(_, columns)| columns.clone())
+        };
+
+        // an alias takes the place of the name for the columns
+        let qualifier = table.name().node.clone();
+        match table {
+            TableRef::Table { name, .. } => match cte(name) {
+                Some(columns) => scope.tables.push((qualifier, columns)),
+                None => self.stored(name, qualifier, scope),
+            },
+            // a derived table can not read the tables next to it
+            TableRef::Subquery { query, alias } => {
+                let columns = self.select(query, None);
+                scope.tables.push((alias.node.clone(), columns));
+            }
+        }
+    }
+
+    fn stored(&mut self, name: &Spanned<String>, qualifier: String, scope: &mut Scope<'_>) {
+        match self.tables.get(&name.node) {
+            Some(table) => {
+                let columns = table
+                    .columns()
+                    .iter()
+                    .map(|c| (c.name.node.clone(), Some(c.type_info.into())))
+                    .collect();
+                scope.tables.push((qualifier, columns));
+            }
+            None => {
+                scope.complete = false;
+                self.errors.push(ExecutionError::TableNotFound(
+                    name.to_string(),
+                    name.span.into(),
+                ));
+            }
+        }
+    }
+
+    /// Bring the common table expressions of a `WITH` in scope, returns how
+    /// many were added
+    fn with(&mut self, with: &With) -> usize {
+        for cte in &with.ctes {
+            let step = with.recursive.then(|| recursive_step(&cte.query)).flatten();
+            let mut columns = match &step {
+                Some((anchor, _)) => self.select(anchor, None),
+                None => self.select(&cte.query, None),
+            };
+            if !cte.columns.is_empty() {
+                if cte.columns.len() == columns.len() {
+                    for ((name, _), renamed) in columns.iter_mut().zip(&cte.columns) {
+                        name.clone_from(&renamed.node);
+                    }
+                } else {
+                    self.errors.push(ExecutionError::CteColumnCount(
+                        cte.name.to_string(),
+                        cte.columns.len(),
+                        columns.len(),
+                        names_span(&cte.columns).into(),
+                    ));
+                }
+            }
+
+            // the recursive select reads the rows of the ones before it
+            if let Some((_, union)) = step {
+                self.ctes.push((cte.name.node.clone(), columns.clone()));
+                let rest = self.select_body(&union.query, None);
+                self.ctes.pop();
+                self.set_operation(&union.op.to_string(), &columns, &rest, cte.name.span);
+            }
+
+            self.ctes.push((cte.name.node.clone(), columns));
+        }
+        with.ctes.len()
+    }
+
+    /// The queries combined by a set operation return as many columns, of
+    /// the same types
+    fn set_operation(&mut self, op: &str, left: &[Output], right: &[Output], span: Span) {
+        if left.len() != right.len() {
+            self.errors.push(ExecutionError::SetOperationColumnCount(
+                op.to_string(),
+                left.len(),
+                right.len(),
+            ));
+            return;
+        }
+        for (index, ((_, l), (_, r))) in left.iter().zip(right).enumerate() {
+            if let (Some(l), Some(r)) = (l, r) {
+                if l != r {
+                    self.errors.push(ExecutionError::WrongType(
+                        format!(
+                            "column {} of {op} is {l} on one side and {r} on the other",
+                            index + 1
+                        ),
+                        span.into(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Check a select, nested in the query of `outer` if it is a subquery,
+    /// and return the columns it outputs
+    fn select(&mut self, select: &SelectStatement, outer: Option<&Scope<'_>>) -> Vec<Output> {
+        let ctes = select.with.as_ref().map_or(0, |with| self.with(with));
+        let output = match select.compound.is_empty() {
+            true => self.select_body(select, outer),
+            false => self.compound(select, outer),
+        };
+        self.ctes.truncate(self.ctes.len() - ctes);
+        output
+    }
+
+    /// A select with set operations, its `ORDER BY` can only read the
+    /// columns of the combined rows
+    fn compound(&mut self, select: &SelectStatement, outer: Option<&Scope<'_>>) -> Vec<Output> {
+        let first = SelectStatement {
+            with: None,
+            compound: vec![],
+            order_by: vec![],
+            limit: None,
+            offset: None,
+            ..select.clone()
+        };
+        let output = self.select_body(&first, outer);
+        for operation in &select.compound {
+            let columns = self.select_body(&operation.query, outer);
+            self.set_operation(
+                &operation.op.to_string(),
+                &output,
+                &columns,
+                Span::default(),
+            );
+        }
+
+        let mut scope = Scope::new(None);
+        scope.tables.push((String::new(), output.clone()));
+        for order in &select.order_by {
+            match position(&order.expr) {
+                Some(n) if n == 0 || n > output.len() => {
+                    self.errors.push(ExecutionError::PositionNotInSelectList(n))
+                }
+                Some(_) => {}
+                None => {
+                    self.expression(&order.expr, &scope, Place::Row);
+                }
+            }
+        }
+        output
+    }
+
+    fn select_body(&mut self, select: &SelectStatement, outer: Option<&Scope<'_>>) -> Vec<Output> {
+        let mut scope = Scope::new(outer);
+        self.table(&select.table, &mut scope);
+        for join in &select.joins {
+            self.table(&join.table, &mut scope);
+            let on = self.expression(&join.on, &scope, Place::Row);
+            self.condition("JOIN", &join.on, on);
+        }
+
+        if let Some(filter) = &select.filter {
+            let ty = self.expression(filter, &scope, Place::Row);
+            self.condition("WHERE", filter, ty);
+        }
+        for expr in &select.group_by {
+            self.expression(expr, &scope, Place::Row);
+        }
+
+        let items: Vec<&SelectItem> = select.fields.iter().collect();
+        let distinct_on = match &select.distinct {
+            Some(Distinct::On(exprs)) => exprs.as_slice(),
+            _ => &[],
+        };
+        let has_aggregate = |expr| has_aggregate(self.functions, expr);
+        let grouped = !select.group_by.is_empty()
+            || items.iter().any(|item| match item {
+                SelectItem::Wildcard(_) => false,
+                SelectItem::Expression { expr, .. } => has_aggregate(expr),
+            })
+            || select
+                .order_by
+                .iter()
+                .any(|order| has_aggregate(&order.expr))
+            || distinct_on.iter().any(has_aggregate);
+
+        let mut output = Vec::new();
+        for item in &items {
+            match item {
+                SelectItem::Wildcard(star) => {
+                    if grouped {
+                        self.errors.push(ExecutionError::ColumnNotGrouped(
+                            "*".to_string(),
+                            star.span.into(),
+                        ));
+                    }
+                    for (_, columns) in &scope.tables {
+                        output.extend(columns.iter().cloned());
+                    }
+                }
+                SelectItem::Expression { expr, alias } => {
+                    let ty = self.output(expr, &scope, grouped.then_some(&select.group_by));
+                    output.push((output_field(expr, alias.as_deref()).name, ty));
+                }
+            }
+        }
+
+        // an alias or a position was already checked in the select list
+        let wildcard: Vec<Expression> = scope
+            .tables
+            .iter()
+            .flat_map(|(table, columns)| {
+                columns.iter().map(|(name, _)| Expression::Column {
+                    table: Some(table.as_str().into()),
+                    name: name.as_str().into(),
+                })
+            })
+            .collect();
+        let resolve = |expr: &Expression| {
+            resolve_output(expr, &items, &wildcard).unwrap_or_else(|_| expr.clone())
+        };
+        for expr in distinct_on
+            .iter()
+            .chain(select.order_by.iter().map(|o| &o.expr))
+        {
+            match resolve_output(expr, &items, &wildcard) {
+                Ok(resolved) if resolved == *expr => {
+                    self.output(expr, &scope, grouped.then_some(&select.group_by));
+                }
+                Ok(_) => {}
+                Err(error) => self.errors.push(error),
+            }
+        }
+
+        // the first row of each DISTINCT ON key is only well defined when
+        // the sort starts with the key
+        let resolved: Vec<Expression> = distinct_on.iter().map(resolve).collect();
+        let sorted_first = select
+            .order_by
+            .iter()
+            .take(distinct_on.len())
+            .all(|order| resolved.contains(&resolve(&order.expr)));
+        if !sorted_first {
+            self.errors.push(ExecutionError::DistinctOnOrder);
+        }
+
+        output
+    }
+
+    /// An expression of the select list or `ORDER BY`, which can only use
+    /// grouped columns when the query aggregates
+    fn output(
+        &mut self,
+        expr: &Expression,
+        scope: &Scope<'_>,
+        group_by: Option<&Vec<Expression>>,
+    ) -> Option<DataType> {
+        let errors = self.errors.len();
+        let ty = self.expression(expr, scope, Place::Output);
+        if let (Some(group_by), true) = (group_by, self.errors.len() == errors) {
+            self.grouped(expr, scope, group_by);
+        }
+        ty
+    }
+
+    fn grouped(&mut self, expr: &Expression, scope: &Scope<'_>, group_by: &[Expression]) {
+        if group_by.contains(expr) {
+            return;
+        }
+        match expr {
+            // a column that does not resolve was reported already, if at all
+            Expression::Column { table, name } if !scope.local(table, name).is_empty() => {
+                self.errors.push(ExecutionError::ColumnNotGrouped(
+                    display_name(table, name),
+                    column_span(table, name).into(),
+                ));
+            }
+            Expression::Function { name, .. } if self.functions.aggregate(name).is_some() => {}
+            Expression::Function { args, .. } => {
+                for arg in args {
+                    self.grouped(arg, scope, group_by);
+                }
+            }
+            Expression::Window { args, over, .. } => {
+                let keys = over.order_by.iter().map(|order| &order.expr);
+                for expr in args.iter().chain(&over.partition_by).chain(keys) {
+                    self.grouped(expr, scope, group_by);
+                }
+            }
+            Expression::Binary { left, right, .. } => {
+                self.grouped(left, scope, group_by);
+                self.grouped(right, scope, group_by);
+            }
+            Expression::Unary { operand, .. } => self.grouped(operand, scope, group_by),
+            // a subquery is checked on its own, only what it is compared with
+            // is evaluated on the group
+            Expression::InSubquery { expr, .. } => self.grouped(expr, scope, group_by),
+            Expression::InList { expr, list, .. } => {
+                for expr in std::iter::once(&**expr).chain(list) {
+                    self.grouped(expr, scope, group_by);
+                }
+            }
+            Expression::Between {
+                expr, low, high, ..
+            } => {
+                for expr in [expr, low, high] {
+                    self.grouped(expr, scope, group_by);
+                }
+            }
+            Expression::Like {
+                expr,
+                pattern,
+                escape,
+                ..
+            } => {
+                for expr in [expr, pattern].into_iter().chain(escape) {
+                    self.grouped(expr, scope, group_by);
+                }
+            }
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                let branches = branches.iter().flat_map(|(when, then)| [when, then]);
+                for expr in operand.iter().map(|o| &**o).chain(branches) {
+                    self.grouped(expr, scope, group_by);
+                }
+                if let Some(else_result) = else_result {
+                    self.grouped(else_result, scope, group_by);
+                }
+            }
+            Expression::Cast { expr, .. } => self.grouped(expr, scope, group_by),
+            Expression::Subquery(_) | Expression::Exists(_) => {}
+            Expression::Column { .. }
+            | Expression::Literal(_)
+            | Expression::Parameter(_)
+            | Expression::Wildcard => {}
+        }
+    }
+
+    /// Check an insert and return the columns its `RETURNING` list outputs
+    fn insert(&mut self, insert: &InsertStatement) -> Vec<Output> {
+        let table = self.tables.get(&insert.table.node);
+        if table.is_none() {
+            self.errors.push(ExecutionError::TableNotFound(
+                insert.table.to_string(),
+                insert.table.span.into(),
+            ));
+        }
+        let columns = table.map_or(&[][..], |table| table.columns());
+
+        // a column that does not resolve has no type to check the value with
+        let mut targets = Vec::new();
+        for (index, name) in insert.columns.iter().enumerate() {
+            if insert.columns[..index].iter().any(|c| c.node == name.node) {
+                self.errors.push(ExecutionError::DuplicateColumn(
+                    name.to_string(),
+                    name.span.into(),
+                ));
+            }
+            let column = columns.iter().find(|column| column.name.node == name.node);
+            if column.is_none() && table.is_some() {
+                self.errors.push(ExecutionError::ColumnDoesNotExists(
+                    name.to_string(),
+                    name.span.into(),
+                ));
+            }
+            targets.push(column);
+        }
+        if insert.columns.is_empty() {
+            targets = columns.iter().map(Some).collect();
+        }
+
+        let values: Vec<(Span, Option<DataType>)> = match &insert.source {
+            // values are computed without any row to read from
+            InsertSource::Values(values) => {
+                let scope = Scope::new(None);
+                values
+                    .iter()
+                    .map(|value| {
+                        let ty = self.expression(value, &scope, Place::Row);
+                        (expression_span(value), ty)
+                    })
+                    .collect()
+            }
+            InsertSource::Select(select) => {
+                let spans = output_spans(select);
+                self.select(select, None)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (_, ty))| (spans.get(index).copied().unwrap_or_default(), ty))
+                    .collect()
+            }
+        };
+        // the table stands for its columns when none are listed
+        let targets_span = match insert.columns.is_empty() {
+            true => insert.table.span,
+            false => names_span(&insert.columns),
+        };
+        // columns left out are NULL, unless they were listed
+        let count = values.len() > targets.len()
+            || (!insert.columns.is_empty() && values.len() < targets.len());
+        if table.is_some() && count {
+            self.errors.push(ExecutionError::InsertColumnCount(
+                targets.len(),
+                values.len(),
+                targets_span.into(),
+            ));
+        }
+
+        if let (Some(table), Some(on_conflict)) = (table, &insert.on_conflict) {
+            self.on_conflict(&insert.table, table, on_conflict);
+        }
+
+        // a placeholder takes the type of the column it goes to, those of a
+        // select up to its first wildcard
+        let placeholders: Vec<&Expression> = match &insert.source {
+            InsertSource::Values(values) => values.iter().collect(),
+            InsertSource::Select(select) => select
+                .fields
+                .iter()
+                .map_while(|item| match item {
+                    SelectItem::Expression { expr, .. } => Some(expr),
+                    SelectItem::Wildcard(_) => None,
+                })
+                .collect(),
+        };
+        for (value, column) in placeholders.into_iter().zip(&targets) {
+            self.expect(value, column.map(|column| column.type_info.into()));
+        }
+
+        // a value without a span, like a column a `*` stands for, is pointed at
+        // by its column
+        for (index, (column, (span, ty))) in targets.into_iter().zip(values).enumerate() {
+            if let (Some(column), Some(ty)) = (column, ty) {
+                if ty != column.type_info.into() {
+                    let message =
+                        format!("column {} is {}, found {ty}", column.name, column.type_info);
+                    let span = match (span.len, insert.columns.get(index)) {
+                        (0, Some(name)) => name.span,
+                        (0, None) => insert.table.span,
+                        _ => span,
+                    };
+                    self.errors
+                        .push(ExecutionError::WrongType(message, span.into()));
+                }
+            }
+        }
+
+        // computed from each stored row, only once the table is known
+        if table.is_none() {
+            return Vec::new();
+        }
+        let mut scope = Scope::new(None);
+        scope.tables.push((
+            insert.table.node.clone(),
+            columns
+                .iter()
+                .map(|column| (column.name.node.clone(), Some(column.type_info.into())))
+                .collect(),
+        ));
+        let mut output = Vec::new();
+        for item in &insert.returning {
+            match item {
+                SelectItem::Wildcard(_) => output.extend(scope.tables[0].1.iter().cloned()),
+                SelectItem::Expression { expr, alias } => {
+                    let ty = self.expression(expr, &scope, Place::Row);
+                    output.push((output_field(expr, alias.as_deref()).name, ty));
+                }
+            }
+        }
+        output
+    }
+
+    /// Columns of `CREATE TABLE`, the keys can only use them and there is at
+    /// most one primary key
+    fn create(&mut self, create: &CreateStatement) {
+        for (index, column) in create.columns.iter().enumerate() {
+            if create.columns[..index]
+                .iter()
+                .any(|c| c.name == column.name)
+            {
+                self.errors.push(ExecutionError::DuplicateColumn(
+                    column.name.to_string(),
+                    column.name.span.into(),
+                ));
+            }
+        }
+        let mut primary = false;
+        for key in &create.keys {
+            if key.kind == KeyKind::Primary {
+                if primary {
+                    self.errors.push(ExecutionError::MultiplePrimaryKeys(
+                        create.table.to_string(),
+                        names_span(&key.columns).into(),
+                    ));
+                }
+                primary = true;
+            }
+            for name in &key.columns {
+                if !create
+                    .columns
+                    .iter()
+                    .any(|column| column.name.node == name.node)
+                {
+                    self.errors.push(ExecutionError::ColumnDoesNotExists(
+                        name.to_string(),
+                        name.span.into(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// The key `ON CONFLICT` names has to be one of the table, and `DO UPDATE`
+    /// values are computed from the row in the table and the `excluded` one
+    fn on_conflict(&mut self, name: &Spanned<String>, table: &Table, on_conflict: &OnConflict) {
+        let columns = table.columns();
+        let mut target = Vec::new();
+        let mut resolved = true;
+        for (index, name) in on_conflict.target.iter().enumerate() {
+            if on_conflict.target[..index].contains(name) {
+                self.errors.push(ExecutionError::DuplicateColumn(
+                    name.to_string(),
+                    name.span.into(),
+                ));
+            }
+            match columns
+                .iter()
+                .position(|column| column.name.node == name.node)
+            {
+                Some(position) => target.push(position),
+                None => {
+                    resolved = false;
+                    self.errors.push(ExecutionError::ColumnDoesNotExists(
+                        name.to_string(),
+                        name.span.into(),
+                    ));
+                }
+            }
+        }
+        target.sort_unstable();
+        target.dedup();
+        let matches = |key: &[usize]| {
+            let mut key = key.to_vec();
+            key.sort_unstable();
+            key == target
+        };
+        // a column that does not exist was reported already
+        if resolved && !target.is_empty() && !table.keys().any(|(_, key)| matches(key)) {
+            self.errors.push(ExecutionError::NoConflictKey(
+                names_span(&on_conflict.target).into(),
+            ));
+        }
+
+        let ConflictAction::Update(assignments) = &on_conflict.action else {
+            return;
+        };
+        let outputs: Vec<Output> = columns
+            .iter()
+            .map(|column| (column.name.node.clone(), Some(column.type_info.into())))
+            .collect();
+        let mut excluded = Scope::new(None);
+        excluded
+            .tables
+            .push(("excluded".to_string(), outputs.clone()));
+        let mut scope = Scope::new(Some(&excluded));
+        scope.tables.push((name.node.clone(), outputs));
+        for (index, assignment) in assignments.iter().enumerate() {
+            let column = &assignment.column;
+            if assignments[..index]
+                .iter()
+                .any(|other| other.column.node == column.node)
+            {
+                self.errors.push(ExecutionError::DuplicateColumn(
+                    column.to_string(),
+                    column.span.into(),
+                ));
+            }
+            let ty = self.expression(&assignment.value, &scope, Place::Row);
+            match columns.iter().find(|c| c.name.node == column.node) {
+                Some(target) => {
+                    if let Some(ty) = ty.filter(|ty| *ty != target.type_info.into()) {
+                        self.mismatch(
+                            format!("column {} is {}, found {ty}", target.name, target.type_info),
+                            &assignment.value,
+                        );
+                    }
+                }
+                None => self.errors.push(ExecutionError::ColumnDoesNotExists(
+                    column.to_string(),
+                    column.span.into(),
+                )),
+            }
+        }
+    }
+
+    /// The query of `CREATE TABLE ... AS`, whose output columns become those
+    /// of the table so they need distinct names and a type a column can hold
+    fn create_as(
+        &mut self,
+        table: &Spanned<String>,
+        query: &SelectStatement,
+    ) -> Vec<Option<DataType>> {
+        let output = self.select(query, None);
+        let spans = output_spans(query);
+        for (index, (name, ty)) in output.iter().enumerate() {
+            if output[..index].iter().any(|(other, _)| other == name) {
+                let span = spans
+                    .get(index)
+                    .copied()
+                    .filter(|span| span.len > 0)
+                    .unwrap_or(table.span);
+                self.errors
+                    .push(ExecutionError::DuplicateColumn(name.clone(), span.into()));
+            }
+            let reason = match ty {
+                Some(ty) if sql_type(*ty).is_some() => continue,
+                Some(ty) => format!("{ty} values can not be stored"),
+                None => "its values have no type, CAST them".to_string(),
+            };
+            self.errors
+                .push(ExecutionError::ColumnType(name.clone(), reason));
+        }
+        output.into_iter().map(|(_, ty)| ty).collect()
+    }
+
+    /// `WHERE` and `ON` have to be conditions
+    fn condition(&mut self, clause: &str, expr: &Expression, ty: Option<DataType>) {
+        if let Some(ty) = ty.filter(|ty| *ty != DataType::Boolean) {
+            self.mismatch(
+                format!("the {clause} condition must be BOOLEAN, found {ty}"),
+                expr,
+            );
+        }
+    }
+
+    fn mismatch(&mut self, message: String, expr: &Expression) {
+        self.errors.push(ExecutionError::WrongType(
+            message,
+            expression_span(expr).into(),
+        ));
+    }
+
+    /// Check an operand is of `expected` type, if its type is known
+    fn operand(
+        &mut self,
+        op: impl std::fmt::Display,
+        expr: &Expression,
+        ty: Option<DataType>,
+        expected: DataType,
+    ) {
+        self.expect(expr, Some(expected));
+        if let Some(ty) = ty.filter(|ty| *ty != expected) {
+            self.mismatch(format!("{op} expects {expected}, found {ty}"), expr);
+        }
+    }
+
+    /// A parameter takes the first type it is compared with or has to be of,
+    /// booleans can not be bound
+    fn expect(&mut self, expr: &Expression, ty: Option<DataType>) {
+        if let Expression::Parameter(Spanned {
+            node: Parameter::Positional(n),
+            ..
+        }) = expr
+        {
+            let parameter = n.checked_sub(1).and_then(|n| self.parameters.get_mut(n));
+            if let Some(parameter @ None) = parameter {
+                *parameter = ty.and_then(sql_type);
+            }
+        }
+    }
+
+    fn expression(
+        &mut self,
+        expr: &Expression,
+        scope: &Scope<'_>,
+        place: Place,
+    ) -> Option<DataType> {
+        match expr {
+            Expression::Literal(value) => match value.node {
+                Value::Number(_) => Some(DataType::Int),
+                Value::String(_) => Some(DataType::String),
+                Value::Boolean(_) => Some(DataType::Boolean),
+                Value::Null => None,
+            },
+            // the type comes with the value bound later
+            Expression::Parameter(_) | Expression::Wildcard => None,
+            Expression::Column { table, name } => self.column(table, name, scope),
+            Expression::Binary { left, op, right } => {
+                let left_ty = self.expression(left, scope, place);
+                let right_ty = self.expression(right, scope, place);
+                match op {
+                    BinaryOperator::And | BinaryOperator::Or => {
+                        self.operand(op, left, left_ty, DataType::Boolean);
+                        self.operand(op, right, right_ty, DataType::Boolean);
+                        Some(DataType::Boolean)
+                    }
+                    BinaryOperator::Eq
+                    | BinaryOperator::NotEq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq => {
+                        self.expect(left, right_ty);
+                        self.expect(right, left_ty);
+                        self.comparable(expr, left_ty, right_ty);
+                        Some(DataType::Boolean)
+                    }
+                    BinaryOperator::Plus
+                    | BinaryOperator::Minus
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulo => {
+                        self.operand(op, left, left_ty, DataType::Int);
+                        self.operand(op, right, right_ty, DataType::Int);
+                        Some(DataType::Int)
+                    }
+                    BinaryOperator::Concat => {
+                        self.operand(op, left, left_ty, DataType::String);
+                        self.operand(op, right, right_ty, DataType::String);
+                        Some(DataType::String)
+                    }
+                }
+            }
+            Expression::Unary { op, operand } => {
+                let ty = self.expression(operand, scope, place);
+                let expected = match op {
+                    UnaryOperator::Not => DataType::Boolean,
+                    UnaryOperator::Minus => DataType::Int,
+                };
+                self.operand(op, operand, ty, expected);
+                Some(expected)
+            }
+            Expression::Function { name, args } => self.function(name, args, scope, place),
+            Expression::Window { name, args, over } => self.window(name, args, over, scope, place),
+            Expression::Subquery(select) => self.subquery(select, scope),
+            Expression::Exists(select) => {
+                self.select(select, Some(scope));
+                Some(DataType::Boolean)
+            }
+            Expression::InSubquery { expr, subquery, .. } => {
+                let ty = self.expression(expr, scope, place);
+                let subquery_ty = self.subquery(subquery, scope);
+                self.expect(expr, subquery_ty);
+                self.comparable(expr, ty, subquery_ty);
+                Some(DataType::Boolean)
+            }
+            Expression::InList { expr, list, .. } => {
+                let ty = self.expression(expr, scope, place);
+                for item in list {
+                    let item_ty = self.expression(item, scope, place);
+                    self.expect(item, ty);
+                    self.expect(expr, item_ty);
+                    self.comparable(item, ty, item_ty);
+                }
+                Some(DataType::Boolean)
+            }
+            Expression::Between {
+                expr, low, high, ..
+            } => {
+                let ty = self.expression(expr, scope, place);
+                for end in [low, high] {
+                    let end_ty = self.expression(end, scope, place);
+                    self.expect(end, ty);
+                    self.expect(expr, end_ty);
+                    self.comparable(end, ty, end_ty);
+                }
+                Some(DataType::Boolean)
+            }
+            Expression::Like {
+                expr,
+                pattern,
+                escape,
+                case_insensitive,
+                ..
+            } => {
+                let op = if *case_insensitive { "ILIKE" } else { "LIKE" };
+                for operand in [expr, pattern].into_iter().chain(escape) {
+                    let ty = self.expression(operand, scope, place);
+                    self.operand(op, operand, ty, DataType::String);
+                }
+                Some(DataType::Boolean)
+            }
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                let operand = operand
+                    .as_ref()
+                    .map(|operand| (operand, self.expression(operand, scope, place)));
+                let mut results = Vec::new();
+                for (when, then) in branches {
+                    let ty = self.expression(when, scope, place);
+                    match operand {
+                        Some((operand, operand_ty)) => {
+                            self.expect(when, operand_ty);
+                            self.expect(operand, ty);
+                            self.comparable(when, operand_ty, ty);
+                        }
+                        None => self.operand("WHEN", when, ty, DataType::Boolean),
+                    }
+                    results.push((then, self.expression(then, scope, place)));
+                }
+                if let Some(else_result) = else_result {
+                    results.push((else_result, self.expression(else_result, scope, place)));
+                }
+                self.common_type("CASE", results)
+            }
+            Expression::Cast { expr, type_info } => {
+                self.expression(expr, scope, place);
+                Some((*type_info).into())
+            }
+        }
+    }
+
+    /// The type of the values an expression picks from, they all have to
+    /// be of the same one
+    fn common_type<'e>(
+        &mut self,
+        what: &str,
+        values: impl IntoIterator<Item = (&'e Expression, Option<DataType>)>,
+    ) -> Option<DataType> {
+        let mut common = None;
+        for (expr, ty) in values {
+            match (common, ty) {
+                (Some(common), Some(ty)) if common != ty => self.mismatch(
+                    format!("{what} expects values of the same type, found {common} and {ty}"),
+                    expr,
+                ),
+                (None, ty) => common = ty,
+                _ => {}
+            }
+        }
+        common
+    }
+
+    /// A call of a function of the registry, whose arguments have to be of
+    /// the declared types
+    fn scalar(
+        &mut self,
+        function: &ScalarFunction,
+        name: &Spanned<String>,
+        args: &[Expression],
+        scope: &Scope<'_>,
+        place: Place,
+    ) -> Option<DataType> {
+        if !function.accepts(args.len()) {
+            self.errors.push(ExecutionError::WrongArgumentCount(
+                name.to_string(),
+                function.arity(),
+                name.span.into(),
+            ));
+        }
+        for (index, arg) in args.iter().enumerate() {
+            let ty = self.expression(arg, scope, place);
+            if let Some(expected) = function.arguments().get(index) {
+                self.operand(name, arg, ty, *expected);
+            }
+        }
+        Some(function.return_type())
+    }
+
+    /// `COALESCE` and `NULLIF`, which evaluate to one of their arguments
+    fn conditional(
+        &mut self,
+        name: &Spanned<String>,
+        args: &[Expression],
+        scope: &Scope<'_>,
+        place: Place,
+    ) -> Option<DataType> {
+        let types: Vec<_> = args
+            .iter()
+            .map(|arg| (arg, self.expression(arg, scope, place)))
+            .collect();
+        match (name.as_str(), types.as_slice()) {
+            ("coalesce", [_, ..]) => self.common_type(name, types),
+            ("nullif", [(left, left_ty), (right, right_ty)]) => {
+                self.expect(left, *right_ty);
+                self.expect(right, *left_ty);
+                self.comparable(right, *left_ty, *right_ty);
+                *left_ty
+            }
+            _ => {
+                self.errors.push(ExecutionError::WrongArgumentCount(
+                    name.to_string(),
+                    conditional_arguments(name).unwrap_or_default().to_string(),
+                    name.span.into(),
+                ));
+                None
+            }
+        }
+    }
+
+    /// Values compared with each other have to be of the same type, if
+    /// both are known
+    fn comparable(&mut self, expr: &Expression, left: Option<DataType>, right: Option<DataType>) {
+        if let (Some(l), Some(r)) = (left, right) {
+            if l != r {
+                self.mismatch(format!("cannot compare {l} with {r}"), expr);
+            }
+        }
+    }
+
+    /// A subquery used as a value, which has to output a single column
+    fn subquery(&mut self, select: &SelectStatement, scope: &Scope<'_>) -> Option<DataType> {
+        match self.select(select, Some(scope)).as_slice() {
+            [(_, ty)] => *ty,
+            columns => {
+                self.errors
+                    .push(ExecutionError::SubqueryColumnCount(columns.len()));
+                None
+            }
+        }
+    }
+
+    fn column(
+        &mut self,
+        table: &Option<Spanned<String>>,
+        name: &Spanned<String>,
+        scope: &Scope<'_>,
+    ) -> Option<DataType> {
+        let error = match scope.lookup(table, name).as_slice() {
+            [ty] => return *ty,
+            [_, _, ..] => ExecutionError::AmbiguousColumn,
+            [] if !scope.is_complete() => return None,
+            [] => ExecutionError::ColumnDoesNotExists,
+        };
+        self.errors.push(error(
+            display_name(table, name),
+            column_span(table, name).into(),
+        ));
+        None
+    }
+
+    fn function(
+        &mut self,
+        name: &Spanned<String>,
+        args: &[Expression],
+        scope: &Scope<'_>,
+        place: Place,
+    ) -> Option<DataType> {
+        if conditional_arguments(name).is_some() {
+            return self.conditional(name, args, scope, place);
+        }
+        if WindowFunction::from_name(name).is_some() {
+            self.errors.push(ExecutionError::WindowRequired(
+                name.to_string(),
+                name.span.into(),
+            ));
+            for arg in args {
+                self.expression(arg, scope, place);
+            }
+            return None;
+        }
+        let Some(function) = self.functions.aggregate(name) else {
+            if let Some(function) = self.functions.scalar(name) {
+                return self.scalar(function, name, args, scope, place);
+            }
+            self.errors.push(ExecutionError::FunctionNotFound(
+                name.to_string(),
+                name.span.into(),
+            ));
+            for arg in args {
+                self.expression(arg, scope, place);
+            }
+            return None;
+        };
+
+        if place == Place::Row {
+            self.errors.push(ExecutionError::AggregateNotAllowed(
+                name.to_string(),
+                name.span.into(),
+            ));
+        }
+        // an aggregate inside another is not allowed either
+        self.aggregate(function, name, args, scope, Place::Row)
+    }
+
+    /// A call of an aggregate, whose argument appears at `place`
+    fn aggregate(
+        &mut self,
+        function: AggregateFunction,
+        name: &Spanned<String>,
+        args: &[Expression],
+        scope: &Scope<'_>,
+        place: Place,
+    ) -> Option<DataType> {
+        let arg = match (&function, args) {
+            (AggregateFunction::Count, [Expression::Wildcard]) => None,
+            (_, [arg]) if *arg != Expression::Wildcard => {
+                let ty = self.expression(arg, scope, place);
+                match &function {
+                    AggregateFunction::Sum | AggregateFunction::Avg => {
+                        self.operand(name, arg, ty, DataType::Int)
+                    }
+                    AggregateFunction::User(function) => {
+                        self.operand(name, arg, ty, function.argument())
+                    }
+                    _ => {}
+                }
+                ty
+            }
+            _ => {
+                let expected = match function {
+                    AggregateFunction::Count => "one argument or *",
+                    _ => "one argument",
+                };
+                self.errors.push(ExecutionError::WrongArgumentCount(
+                    name.to_string(),
+                    expected.to_string(),
+                    name.span.into(),
+                ));
+                None
+            }
+        };
+
+        match function {
+            AggregateFunction::Count | AggregateFunction::Sum | AggregateFunction::Avg => {
+                Some(DataType::Int)
+            }
+            AggregateFunction::Min | AggregateFunction::Max => arg,
+            AggregateFunction::User(function) => Some(function.return_type()),
+        }
+    }
+
+    /// A window function or an aggregate computed over a window, allowed in
+    /// the output only
+    fn window(
+        &mut self,
+        name: &Spanned<String>,
+        args: &[Expression],
+        over: &Window,
+        scope: &Scope<'_>,
+        place: Place,
+    ) -> Option<DataType> {
+        if place != Place::Output {
+            self.errors.push(ExecutionError::WindowNotAllowed(
+                name.to_string(),
+                name.span.into(),
+            ));
+        }
+        if let Err(error) = window_frame(over) {
+            self.errors.push(error);
+        }
+        let keys = over.order_by.iter().map(|order| &order.expr);
+        for expr in over.partition_by.iter().chain(keys) {
+            self.expression(expr, scope, Place::Window);
+        }
+
+        let Some(function) = WindowFunction::from_name(name) else {
+            if let Some(function) = self.functions.aggregate(name) {
+                return self.aggregate(function, name, args, scope, Place::Window);
+            }
+            self.errors.push(ExecutionError::FunctionNotFound(
+                name.to_string(),
+                name.span.into(),
+            ));
+            for arg in args {
+                self.expression(arg, scope, Place::Window);
+            }
+            return None;
+        };
+
+        if !function.accepts(args.len()) || args.contains(&Expression::Wildcard) {
+            self.errors.push(ExecutionError::WrongArgumentCount(
+                name.to_string(),
+                function.arity().to_string(),
+                name.span.into(),
+            ));
+        }
+        let types: Vec<_> = args
+            .iter()
+            .map(|arg| (arg, self.expression(arg, scope, Place::Window)))
+            .collect();
+        match (function, types.as_slice()) {
+            (WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank, _) => {
+                Some(DataType::Int)
+            }
+            // the default stands in for the value past the partition
+            (WindowFunction::Lag | WindowFunction::Lead, [value, rest @ ..]) => {
+                if let Some((offset, ty)) = rest.first() {
+                    self.operand(name, offset, *ty, DataType::Int);
+                }
+                self.common_type(name, std::iter::once(*value).chain(rest.get(1).copied()))
+            }
+            (_, types) => types.first().and_then(|(_, ty)| *ty),
+        }
+    }
+}
+
+fn display_name(table: &Option<Spanned<String>>, name: &Spanned<String>) -> String {
+    match table {
+        Some(table) => format!("{table}.{name}"),
+        None => name.to_string(),
+    }
+}
+
+fn has_aggregate(functions: &FunctionRegistry, expr: &Expression) -> bool {
+    struct HasAggregate<'a>(&'a FunctionRegistry, bool);
+
+    impl Visitor for HasAggregate<'_> {
+        // aggregates of a subquery belong to it
+        fn visit_select(&mut self, _select: &SelectStatement) {}
+
+        fn visit_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::Function { name, .. } if self.0.aggregate(name).is_some() => {
+                    self.1 = true
+                }
+                expr => walk_expression(self, expr),
+            }
+        }
+    }
+
+    let mut visitor = HasAggregate(functions, false);
+    visitor.visit_expression(expr);
+    visitor.1
+}
+
+/// From the first to the last of a list of names
+fn names_span(names: &[Spanned<String>]) -> Span {
+    match (names.first(), names.last()) {
+        (Some(first), Some(last)) => Span {
+            offset: first.span.offset,
+            len: last.span.offset + last.span.len - first.span.offset,
+        },
+        _ => Span::default(),
+    }
+}
+
+/// Span of each column a select outputs, up to its first `*` whose columns
+/// are only known once bound
+fn output_spans(select: &SelectStatement) -> Vec<Span> {
+    select
+        .fields
+        .iter()
+        .map_while(|item| match item {
+            SelectItem::Expression { expr, .. } => Some(expression_span(expr)),
+            SelectItem::Wildcard(_) => None,
+        })
+        .collect()
+}
+
+/// From the first to the last name or literal in the expression, which
+/// covers an operation from its left operand to its right one
+fn expression_span(expr: &Expression) -> Span {
+    struct Names(Option<(usize, usize)>);
+
+    impl Names {
+        fn add(&mut self, span: Span) {
+            if span.len == 0 {
+                return;
+            }
+            let end = span.offset + span.len;
+            self.0 = Some(match self.0 {
+                Some((start, stop)) => (start.min(span.offset), stop.max(end)),
+                None => (span.offset, end),
+            });
+        }
+    }
+
+    impl Visitor for Names {
+        fn visit_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::Function { name, .. } => self.add(name.span),
+                Expression::Literal(value) => self.add(value.span),
+                Expression::Parameter(parameter) => self.add(parameter.span),
+                _ => {}
+            }
+            walk_expression(self, expr)
+        }
+
+        fn visit_column(&mut self, table: Option<&Spanned<String>>, name: &Spanned<String>) {
+            if let Some(table) = table {
+                self.add(table.span);
+            }
+            self.add(name.span);
+        }
+    }
+
+    let mut names = Names(None);
+    names.visit_expression(expr);
+    names.0.map_or_else(Span::default, |(start, end)| Span {
+        offset: start,
+        len: end - start,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::SourceSpan;
+    use sqlmicro_parser::{parse::Parse, Column, TableLayout};
+
+    use super::*;
+
+    fn tables() -> HashMap<String, Table> {
+        let column = |name: &str, type_info| Column {
+            name: name.into(),
+            type_info,
+        };
+        let users = Table::new(
+            vec![
+                column("id", SqlTypeInfo::Int),
+                column("name", SqlTypeInfo::String),
+                column("age", SqlTypeInfo::Int),
+            ],
+            TableLayout::Row,
+        );
+        let posts = Table::new(
+            vec![
+                column("author", SqlTypeInfo::Int),
+                column("title", SqlTypeInfo::String),
+            ],
+            TableLayout::Row,
+        );
+        HashMap::from([("users".to_string(), users), ("posts".to_string(), posts)])
+    }
+
+    fn bind(sql: &str) -> Result<Vec<Option<DataType>>, ExecutionError> {
+        let tables = tables();
+        let query = SqlQuery::parse_format_error(sql).unwrap();
+        Binder::new(&tables, &FunctionRegistry::default()).bind(&query)
+    }
+
+    /// Every error of the statement, as its message and span
+    fn errors(sql: &str) -> Vec<(String, Option<SourceSpan>)> {
+        let describe = |error: &ExecutionError| (error.to_string(), error.span());
+        match bind(sql) {
+            Ok(_) => Vec::new(),
+            Err(ExecutionError::Semantic(errors)) => errors.iter().map(describe).collect(),
+            Err(error) => vec![describe(&error)],
+        }
+    }
+
+    #[test]
+    fn test_output_types() {
+        use DataType::*;
+
+        assert_eq!(
+            bind("select *, id > 1, -age, null from users;").unwrap(),
+            [
+                Some(Int),
+                Some(String),
+                Some(Int),
+                Some(Boolean),
+                Some(Int),
+                None
+            ]
+        );
+        assert_eq!(
+            bind("select count(*), max(name), avg(age) from users;").unwrap(),
+            [Some(Int), Some(String), Some(Int)]
+        );
+        assert_eq!(
+            bind("select title, age + ? from users join posts on id = author where name = $1;")
+                .unwrap(),
+            [Some(String), Some(Int)]
+        );
+        assert!(
+            bind("select name, count(*) as total from users group by name order by total;").is_ok()
+        );
+    }
+
+    #[test]
+    fn test_all_errors() {
+        // with a table missing, columns that do not resolve are not reported
+        let sql = "select missing, name + 1, sum(count(id)) from users \
+                   join nowhere on x where age group by max(id);";
+
+        assert_eq!(
+            errors(sql),
+            [
+                (
+                    "Table nowhere was not found".to_string(),
+                    Some((57, 7).into())
+                ),
+                (
+                    "Type mismatch: the WHERE condition must be BOOLEAN, found INT".to_string(),
+                    Some((76, 3).into())
+                ),
+                (
+                    "Aggregate function max is not allowed here".to_string(),
+                    Some((89, 3).into())
+                ),
+                (
+                    "Type mismatch: + expects INT, found STRING".to_string(),
+                    Some((16, 4).into())
+                ),
+                (
+                    "Aggregate function count is not allowed here".to_string(),
+                    Some((30, 5).into())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        for (sql, span) in [
+            ("select id from users where name = 1;", (27, 8)),
+            ("select id from users where 'x' > 1;", (27, 7)),
+            ("select id from users where -1;", (27, 2)),
+            ("select 1 + 'a' from users;", (11, 3)),
+            ("select id from users where not age;", (31, 3)),
+            ("select id from users join posts on title;", (35, 5)),
+            ("select avg(name) from users;", (11, 4)),
+            (
+                "select id from users join posts on true where users.id = posts.title;",
+                (46, 22),
+            ),
+            ("select id from users where id in (1, name);", (37, 4)),
+            (
+                "select id from users where name between 'a' and age;",
+                (48, 3),
+            ),
+            ("select id from users where age like '3%';", (27, 3)),
+            ("select case when age then 1 end from users;", (17, 3)),
+            (
+                "select case when true then id else name end from users;",
+                (35, 4),
+            ),
+            ("select coalesce(id, name) from users;", (20, 4)),
+            ("select nullif(name, age) from users;", (20, 3)),
+            ("select upper(age) from users;", (13, 3)),
+            ("select name || age from users;", (15, 3)),
+            ("insert into users values 'ana';", (25, 5)),
+            ("insert into users (age, name) values 1, 2;", (40, 1)),
+            ("insert into users (id) select name from users;", (30, 4)),
+        ] {
+            let errors = errors(sql);
+            assert_eq!(errors.len(), 1, "{sql}: {errors:?}");
+            assert!(
+                errors[0].0.starts_with("Type mismatch"),
+                "{sql}: {errors:?}"
+            );
+            assert_eq!(errors[0].1, (span.1 > 0).then(|| span.into()), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_insert_errors() {
+        for (sql, message, span) in [
+            (
+                "insert into users (id, id) values 1, 2;",
+                "Column id is specified more than once",
+                (23, 2),
+            ),
+            (
+                "insert into users (id, name) values 1;",
+                "INSERT has 2 target columns but 1 values",
+                (19, 8),
+            ),
+            (
+                "insert into users values 1, 'a', 2, 3;",
+                "INSERT has 3 target columns but 4 values",
+                (12, 5),
+            ),
+            (
+                "create table t as select id, id from users;",
+                "Column id is specified more than once",
+                (29, 2),
+            ),
+            (
+                "create table t (a int, a string);",
+                "Column a is specified more than once",
+                (23, 1),
+            ),
+            (
+                "select *, count(*) from users;",
+                "Column * must appear in the GROUP BY clause or be used in an aggregate function",
+                (7, 1),
+            ),
+        ] {
+            assert_eq!(
+                errors(sql),
+                [(message.to_string(), Some(span.into()))],
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_conditionals() {
+        use DataType::*;
+
+        assert_eq!(
+            bind(
+                "select cast(id as string), age::int, case when true then 1 end, \
+                 case id when 1 then null else name end, coalesce(null, name), nullif(age, 1) \
+                 from users;"
+            )
+            .unwrap(),
+            [
+                Some(String),
+                Some(Int),
+                Some(Int),
+                Some(String),
+                Some(String),
+                Some(Int)
+            ]
+        );
+        assert!(matches!(
+            bind("select coalesce(), nullif(id) from users;"),
+            Err(ExecutionError::Semantic(errors)) if errors.iter().all(
+                |e| matches!(e, ExecutionError::WrongArgumentCount(..))
+            )
+        ));
+    }
+
+    #[test]
+    fn test_functions() {
+        use DataType::*;
+
+        assert_eq!(
+            bind(
+                "select length(name), upper(name) || 'x', round(age), round(age, 1), now(), \
+                 extract(year from now()) from users;"
+            )
+            .unwrap(),
+            [
+                Some(Int),
+                Some(String),
+                Some(Int),
+                Some(Int),
+                Some(String),
+                Some(Int)
+            ]
+        );
+        assert_eq!(
+            errors("select length(name, 1), round(), now(1) from users;")
+                .into_iter()
+                .map(|(message, _)| message)
+                .collect::<Vec<_>>(),
+            [
+                "Function length takes one argument",
+                "Function round takes one to two arguments",
+                "Function now takes no arguments"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_windows() {
+        use DataType::*;
+
+        assert_eq!(
+            bind(
+                "select rank() over (order by age), lag(name, 1, 'x') over (), \
+                 first_value(age) over (partition by name), sum(count(*)) over () \
+                 from users group by name, age;"
+            )
+            .unwrap(),
+            [Some(Int), Some(String), Some(Int), Some(Int)]
+        );
+        assert_eq!(
+            errors(
+                "select lag(name, 1, 0) over (), rank(id) over (), lead(name, 'x') over (), \
+                 max(id) over (rows between unbounded following and current row) \
+                 from users where rank() over () = 1 group by name;"
+            )
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect::<Vec<_>>(),
+            [
+                "Window function rank is not allowed here",
+                "Type mismatch: lag expects values of the same type, found STRING and INT",
+                "Function rank takes no arguments",
+                "Type mismatch: lead expects INT, found STRING",
+                "Invalid window frame: it can not start at UNBOUNDED FOLLOWING",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subqueries() {
+        use DataType::*;
+
+        // a subquery sees the outer query, a derived table does not
+        assert_eq!(
+            bind(
+                "select (select max(title) from posts where author = id), t.n \
+                 from (select name as n from users) as t;"
+            )
+            .unwrap_err()
+            .to_string(),
+            "Column id does not exists"
+        );
+        assert_eq!(
+            bind(
+                "select t.n, exists (select * from posts) from (select name as n from users) as t;"
+            )
+            .unwrap(),
+            [Some(String), Some(Boolean)]
+        );
+        assert_eq!(
+            bind("select (select max(title) from posts where author = id) from users;").unwrap(),
+            [Some(String)]
+        );
+
+        assert!(matches!(
+            bind("select id from users where id in (select author, title from posts);"),
+            Err(ExecutionError::SubqueryColumnCount(2))
+        ));
+        assert_eq!(
+            errors("select id from users where name in (select author from posts);")[0].0,
+            "Type mismatch: cannot compare STRING with INT"
+        );
+    }
+
+    #[test]
+    fn test_ctes() {
+        use DataType::*;
+
+        assert_eq!(
+            bind(
+                "with recursive n (x, label) as (select id, name from users \
+                 union all select x + 1, label from n where x < 3), \
+                 m as (select label from n) select *, label from m;"
+            )
+            .unwrap(),
+            [Some(String), Some(String)]
+        );
+
+        assert_eq!(
+            errors(
+                "with a (x, y) as (select id from users), \
+                 b as (select id from users union select name from users) \
+                 select * from c;"
+            )
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect::<Vec<_>>(),
+            [
+                "Common table expression a names 2 columns but its query returns 1",
+                "Type mismatch: column 1 of UNION is INT on one side and STRING on the other",
+                "Table c was not found",
+            ]
+        );
+        // without RECURSIVE a query can not read itself
+        assert!(matches!(
+            bind("with n as (select id from users union select id from n) select * from n;"),
+            Err(ExecutionError::TableNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        assert_eq!(
+            bind("select id, name from users union select author, title from posts order by id;")
+                .unwrap(),
+            [Some(DataType::Int), Some(DataType::String)]
+        );
+        assert_eq!(
+            errors(
+                "select id from users except select title from posts \
+                 order by users.id;"
+            )
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect::<Vec<_>>(),
+            [
+                "Type mismatch: column 1 of EXCEPT is INT on one side and STRING on the other",
+                "Column users.id does not exists",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(matches!(
+            bind("select id from users join users on true;"),
+            Err(ExecutionError::AmbiguousColumn(..))
+        ));
+        assert!(matches!(
+            bind("select count(id, age), sum(*) from users;"),
+            Err(ExecutionError::Semantic(errors)) if errors.iter().all(
+                |e| matches!(e, ExecutionError::WrongArgumentCount(..))
+            )
+        ));
+        assert!(matches!(
+            bind("select initcap(name) from users;"),
+            Err(ExecutionError::FunctionNotFound(..))
+        ));
+        assert!(matches!(
+            bind("insert into users values id;"),
+            Err(ExecutionError::ColumnDoesNotExists(..))
+        ));
+        // the columns of a missing table are not reported again
+        assert!(matches!(
+            bind("select title from nowhere;"),
+            Err(ExecutionError::TableNotFound(..))
+        ));
+        assert!(matches!(
+            bind("select *, count(*) from users;"),
+            Err(ExecutionError::ColumnNotGrouped(..))
+        ));
+    }
+}
@@ -1,13 +1,163 @@
-use miette::Diagnostic;
+use miette::{Diagnostic, SourceSpan};
+use sqlmicro_parser::parse::Span;
 use thiserror::Error;
 
+use crate::executor::ExecutionResponse;
+
+/// Errors about a table, column, function, parameter or expression of the
+/// statement also hold its span in the source, empty when the statement was
+/// not parsed from text
 #[derive(Error, Debug, Diagnostic)]
 #[error("Execution Error")]
 pub enum ExecutionError {
     #[error("Table {0} was not found")]
-    TableNotFound(String),
+    TableNotFound(String, #[label("unknown table")] SourceSpan),
     #[error("Table {0} already exists")]
-    TableAlreadyExists(String),
+    TableAlreadyExists(String, #[label("already exists")] SourceSpan),
     #[error("Column {0} does not exists")]
-    ColumnDoesNotExists(String),
+    ColumnDoesNotExists(String, #[label("unknown column")] SourceSpan),
+    #[error("Column {0} is specified more than once")]
+    DuplicateColumn(String, #[label("duplicate column")] SourceSpan),
+    #[error("Column {0} is ambiguous")]
+    AmbiguousColumn(String, #[label("ambiguous column")] SourceSpan),
+    #[error("Column {0} must appear in the GROUP BY clause or be used in an aggregate function")]
+    ColumnNotGrouped(String, #[label("not grouped")] SourceSpan),
+    #[error("Function {0} does not exists")]
+    FunctionNotFound(String, #[label("unknown function")] SourceSpan),
+    #[error("Aggregate function {0} is not allowed here")]
+    AggregateNotAllowed(String, #[label("aggregate")] SourceSpan),
+    #[error("Type mismatch: {0}")]
+    TypeMismatch(String),
+    #[error("Type mismatch: {0}")]
+    WrongType(String, #[label("wrong type")] SourceSpan),
+    #[error("Function {0} takes {1}")]
+    WrongArgumentCount(String, String, #[label("wrong arguments")] SourceSpan),
+    #[error("INSERT has {0} target columns but {1} values")]
+    InsertColumnCount(usize, usize, #[label("target columns")] SourceSpan),
+    #[error("Cannot infer the type of column {0}: {1}")]
+    ColumnType(String, String),
+    #[error("Duplicate value ({1}) for {0}")]
+    UniqueViolation(String, String),
+    #[error("Columns ({0}) of the primary key can not be NULL")]
+    NullKey(String),
+    #[error("Table {0} can not have more than one primary key")]
+    MultiplePrimaryKeys(String, #[label("second primary key")] SourceSpan),
+    #[error("ON CONFLICT DO UPDATE can not change the same row twice")]
+    RowAffectedTwice,
+    #[error("No primary key or unique constraint matches the ON CONFLICT columns")]
+    NoConflictKey(#[label("conflict columns")] SourceSpan),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Setting {0} does not exists")]
+    SettingNotFound(String),
+    #[error("Invalid value {1} for setting {0}")]
+    InvalidSettingValue(String, String),
+    #[error("Parameter {0} has no value bound")]
+    ParameterNotBound(String, #[label("no value")] SourceSpan),
+    #[error("A statement can not mix ? and $n placeholders")]
+    MixedParameters,
+    #[error("Expected {expected} parameters, got {found}")]
+    ParameterCountMismatch { expected: usize, found: usize },
+    #[error("Subquery used as a value returned more than one row")]
+    SubqueryTooManyRows,
+    #[error("Subquery has to return a single column, not {0}")]
+    SubqueryColumnCount(usize),
+    #[error("Correlated subqueries are only supported in WHERE, the select list and ORDER BY")]
+    CorrelatedSubqueryNotAllowed,
+    #[error("Common table expression {0} names {1} columns but its query returns {2}")]
+    CteColumnCount(String, usize, usize, #[label("column names")] SourceSpan),
+    #[error("Each query of {0} has to return the same number of columns, found {1} and {2}")]
+    SetOperationColumnCount(String, usize, usize),
+    #[error(
+        "Recursive query {0} did not finish within {1} iterations, see the recursion_limit setting"
+    )]
+    RecursionLimit(String, usize, #[label("recursive query")] SourceSpan),
+    #[error("Invalid LIKE pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Cannot cast {0} to {1}")]
+    InvalidCast(String, String),
+    #[error("Invalid argument for {0}: {1}")]
+    InvalidArgument(String, String),
+    #[error("Window function {0} is not allowed here")]
+    WindowNotAllowed(String, #[label("window function")] SourceSpan),
+    #[error("Window function {0} requires an OVER clause")]
+    WindowRequired(String, #[label("no OVER clause")] SourceSpan),
+    #[error("Invalid window frame: {0}")]
+    InvalidFrame(String),
+    #[error("Function {0} is built in and can not be replaced")]
+    BuiltinFunction(String),
+    #[error("Position {0} is not in the select list")]
+    PositionNotInSelectList(usize),
+    #[error("SELECT DISTINCT ON expressions must match the first ORDER BY expressions")]
+    DistinctOnOrder,
+    #[error("The statement has {} errors", .0.len())]
+    Semantic(#[related] Vec<ExecutionError>),
+}
+
+impl ExecutionError {
+    /// Point the error at `span`, for the errors that hold one
+    pub fn at(mut self, span: Span) -> Self {
+        match &mut self {
+            ExecutionError::TableNotFound(_, at)
+            | ExecutionError::TableAlreadyExists(_, at)
+            | ExecutionError::ColumnDoesNotExists(_, at)
+            | ExecutionError::DuplicateColumn(_, at)
+            | ExecutionError::InsertColumnCount(_, _, at)
+            | ExecutionError::MultiplePrimaryKeys(_, at)
+            | ExecutionError::NoConflictKey(at)
+            | ExecutionError::AmbiguousColumn(_, at)
+            | ExecutionError::ColumnNotGrouped(_, at)
+            | ExecutionError::FunctionNotFound(_, at)
+            | ExecutionError::AggregateNotAllowed(_, at)
+            | ExecutionError::WindowNotAllowed(_, at)
+            | ExecutionError::WindowRequired(_, at)
+            | ExecutionError::WrongType(_, at)
+            | ExecutionError::WrongArgumentCount(_, _, at)
+            | ExecutionError::ParameterNotBound(_, at)
+            | ExecutionError::CteColumnCount(_, _, _, at)
+            | ExecutionError::RecursionLimit(_, _, at) => *at = span.into(),
+            _ => {}
+        }
+        self
+    }
+
+    /// Span of the part of the statement the error is about, if it has one
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            ExecutionError::TableNotFound(_, at)
+            | ExecutionError::TableAlreadyExists(_, at)
+            | ExecutionError::ColumnDoesNotExists(_, at)
+            | ExecutionError::DuplicateColumn(_, at)
+            | ExecutionError::InsertColumnCount(_, _, at)
+            | ExecutionError::MultiplePrimaryKeys(_, at)
+            | ExecutionError::NoConflictKey(at)
+            | ExecutionError::AmbiguousColumn(_, at)
+            | ExecutionError::ColumnNotGrouped(_, at)
+            | ExecutionError::FunctionNotFound(_, at)
+            | ExecutionError::AggregateNotAllowed(_, at)
+            | ExecutionError::WindowNotAllowed(_, at)
+            | ExecutionError::WindowRequired(_, at)
+            | ExecutionError::WrongType(_, at)
+            | ExecutionError::WrongArgumentCount(_, _, at)
+            | ExecutionError::ParameterNotBound(_, at)
+            | ExecutionError::CteColumnCount(_, _, _, at)
+            | ExecutionError::RecursionLimit(_, _, at) => (!at.is_empty()).then_some(*at),
+            _ => None,
+        }
+    }
+}
+
+/// A statement of a script failed, the statements before it stay applied
+#[derive(Error, Debug, Diagnostic)]
+#[error("Statement {} of the script failed", .index + 1)]
+pub struct ScriptError {
+    /// Position of the failed statement in the script, from 0
+    pub index: usize,
+    /// The name the error is about, or the whole statement
+    #[label("{error}")]
+    pub span: SourceSpan,
+    #[source]
+    pub error: ExecutionError,
+    /// Responses of the statements that ran before the failure
+    pub responses: Vec<ExecutionResponse>,
 }
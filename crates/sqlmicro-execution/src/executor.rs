@@ -1,59 +1,1860 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use derive_more::Display;
-use sqlmicro_parser::query::SqlQuery;
+use sqlmicro_parser::{
+    parse::Spanned, query::SqlQuery, script::Script, value::Value, visit::VisitorMut, Column,
+    ConflictAction, InsertSource, InsertStatement, SelectItem, SelectStatement,
+};
 
-use crate::{error::ExecutionError, row::Row, table::Table};
+use crate::{
+    binder::{sql_type, Binder},
+    error::{ExecutionError, ScriptError},
+    expression::PhysicalExpr,
+    functions::{FunctionRegistry, ScalarFunction, UserAggregate},
+    operator::collect,
+    planner::{output_field, Planner},
+    prepared::PreparedStatement,
+    row::Row,
+    schema::{Field, Schema},
+    settings::Settings,
+    table::{Change, Table},
+};
 
 #[derive(Debug, Display)]
-pub enum ExecutionResponse<'a> {
+pub enum ExecutionResponse {
     #[display(fmt = "{_0:?}")]
-    Select(Vec<Row<'a>>),
-    Insert,
+    Select(Vec<Row>),
+    /// Rows updated by `ON CONFLICT DO UPDATE` are not counted as inserted
+    #[display(fmt = "Inserted {inserted}, updated {updated}")]
+    Insert {
+        inserted: usize,
+        updated: usize,
+    },
     Create,
+    Set,
 }
 
 #[derive(Debug, Default)]
 pub struct Executor {
     tables: HashMap<String, Table>,
+    settings: Settings,
+    functions: FunctionRegistry,
 }
 
 impl Executor {
     pub fn new() -> Self {
-        Self {
-            tables: HashMap::new(),
-        }
+        Self::default()
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
     }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
     pub fn run(&mut self, query: SqlQuery) -> Result<ExecutionResponse, ExecutionError> {
+        let types = Binder::new(&self.tables, &self.functions).bind(&query)?;
+
         match query {
             SqlQuery::Select(select) => {
-                let table = select.table;
-                let table = self
-                    .tables
-                    .get(&table)
-                    .ok_or(ExecutionError::TableNotFound(table))?;
-
-                let rows = table.iter().collect();
+                let plan = Planner::new(&self.tables, &self.settings, &self.functions)
+                    .plan_select(&select)?;
+                let rows = collect(plan)?;
                 Ok(ExecutionResponse::Select(rows))
             }
-            SqlQuery::Insert(insert) => {
-                let table = self
-                    .tables
-                    .get_mut(&insert.table)
-                    .ok_or(ExecutionError::TableNotFound(insert.table))?;
+            SqlQuery::Insert(insert) => self.insert(&insert),
+            SqlQuery::Create(create) => {
+                let table = match &create.query {
+                    None => {
+                        let mut table = Table::new(create.columns, create.layout);
+                        for key in create.keys {
+                            let columns = key
+                                .columns
+                                .iter()
+                                .map(|name| {
+                                    table
+                                        .columns()
+                                        .iter()
+                                        .position(|column| column.name.node == name.node)
+                                        .expect("the binder checks the key columns exist")
+                                })
+                                .collect();
+                            table.add_key(key.kind, columns);
+                        }
+                        table
+                    }
+                    Some(query) => {
+                        let plan = Planner::new(&self.tables, &self.settings, &self.functions)
+                            .plan_select(query)?;
+                        let columns = plan
+                            .schema()
+                            .fields()
+                            .iter()
+                            .zip(types)
+                            .map(|(field, ty)| Column {
+                                name: field.name.clone().into(),
+                                type_info: ty
+                                    .and_then(sql_type)
+                                    .expect("the binder checks the columns have a stored type"),
+                            })
+                            .collect();
+                        let mut table = Table::new(columns, create.layout);
+                        for row in collect(plan)? {
+                            table.insert(row.values().to_vec())?;
+                        }
+                        table
+                    }
+                };
 
-                // TODO: convert inside insert ?
-                table.insert(insert.values.into_iter().map(|v| v.to_string()).collect());
+                self.tables.insert(create.table.node, table);
 
-                Ok(ExecutionResponse::Insert)
+                Ok(ExecutionResponse::Create)
             }
-            SqlQuery::Create(create) => {
-                let table = Table::new(create.columns);
+            SqlQuery::Set(set) => {
+                self.settings.set(&set.name, &set.value)?;
 
-                self.tables.insert(create.table, table);
+                Ok(ExecutionResponse::Set)
+            }
+        }
+    }
 
-                Ok(ExecutionResponse::Create)
+    /// Insert the rows of the statement, or none of them when one fails. With
+    /// `RETURNING` the response holds a row for each one inserted or updated,
+    /// whose id is the one of the stored row
+    fn insert(&mut self, insert: &InsertStatement) -> Result<ExecutionResponse, ExecutionError> {
+        let not_found =
+            || ExecutionError::TableNotFound(insert.table.to_string(), insert.table.span.into());
+        let table = self.tables.get(&insert.table.node).ok_or_else(not_found)?;
+        let columns = table.columns();
+        let position = |name: &Spanned<String>| {
+            columns
+                .iter()
+                .position(|column| column.name.node == name.node)
+                .expect("the binder checks the columns exist")
+        };
+        let positions: Vec<usize> = insert.columns.iter().map(position).collect();
+
+        // the rows are all computed before the table changes, so a select
+        // can read the table it inserts into
+        let planner = Planner::new(&self.tables, &self.settings, &self.functions);
+        let rows: Vec<Vec<Value>> = match &insert.source {
+            InsertSource::Values(values) => vec![values
+                .iter()
+                .map(|expr| planner.compile(expr, &Schema::default())?.evaluate(&[]))
+                .collect::<Result<_, _>>()?],
+            InsertSource::Select(select) => collect(planner.plan_select(select)?)?
+                .iter()
+                .map(|row| row.values().to_vec())
+                .collect(),
+        };
+
+        // conflicts are looked for on the key of the ON CONFLICT columns, on
+        // every key when it names none
+        let mut key = None;
+        let mut assignments = Vec::new();
+        if let Some(on_conflict) = &insert.on_conflict {
+            let mut target: Vec<usize> = on_conflict.target.iter().map(position).collect();
+            target.sort_unstable();
+            key = table.keys().position(|(_, columns)| {
+                let mut columns = columns.to_vec();
+                columns.sort_unstable();
+                columns == target
+            });
+            if let ConflictAction::Update(updates) = &on_conflict.action {
+                // the new values read the row in the table then the rejected
+                // one, a column without a table is the one of the row
+                let fields = columns
+                    .iter()
+                    .map(|column| Field::new(Some(insert.table.node.clone()), &column.name.node))
+                    .chain(
+                        columns
+                            .iter()
+                            .map(|column| Field::new(Some("excluded".into()), &column.name.node)),
+                    )
+                    .collect();
+                let schema = Schema::new(fields);
+                for assignment in updates {
+                    let mut value = assignment.value.clone();
+                    Qualify(&insert.table).visit_expression_mut(&mut value);
+                    let expr = planner.compile(&value, &schema)?;
+                    assignments.push((position(&assignment.column), expr));
+                }
+            }
+        }
+
+        // the returned values are computed from the row as stored
+        let stored: Vec<Field> = columns
+            .iter()
+            .map(|column| Field::new(Some(insert.table.node.clone()), &column.name.node))
+            .collect();
+        let stored_schema = Schema::new(stored.clone());
+        let mut returning = Vec::new();
+        let mut fields = Vec::new();
+        for item in &insert.returning {
+            match item {
+                SelectItem::Wildcard(_) => {
+                    for (index, field) in stored.iter().enumerate() {
+                        returning.push(ReturnedColumn::Stored(index));
+                        fields.push(field.clone());
+                    }
+                }
+                SelectItem::Expression { expr, alias } => {
+                    let mut value = expr.clone();
+                    Qualify(&insert.table).visit_expression_mut(&mut value);
+                    let compiled = planner.compile(&value, &stored_schema)?;
+                    returning.push(ReturnedColumn::Computed(compiled));
+                    fields.push(output_field(expr, alias.as_deref()));
+                }
+            }
+        }
+        let schema = Rc::new(Schema::new(fields));
+
+        let table = self
+            .tables
+            .get_mut(&insert.table.node)
+            .ok_or_else(not_found)?;
+        let width = table.columns().len();
+        let mut changes = Vec::new();
+        // rows this statement inserted or updated, DO UPDATE can not change
+        // them again
+        let mut affected = HashSet::new();
+        let mut returned = Vec::new();
+        let mut output = |table: &Table, id: usize| -> Result<(), ExecutionError> {
+            if returning.is_empty() {
+                return Ok(());
+            }
+            let row = table.get(id);
+            let values = returning
+                .iter()
+                .map(|column| match column {
+                    ReturnedColumn::Stored(index) => Ok(row[*index].clone()),
+                    ReturnedColumn::Computed(expr) => expr.evaluate(&row),
+                })
+                .collect::<Result<_, _>>()?;
+            returned.push(Row::new(schema.clone(), id, values));
+            Ok(())
+        };
+        let counts =
+            rows.into_iter()
+                .try_fold((0, 0), |(inserted, updated), mut values| {
+                    if !positions.is_empty() {
+                        let mut row = vec![Value::Null; width];
+                        for (&position, value) in positions.iter().zip(values) {
+                            row[position] = value;
+                        }
+                        values = row;
+                    }
+                    values.resize(width, Value::Null);
+                    // keys are compared as they would be stored
+                    table.coerce(&mut values)?;
+
+                    let conflict = insert.on_conflict.as_ref().and_then(|on_conflict| {
+                        let id = match key {
+                            Some(key) => table.conflict(key, &values),
+                            None => (0..table.keys().count())
+                                .find_map(|key| table.conflict(key, &values)),
+                        }?;
+                        Some((id, &on_conflict.action))
+                    });
+                    match conflict {
+                        None => {
+                            let id = table.insert(values)?;
+                            changes.push(Change::Inserted(id));
+                            affected.insert(id);
+                            output(table, id)?;
+                            Ok((inserted + 1, updated))
+                        }
+                        Some((_, ConflictAction::Nothing)) => Ok((inserted, updated)),
+                        Some((id, ConflictAction::Update(_))) => {
+                            if !affected.insert(id) {
+                                return Err(ExecutionError::RowAffectedTwice);
+                            }
+                            let mut row = table.get(id);
+                            let tuple: Vec<Value> = row.iter().cloned().chain(values).collect();
+                            for (position, expr) in &assignments {
+                                row[*position] = expr.evaluate(&tuple)?;
+                            }
+                            changes.push(Change::Updated(id, table.update(id, row)?));
+                            output(table, id)?;
+                            Ok((inserted, updated + 1))
+                        }
+                    }
+                });
+
+        let (inserted, updated) = counts.inspect_err(|_| {
+            for change in changes.into_iter().rev() {
+                table.undo(change);
+            }
+        })?;
+        match insert.returning.is_empty() {
+            true => Ok(ExecutionResponse::Insert { inserted, updated }),
+            false => Ok(ExecutionResponse::Select(returned)),
+        }
+    }
+
+    /// Run the statements of a script in order, stopping at the first one
+    /// that fails
+    pub fn run_script(&mut self, script: Script) -> Result<Vec<ExecutionResponse>, ScriptError> {
+        let mut responses = Vec::new();
+
+        for (index, statement) in script.statements.into_iter().enumerate() {
+            match self.run(statement.query) {
+                Ok(response) => responses.push(response),
+                Err(error) => {
+                    return Err(ScriptError {
+                        index,
+                        span: error.span().unwrap_or(statement.span.into()),
+                        error,
+                        responses,
+                    })
+                }
+            }
+        }
+
+        Ok(responses)
+    }
+
+    /// Check a statement with `?` or `$n` placeholders once, so it can be
+    /// executed many times with different values
+    pub fn prepare(&self, query: SqlQuery) -> Result<PreparedStatement, ExecutionError> {
+        PreparedStatement::new(query, &self.tables, &self.functions)
+    }
+
+    /// Run a prepared statement, `params` holds the value of `$1`, `$2`, ...
+    pub fn execute(
+        &mut self,
+        statement: &PreparedStatement,
+        params: &[Value],
+    ) -> Result<ExecutionResponse, ExecutionError> {
+        let query = statement.bind(params)?;
+        self.run(query)
+    }
+
+    /// Make a scalar function callable from the statements run afterwards,
+    /// replacing a built-in or registered function of the same name
+    pub fn register_scalar_function(
+        &mut self,
+        function: ScalarFunction,
+    ) -> Result<(), ExecutionError> {
+        self.functions.register_scalar(function)
+    }
+
+    /// Make an aggregate function callable from the statements run
+    /// afterwards, `COUNT`, `SUM`, `MIN`, `MAX`, `AVG` and the window functions
+    /// can not be replaced
+    pub fn register_aggregate_function(
+        &mut self,
+        function: UserAggregate,
+    ) -> Result<(), ExecutionError> {
+        self.functions.register_aggregate(function)
+    }
+}
+
+/// A column of `RETURNING`, `*` copies the stored ones
+enum ReturnedColumn {
+    Stored(usize),
+    Computed(PhysicalExpr),
+}
+
+/// Qualifies the columns of an expression that have no table with `table`,
+/// leaving its subqueries alone
+struct Qualify<'a>(&'a Spanned<String>);
+
+impl VisitorMut for Qualify<'_> {
+    fn visit_select_mut(&mut self, _select: &mut SelectStatement) {}
+
+    fn visit_column_mut(
+        &mut self,
+        table: &mut Option<Spanned<String>>,
+        _name: &mut Spanned<String>,
+    ) {
+        table.get_or_insert_with(|| self.0.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::{BigDecimal, Zero};
+    use sqlmicro_parser::{parse::Parse, SqlTypeInfo};
+
+    use super::*;
+    use crate::{operator::Accumulator, DataType};
+
+    fn run(exec: &mut Executor, query: &str) -> ExecutionResponse {
+        let query = SqlQuery::parse_format_error(query).unwrap();
+        exec.run(query).unwrap()
+    }
+
+    fn select(exec: &mut Executor, query: &str) -> Vec<Vec<String>> {
+        match run(exec, query) {
+            ExecutionResponse::Select(rows) => rows
+                .iter()
+                .map(|row| row.values().iter().map(|v| v.to_string()).collect())
+                .collect(),
+            other => panic!("expected rows, got {other}"),
+        }
+    }
+
+    fn executor() -> Executor {
+        let mut exec = Executor::new();
+        run(
+            &mut exec,
+            "create table users (id int, name string, age int);",
+        );
+        run(&mut exec, "insert into users values 1, 'ana', 30;");
+        run(&mut exec, "insert into users values 2, 'bob', 25;");
+        run(&mut exec, "insert into users values 3, 'carl', 30;");
+        run(&mut exec, "create table posts (author int, title string);");
+        run(&mut exec, "insert into posts values 1, 'hello';");
+        run(&mut exec, "insert into posts values 3, 'bye';");
+        run(&mut exec, "insert into posts values 1, 'again';");
+        exec
+    }
+
+    #[test]
+    fn test_filter_sort_limit() {
+        let mut exec = executor();
+
+        let rows = select(
+            &mut exec,
+            "select name, age + 1 as next from users where age >= 30 or id = 2 \
+             order by next desc, name limit 2;",
+        );
+
+        assert_eq!(rows, vec![vec!["ana", "31"], vec!["carl", "31"]]);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut exec = executor();
+
+        let rows = select(
+            &mut exec,
+            "select age, count(*), max(name) from users group by age order by age;",
+        );
+        assert_eq!(rows, vec![vec!["25", "1", "bob"], vec!["30", "2", "carl"]]);
+
+        let rows = select(
+            &mut exec,
+            "select sum(age), min(id) from users where id > 9;",
+        );
+        assert_eq!(rows, vec![vec!["NULL", "NULL"]]);
+    }
+
+    #[test]
+    fn test_join() {
+        let mut exec = executor();
+
+        let rows = select(
+            &mut exec,
+            "select name, title from users join posts on users.id = posts.author \
+             order by title;",
+        );
+
+        assert_eq!(
+            rows,
+            vec![
+                vec!["ana", "again"],
+                vec!["carl", "bye"],
+                vec!["ana", "hello"]
+            ]
+        );
+
+        // a table joined with itself, a common table expression renamed
+        assert_eq!(
+            select(
+                &mut exec,
+                "select a.name, b.name from users a join users as b \
+                 on a.age = b.age and a.id < b.id;",
+            ),
+            [["ana", "carl"]]
+        );
+        assert_eq!(
+            select(
+                &mut exec,
+                "with c as (select id from users) select x.id from c x where x.id = 2;",
+            ),
+            [["2"]]
+        );
+        // the alias hides the name of the table
+        let query = SqlQuery::parse_format_error("select users.id from users u;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::ColumnDoesNotExists(..))
+        ));
+    }
+
+    #[test]
+    fn test_subqueries() {
+        let mut exec = executor();
+
+        for (sql, expected) in [
+            (
+                "select name from users where id in (select author from posts) order by id;",
+                vec![vec!["ana"], vec!["carl"]],
+            ),
+            (
+                "select name from users where id not in (select author from posts);",
+                vec![vec!["bob"]],
+            ),
+            (
+                "select name from users where age = (select max(age) from users) \
+                 and exists (select * from posts where author = id) order by name;",
+                vec![vec!["ana"], vec!["carl"]],
+            ),
+            (
+                "select name from users where not exists \
+                 (select * from posts where posts.author = users.id);",
+                vec![vec!["bob"]],
+            ),
+            // an alias tells the same table apart inside its own subquery
+            (
+                "select name from users where exists \
+                 (select 1 from users as u where u.age = users.age and u.id <> users.id) \
+                 order by name;",
+                vec![vec!["ana"], vec!["carl"]],
+            ),
+            (
+                "select u.name, p.title from users u join posts p on p.author = u.id \
+                 where not exists (select 1 from posts q where q.author = u.id and q.title < p.title) \
+                 order by u.id;",
+                vec![vec!["ana", "again"], vec!["carl", "bye"]],
+            ),
+            (
+                "select name, (select count(*) from posts where author = id) as posts \
+                 from users order by posts desc, name;",
+                vec![vec!["ana", "2"], vec!["carl", "1"], vec!["bob", "0"]],
+            ),
+            (
+                "select name from users where \
+                 (select count(*) from posts where author = id) = 1;",
+                vec![vec!["carl"]],
+            ),
+            (
+                "select t.name, t.older from \
+                 (select name, age > 26 as older from users where id > 1) as t;",
+                vec![vec!["bob", "false"], vec!["carl", "true"]],
+            ),
+            (
+                "select title from posts where author in \
+                 (select id from users where name = 'carl');",
+                vec![vec!["bye"]],
+            ),
+            // after grouping a subquery reads the grouped columns
+            (
+                "select age, (select count(*) from posts where author * 10 = age) \
+                 from users group by age order by age;",
+                vec![vec!["25", "0"], vec!["30", "1"]],
+            ),
+            (
+                "select id, count(*), (select count(*) from posts where author = users.id) \
+                 from users group by id order by id;",
+                vec![
+                    vec!["1", "1", "2"],
+                    vec!["2", "1", "0"],
+                    vec!["3", "1", "1"],
+                ],
+            ),
+            (
+                "select age, max(id) in (select author from posts), \
+                 exists (select * from posts where author * 10 = age) \
+                 from users group by age order by age;",
+                vec![vec!["25", "false", "false"], vec!["30", "true", "true"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        let query = SqlQuery::parse_format_error("select name, (select id from users) from users;")
+            .unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::SubqueryTooManyRows)
+        ));
+    }
+
+    #[test]
+    fn test_ctes() {
+        let mut exec = executor();
+        run(&mut exec, "create table staff (id int, boss int);");
+        for (id, boss) in [(1, 0), (2, 1), (3, 1), (4, 2), (5, 4), (6, 9)] {
+            run(
+                &mut exec,
+                &format!("insert into staff values {id}, {boss};"),
+            );
+        }
+
+        for (sql, expected) in [
+            (
+                "with older as (select id, name from users where age = 30), \
+                 named (who) as (select name from older where id > 1) \
+                 select who from named;",
+                vec![vec!["carl"]],
+            ),
+            (
+                "with recursive n (x) as (select 1 from users where id = 1 \
+                 union all select x + 1 from n where x < 4) select x from n;",
+                vec![vec!["1"], vec!["2"], vec!["3"], vec!["4"]],
+            ),
+            (
+                "with recursive reports (id, depth) as (select id, 0 from staff where id = 2 \
+                 union all select staff.id, depth + 1 from staff join reports \
+                 on staff.boss = reports.id) select id, depth from reports order by id;",
+                vec![vec!["2", "0"], vec!["4", "1"], vec!["5", "2"]],
+            ),
+            // without ALL, rows already found stop the recursion
+            (
+                "with recursive up (x) as (select 1 from users where id = 1 \
+                 union select (x + 1) % 3 from up) select count(*) from up;",
+                vec![vec!["3"]],
+            ),
+            (
+                "select name from users where id in \
+                 (with recursive chain (id) as (select 2 from users where id = 1 \
+                 union select staff.boss from staff join chain on staff.id = chain.id) \
+                 select id from chain) order by name;",
+                vec![vec!["ana"], vec!["bob"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        run(&mut exec, "set recursion_limit = 3;");
+        let count = |to: usize| {
+            SqlQuery::parse_format_error(&format!(
+                "with recursive n (x) as (select 1 from users where id = 1 \
+                 union all select x + 1 from n where x < {to}) select x from n;"
+            ))
+            .unwrap()
+        };
+        assert!(exec.run(count(4)).is_ok());
+        assert!(matches!(
+            exec.run(count(5)),
+            Err(ExecutionError::RecursionLimit(name, 3, _)) if name == "n"
+        ));
+    }
+
+    #[test]
+    fn test_predicates() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select name from users where id in (1, 3) order by name;",
+                vec![vec!["ana"], vec!["carl"]],
+            ),
+            (
+                "select name from users where id not in (1, 3);",
+                vec![vec!["bob"]],
+            ),
+            // a NULL item makes a miss unknown rather than false
+            (
+                "select count(*) from users where id in (1, null);",
+                vec![vec!["1"]],
+            ),
+            (
+                "select count(*) from users where id not in (1, null);",
+                vec![vec!["0"]],
+            ),
+            (
+                "select name from users where age between 26 and 30 order by name;",
+                vec![vec!["ana"], vec!["carl"]],
+            ),
+            (
+                "select name from users where id not between 2 and 3;",
+                vec![vec!["ana"]],
+            ),
+            (
+                "select name from users where id not between null and 2;",
+                vec![vec!["carl"]],
+            ),
+            (
+                "select name from users where name like '%a%' order by name;",
+                vec![vec!["ana"], vec!["carl"]],
+            ),
+            (
+                "select name from users where name not like '_a%';",
+                vec![vec!["ana"], vec!["bob"]],
+            ),
+            ("select name from users where name like 'A%';", vec![]),
+            (
+                "select name from users where name ilike 'A%';",
+                vec![vec!["ana"]],
+            ),
+            (
+                "select 'a%c' like 'a!%c' escape '!', 'abc' like 'a!%c' escape '!', \
+                 'a_c' like 'a\\_c', 'abc' like 'a\\_c', 'a\\c' like 'a\\c' escape '', \
+                 null like 'a', 'a' like '%%a%' from users where id = 1;",
+                vec![vec![
+                    "true", "false", "true", "false", "true", "NULL", "true",
+                ]],
+            ),
+            // predicates over grouped columns and aggregates
+            (
+                "select age in (30, 1), age between 20 and 26, count(*) not in (2) \
+                 from users group by age order by age;",
+                vec![
+                    vec!["false", "true", "true"],
+                    vec!["true", "false", "false"],
+                ],
+            ),
+            (
+                "select name like 'a%', name ilike max(name) from users group by name \
+                 order by name;",
+                vec![
+                    vec!["true", "true"],
+                    vec!["false", "true"],
+                    vec!["false", "true"],
+                ],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        let query =
+            SqlQuery::parse_format_error("select name like 'a' escape 'ab' from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select name, case when age > 28 then 'old' when age > 26 then 'mid' else 'young' end \
+                 from users order by id;",
+                vec![vec!["ana", "old"], vec!["bob", "young"], vec!["carl", "old"]],
+            ),
+            (
+                "select case id when 1 then 'one' when 2 then 'two' end from users order by id;",
+                vec![vec!["one"], vec!["two"], vec!["NULL"]],
+            ),
+            // a branch that is not reached is not evaluated
+            (
+                "select case when id = 2 then 1 / 0 else id end from users where id <> 2;",
+                vec![vec!["1"], vec!["3"]],
+            ),
+            (
+                "select case count(*) when 3 then 'all' else 'some' end, \
+                 coalesce(max(age), 0) from users;",
+                vec![vec!["all", "30"]],
+            ),
+            (
+                "select coalesce(null, null, name), coalesce(null, id, 1 / 0), nullif(age, 30) \
+                 from users order by id;",
+                vec![
+                    vec!["ana", "1", "NULL"],
+                    vec!["bob", "2", "25"],
+                    vec!["carl", "3", "NULL"],
+                ],
+            ),
+            (
+                "select cast(id as string) = '2', cast(' -12 ' as int), '7'::int + 1, \
+                 cast(true as int), cast(7 / 2 as int), 10 / 4::int, cast(null as int), \
+                 age::string from users where id = 2;",
+                vec![vec!["true", "-12", "8", "1", "3", "2", "NULL", "25"]],
+            ),
+            // numbers and numeric strings are rounded, halves away from zero
+            (
+                "select cast('1.5' as int), cast('-3' as int), cast(' -2.5' as int), \
+                 cast('+0.4' as int), cast(-1.4 as int), cast(-2.5 as int) from users where id = 2;",
+                vec![vec!["2", "-3", "-3", "0", "-1", "-3"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        for sql in [
+            "select cast('abc' as int) from users;",
+            "select cast('1.5x' as int) from users;",
+            "select cast('' as int) from users;",
+            "select cast(name as int) from users;",
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert!(
+                matches!(exec.run(query), Err(ExecutionError::InvalidCast(..))),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select upper(name) || '-' || lower('X'), length(name), substr(name, 2), \
+                 substr(name, 0, 2), trim('  a b ') from users order by id limit 2;",
+                vec![
+                    vec!["ANA-x", "3", "na", "a", "a b"],
+                    vec!["BOB-x", "3", "ob", "b", "a b"],
+                ],
+            ),
+            (
+                "select replace(name, 'a', 'o'), name || null from users where id = 3;",
+                vec![vec!["corl", "NULL"]],
+            ),
+            // integers divide to an integer, towards zero
+            (
+                "select 10 / 3, -7 / 2, age / 7, 1.0 / 4, age / 2.5 from users where id = 2;",
+                vec![vec!["3", "-3", "3", "0.25", "10"]],
+            ),
+            (
+                "select abs(-age), round(age / 7.0), round(age / 7.0, 2), floor(-age / 7.0), \
+                 ceil(age / 7.0), mod(age, 7), mod(-age, 7) from users where id = 2;",
+                vec![vec!["25", "4", "3.57", "-4", "4", "4", "-4"]],
+            ),
+            (
+                "select date_trunc('month', '2024-02-29 13:45:10'), \
+                 date_trunc('week', '2024-02-29'), date_trunc('hour', '2024-02-29 13:45:10'), \
+                 extract(year from '2024-02-29 13:45:10'), extract(DOW from '2024-02-29'), \
+                 extract('doy', '2024-02-29'), extract(epoch from '1970-01-02') \
+                 from users where id = 1;",
+                vec![vec![
+                    "2024-02-01 00:00:00",
+                    "2024-02-26 00:00:00",
+                    "2024-02-29 13:00:00",
+                    "2024",
+                    "4",
+                    "60",
+                    "86400",
+                ]],
+            ),
+            (
+                "select extract(year from now()) >= 2024, length(now()) from users where id = 1;",
+                vec![vec!["true", "19"]],
+            ),
+            // functions can take aggregates and grouped columns
+            (
+                "select age, upper(max(name)) from users group by age order by age;",
+                vec![vec!["25", "BOB"], vec!["30", "CARL"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        for sql in [
+            "select substr(name, 1, -1) from users;",
+            "select date_trunc('fortnight', '2024-01-01') from users;",
+            "select extract(year from 'tomorrow') from users;",
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert!(
+                matches!(exec.run(query), Err(ExecutionError::InvalidArgument(..))),
+                "{sql}"
+            );
+        }
+        let query = SqlQuery::parse_format_error("select mod(id, 0) from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::DivisionByZero)
+        ));
+    }
+
+    /// Product of the values of a group, `NULL` when it has none
+    #[derive(Default)]
+    struct Product(Option<BigDecimal>);
+
+    impl Accumulator for Product {
+        fn update(&mut self, value: &Value) -> Result<(), ExecutionError> {
+            if let Value::Number(n) = value {
+                self.0 = Some(self.0.take().map_or(n.clone(), |product| product * n));
             }
+            Ok(())
+        }
+
+        fn state(&self) -> Vec<Value> {
+            vec![self.evaluate().unwrap()]
+        }
+
+        fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError> {
+            self.update(&state[0])
+        }
+
+        fn evaluate(&self) -> Result<Value, ExecutionError> {
+            Ok(self.0.clone().map_or(Value::Null, Value::Number))
+        }
+    }
+
+    #[test]
+    fn test_user_functions() {
+        let mut exec = executor();
+        exec.register_scalar_function(ScalarFunction::new(
+            "Greet",
+            vec![DataType::String, DataType::String],
+            DataType::String,
+            |args| match args {
+                [Value::String(name), Value::String(greeting)] => {
+                    Ok(Value::String(format!("{greeting} {name}")))
+                }
+                _ => Err(ExecutionError::InvalidArgument("greet".into(), "".into())),
+            },
+        ))
+        .unwrap();
+        exec.register_scalar_function(ScalarFunction::new(
+            "checked_div",
+            vec![DataType::Int, DataType::Int],
+            DataType::Int,
+            |args| match args {
+                [_, Value::Number(d)] if d.is_zero() => Err(ExecutionError::DivisionByZero),
+                [Value::Number(n), Value::Number(d)] => Ok(Value::Number(n / d)),
+                _ => unreachable!(),
+            },
+        ))
+        .unwrap();
+        // replaces the built-in
+        exec.register_scalar_function(ScalarFunction::new(
+            "upper",
+            vec![DataType::String],
+            DataType::String,
+            |args| Ok(args[0].clone()),
+        ))
+        .unwrap();
+        exec.register_aggregate_function(UserAggregate::new(
+            "product",
+            DataType::Int,
+            DataType::Int,
+            Product::default,
+        ))
+        .unwrap();
+
+        for (sql, expected) in [
+            (
+                "select greet(name, 'hi'), upper(name), checked_div(age, id) from users \
+                 order by id limit 2;",
+                vec![vec!["hi ana", "ana", "30"], vec!["hi bob", "bob", "12.5"]],
+            ),
+            (
+                "select age, product(id), product(id) + 1 from users group by age \
+                 order by age;",
+                vec![vec!["25", "2", "3"], vec!["30", "3", "4"]],
+            ),
+            (
+                "select product(age) from users where id > 9;",
+                vec![vec!["NULL"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        // declared types are checked before running
+        for sql in [
+            "select greet(age, 'hi') from users;",
+            "select product(name) from users;",
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert!(
+                matches!(exec.run(query), Err(ExecutionError::WrongType(..))),
+                "{sql}"
+            );
+        }
+        let query = SqlQuery::parse_format_error("select greet(name) from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::WrongArgumentCount(..))
+        ));
+
+        let query = SqlQuery::parse_format_error("select checked_div(age, 0) from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::DivisionByZero)
+        ));
+
+        // a function returning a value of another type fails
+        exec.register_scalar_function(ScalarFunction::new(
+            "greet",
+            vec![DataType::String, DataType::String],
+            DataType::String,
+            |_| Ok(Value::Number(1.into())),
+        ))
+        .unwrap();
+        let query = SqlQuery::parse_format_error("select greet(name, 'hi') from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::TypeMismatch(..))
+        ));
+
+        assert!(matches!(
+            exec.register_aggregate_function(UserAggregate::new(
+                "SUM",
+                DataType::Int,
+                DataType::Int,
+                Product::default,
+            )),
+            Err(ExecutionError::BuiltinFunction(name)) if name == "sum"
+        ));
+    }
+
+    #[test]
+    fn test_window_functions() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select id, row_number() over (order by age, id), rank() over (order by age), \
+                 dense_rank() over (order by age desc) from users order by id;",
+                vec![
+                    vec!["1", "2", "2", "1"],
+                    vec!["2", "1", "1", "2"],
+                    vec!["3", "3", "2", "1"],
+                ],
+            ),
+            (
+                "select id, lag(name) over (order by id), lead(name, 2, 'none') over (order by id), \
+                 first_value(name) over (partition by age order by id desc) from users order by id;",
+                vec![
+                    vec!["1", "NULL", "carl", "carl"],
+                    vec!["2", "ana", "none", "bob"],
+                    vec!["3", "bob", "none", "carl"],
+                ],
+            ),
+            // without a frame, rows sorted the same are summed together
+            (
+                "select id, sum(age) over (order by id), sum(age) over (order by age), \
+                 sum(age) over (order by id rows between 1 preceding and current row), \
+                 sum(age) over (order by age range between current row and 5 following), \
+                 count(*) over () from users order by id;",
+                vec![
+                    vec!["1", "30", "85", "30", "60", "3"],
+                    vec!["2", "55", "25", "55", "85", "3"],
+                    vec!["3", "85", "85", "55", "60", "3"],
+                ],
+            ),
+            (
+                "select id, sum(age) over (order by id rows between 2 following and \
+                 unbounded following), count(*) over (order by id rows 5 preceding) \
+                 from users order by id;",
+                vec![
+                    vec!["1", "30", "1"],
+                    vec!["2", "NULL", "2"],
+                    vec!["3", "NULL", "3"],
+                ],
+            ),
+            // windows are computed over the groups
+            (
+                "select age, count(*), sum(count(*)) over (), rank() over (order by count(*) desc) \
+                 from users group by age order by age;",
+                vec![vec!["25", "1", "3", "2"], vec!["30", "2", "3", "1"]],
+            ),
+            (
+                "select name, row_number() over (order by name desc) as r from users order by r;",
+                vec![vec!["carl", "1"], vec!["bob", "2"], vec!["ana", "3"]],
+            ),
+            (
+                "select name from users order by row_number() over (order by age desc, id);",
+                vec![vec!["ana"], vec!["carl"], vec!["bob"]],
+            ),
+            (
+                "select *, row_number() over (order by id) from users where id = 2;",
+                vec![vec!["2", "bob", "25", "1"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        for sql in [
+            "select id from users where row_number() over () > 1;",
+            "select sum(row_number() over ()) over () from users;",
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert!(
+                matches!(exec.run(query), Err(ExecutionError::WindowNotAllowed(..))),
+                "{sql}"
+            );
+        }
+        let query = SqlQuery::parse_format_error("select row_number() from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::WindowRequired(..))
+        ));
+        for sql in [
+            "select sum(age) over (rows between current row and 1 preceding) from users;",
+            "select sum(age) over (range between 1 preceding and current row) from users;",
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert!(
+                matches!(exec.run(query), Err(ExecutionError::InvalidFrame(..))),
+                "{sql}"
+            );
+        }
+        let query = SqlQuery::parse_format_error("select lag(id, -1) over () from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::InvalidArgument(..))
+        ));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select id from users union select author from posts order by id;",
+                vec![vec!["1"], vec!["2"], vec!["3"]],
+            ),
+            (
+                "select author from posts union all select id from users where age = 30 \
+                 order by author;",
+                vec![vec!["1"], vec!["1"], vec!["1"], vec!["3"], vec!["3"]],
+            ),
+            (
+                "select id from users intersect select author from posts order by id;",
+                vec![vec!["1"], vec!["3"]],
+            ),
+            (
+                "select author from posts intersect all select author from posts \
+                 where author = 1;",
+                vec![vec!["1"], vec!["1"]],
+            ),
+            (
+                "select id from users except select author from posts;",
+                vec![vec!["2"]],
+            ),
+            (
+                "select author from posts except all select id from users;",
+                vec![vec!["1"]],
+            ),
+            // INTERSECT is evaluated first
+            (
+                "select id from users where id = 2 union select id from users \
+                 intersect select author from posts order by id;",
+                vec![vec!["1"], vec!["2"], vec!["3"]],
+            ),
+            (
+                "select name from users union select title from posts \
+                 order by name desc limit 2 offset 1;",
+                vec![vec!["carl"], vec!["bye"]],
+            ),
+            (
+                "select null from users union select null from posts;",
+                vec![vec!["NULL"]],
+            ),
+            // a position names an output column
+            (
+                "select id from users union select age from users order by 1 desc limit 2;",
+                vec![vec!["30"], vec!["25"]],
+            ),
+            (
+                "select name, age from users union select title, author from posts \
+                 order by 2 desc, 1 limit 3;",
+                vec![vec!["ana", "30"], vec!["carl", "30"], vec!["bob", "25"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        let query = SqlQuery::parse_format_error(
+            "select id from users union select author from posts order by 2;",
+        )
+        .unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::PositionNotInSelectList(2))
+        ));
+
+        let query = SqlQuery::parse_format_error(
+            "select id, name from users union select author from posts;",
+        )
+        .unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::SetOperationColumnCount(op, 2, 1)) if op == "UNION"
+        ));
+    }
+
+    #[test]
+    fn test_order_by_position() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select name, age from users order by 2 desc, 1;",
+                vec![vec!["ana", "30"], vec!["carl", "30"], vec!["bob", "25"]],
+            ),
+            (
+                "select * from users order by 3, 2 desc;",
+                vec![
+                    vec!["2", "bob", "25"],
+                    vec!["3", "carl", "30"],
+                    vec!["1", "ana", "30"],
+                ],
+            ),
+            (
+                "select distinct on (2) name, age from users order by 2, 1;",
+                vec![vec!["bob", "25"], vec!["ana", "30"]],
+            ),
+            (
+                "select age, count(*) from users group by age order by 2 desc;",
+                vec![vec!["30", "2"], vec!["25", "1"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        for sql in [
+            "select id from users order by 2;",
+            "select id from users order by 0;",
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert!(
+                matches!(
+                    exec.run(query),
+                    Err(ExecutionError::PositionNotInSelectList(_))
+                ),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_distinct() {
+        let mut exec = executor();
+        for (sql, expected) in [
+            (
+                "select distinct age from users order by age;",
+                vec![vec!["25"], vec!["30"]],
+            ),
+            ("select distinct age % 5 from users;", vec![vec!["0"]]),
+            (
+                "select distinct * from posts;",
+                vec![vec!["1", "hello"], vec!["3", "bye"], vec!["1", "again"]],
+            ),
+            ("select distinct null from users;", vec![vec!["NULL"]]),
+            (
+                "select distinct on (age) age, name from users order by age, name desc;",
+                vec![vec!["25", "bob"], vec!["30", "carl"]],
+            ),
+            (
+                "select distinct on (who) author as who, title from posts order by who, title;",
+                vec![vec!["1", "again"], vec!["3", "bye"]],
+            ),
+            (
+                "select distinct on (age) count(*) from users group by age order by age;",
+                vec![vec!["1"], vec!["2"]],
+            ),
+            (
+                "select distinct author from posts union all select distinct author from posts;",
+                vec![vec!["1"], vec!["3"], vec!["1"], vec!["3"]],
+            ),
+        ] {
+            assert_eq!(select(&mut exec, sql), expected, "{sql}");
+        }
+
+        let query =
+            SqlQuery::parse_format_error("select distinct on (age) name from users order by name;")
+                .unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::DistinctOnOrder)
+        ));
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let mut exec = executor();
+        run(&mut exec, "create table names (name string, n int);");
+        run(
+            &mut exec,
+            "insert into names (n, name) select id * 10, name from users where age = 30;",
+        );
+        run(
+            &mut exec,
+            "insert into names select title, author from posts where author = 3;",
+        );
+        run(&mut exec, "insert into names (name) values 'solo';");
+        // the rows to insert are read before the table changes
+        run(
+            &mut exec,
+            "insert into names select name || '2', n from names where n > 10;",
+        );
+        assert_eq!(
+            select(&mut exec, "select * from names order by name;"),
+            [
+                ["ana", "10"],
+                ["bye", "3"],
+                ["carl", "30"],
+                ["carl2", "30"],
+                ["solo", "NULL"]
+            ]
+        );
+
+        let insert =
+            SqlQuery::parse_format_error("insert into names (n, name) select ?, name from users;")
+                .unwrap();
+        assert_eq!(
+            exec.prepare(insert).unwrap().parameters(),
+            [Some(SqlTypeInfo::Int)]
+        );
+
+        run(
+            &mut exec,
+            "create table stats using columnar as \
+             select age, count(*) as total, max(name) from users group by age;",
+        );
+        run(&mut exec, "insert into stats values 40, 0, 'x';");
+        assert_eq!(
+            select(&mut exec, "select * from stats order by age;"),
+            [["25", "1", "bob"], ["30", "2", "carl"], ["40", "0", "x"]]
+        );
+        // the columns come from the query even without rows
+        run(
+            &mut exec,
+            "create table nobody as select id, upper(name) as shout from users where id > 9;",
+        );
+        assert_eq!(select(&mut exec, "select count(*) from nobody;"), [["0"]]);
+        let query = SqlQuery::parse_format_error("insert into nobody values 'x';").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::WrongType(..))
+        ));
+
+        // numbers stored in an INT column are rounded the way CAST does
+        run(&mut exec, "create table whole (n int primary key);");
+        for sql in [
+            "insert into whole values 2.5;",
+            "insert into whole values -1.5;",
+            "insert into whole select id + 0.4 from users where id = 2;",
+            "insert into whole values 2.6 on conflict do nothing;",
+            "insert into whole values 3.2 on conflict (n) do update set n = whole.n + 0.7;",
+        ] {
+            run(&mut exec, sql);
+        }
+        assert_eq!(
+            select(&mut exec, "select * from whole order by n;"),
+            [["-2"], ["2"], ["4"]]
+        );
+        run(
+            &mut exec,
+            "create table halves as select id / 2 as h from users;",
+        );
+        assert_eq!(
+            select(&mut exec, "select h from halves order by h;"),
+            [["0"], ["1"], ["1"]]
+        );
+
+        for (sql, expected) in [
+            (
+                "insert into names (nope) values 'a';",
+                "Column nope does not exists",
+            ),
+            (
+                "insert into names (n, n) values 1, 2;",
+                "Column n is specified more than once",
+            ),
+            (
+                "insert into names (name) values 'a', 1;",
+                "INSERT has 1 target columns but 2 values",
+            ),
+            (
+                "insert into names (name, n) values 'a';",
+                "INSERT has 2 target columns but 1 values",
+            ),
+            (
+                "insert into names select name, name from users;",
+                "Type mismatch: column n is INT, found STRING",
+            ),
+            (
+                "create table bad as select id, id from users;",
+                "Column id is specified more than once",
+            ),
+            (
+                "create table bad as select id = 1 as same from users;",
+                "Cannot infer the type of column same: BOOLEAN values can not be stored",
+            ),
+            (
+                "create table bad as select null as nothing from users;",
+                "Cannot infer the type of column nothing: its values have no type, CAST them",
+            ),
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert_eq!(exec.run(query).unwrap_err().to_string(), expected, "{sql}");
+        }
+        let query = SqlQuery::parse_format_error("select * from bad;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::TableNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_upsert() {
+        let mut exec = executor();
+        let counts = |exec: &mut Executor, sql| match run(exec, sql) {
+            ExecutionResponse::Insert { inserted, updated } => (inserted, updated),
+            other => panic!("expected counts, got {other}"),
+        };
+        run(
+            &mut exec,
+            "create table stock (sku string primary key, qty int, note string, unique (note));",
+        );
+
+        for (sql, expected) in [
+            ("insert into stock values 'a', 1, 'x';", (1, 0)),
+            // rows with a NULL never conflict on a unique key
+            ("insert into stock (sku, qty) values 'b', 2;", (1, 0)),
+            ("insert into stock (sku, qty) values 'hello', 3;", (1, 0)),
+            (
+                "insert into stock values 'a', 5, 'y' on conflict do nothing;",
+                (0, 0),
+            ),
+            (
+                "insert into stock values 'a', 5, 'y' on conflict (sku) \
+                 do update set qty = qty + excluded.qty, note = excluded.note;",
+                (0, 1),
+            ),
+            (
+                "insert into stock (sku, qty) select title, author from posts \
+                 on conflict (sku) do update set qty = stock.qty + excluded.qty;",
+                (2, 1),
+            ),
+            (
+                "insert into stock select name, age, null from users where age = 30 \
+                 on conflict (sku) do nothing;",
+                (2, 0),
+            ),
+        ] {
+            assert_eq!(counts(&mut exec, sql), expected, "{sql}");
+        }
+        let rows = [
+            ["a", "6", "y"],
+            ["again", "1", "NULL"],
+            ["ana", "30", "NULL"],
+            ["b", "2", "NULL"],
+            ["bye", "3", "NULL"],
+            ["carl", "30", "NULL"],
+            ["hello", "4", "NULL"],
+        ];
+        assert_eq!(select(&mut exec, "select * from stock order by sku;"), rows);
+
+        // a statement that fails changes nothing
+        for (sql, expected) in [
+            (
+                "insert into stock values 'z', 1, 'y';",
+                "Duplicate value (y) for UNIQUE (note)",
+            ),
+            (
+                "insert into stock (qty) values 1;",
+                "Columns (sku) of the primary key can not be NULL",
+            ),
+            (
+                "insert into stock (sku, qty) select name || '!', id from users \
+                 union all select 'a', 0 from users where id = 1;",
+                "Duplicate value (a) for PRIMARY KEY (sku)",
+            ),
+            (
+                "insert into stock (sku, qty) select name, 0 from users \
+                 on conflict (sku) do update set note = 'y';",
+                "Duplicate value (y) for UNIQUE (note)",
+            ),
+            (
+                "insert into stock (sku, qty) select 'a', id from users \
+                 on conflict (sku) do update set qty = qty + excluded.qty;",
+                "ON CONFLICT DO UPDATE can not change the same row twice",
+            ),
+            // nor a row the statement inserted
+            (
+                "insert into stock (sku, qty) select 'new', id from users \
+                 on conflict (sku) do update set qty = excluded.qty;",
+                "ON CONFLICT DO UPDATE can not change the same row twice",
+            ),
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert_eq!(exec.run(query).unwrap_err().to_string(), expected, "{sql}");
         }
+        assert_eq!(select(&mut exec, "select * from stock order by sku;"), rows);
+
+        for (sql, expected) in [
+            (
+                "insert into stock values 'a', 1, 'x' on conflict (qty) do nothing;",
+                "No primary key or unique constraint matches the ON CONFLICT columns",
+            ),
+            (
+                "insert into stock values 'a', 1, 'x' on conflict (sku, sku) do nothing;",
+                "Column sku is specified more than once",
+            ),
+            // a repeated column does not hide that no key matches
+            (
+                "insert into stock values 'a', 1, 'x' on conflict (qty, qty) do nothing;",
+                "The statement has 2 errors",
+            ),
+            (
+                "insert into stock values 'a', 1, 'x' on conflict (sku) do update set qty = 'x';",
+                "Type mismatch: column qty is INT, found STRING",
+            ),
+            (
+                "create table bad (a int primary key, b int, primary key (b));",
+                "Table bad can not have more than one primary key",
+            ),
+            (
+                "create table bad (a int, unique (b));",
+                "Column b does not exists",
+            ),
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert_eq!(exec.run(query).unwrap_err().to_string(), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_upsert_columnar() {
+        let mut exec = executor();
+        run(
+            &mut exec,
+            "create table ages (age int, total int, primary key (age)) using columnar;",
+        );
+        let upsert = "insert into ages select distinct age, 1 from users \
+                      on conflict (age) do update set total = total + 1;";
+        run(&mut exec, upsert);
+        run(&mut exec, upsert);
+        let query = SqlQuery::parse_format_error(
+            "insert into ages values 40, 1 on conflict (age) do update set total = total + 1;",
+        )
+        .unwrap();
+        assert!(exec.run(query).is_ok());
+        let query =
+            SqlQuery::parse_format_error("insert into ages select 50, 1 from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::UniqueViolation(..))
+        ));
+
+        assert_eq!(
+            select(&mut exec, "select * from ages order by age;"),
+            [["25", "2"], ["30", "2"], ["40", "1"]]
+        );
+    }
+
+    #[test]
+    fn test_returning() {
+        let mut exec = executor();
+        run(
+            &mut exec,
+            "create table stock (sku string primary key, qty int);",
+        );
+
+        let ExecutionResponse::Select(rows) = run(
+            &mut exec,
+            "insert into stock values 'a', 2 returning sku, qty * 2 as double;",
+        ) else {
+            panic!("expected rows");
+        };
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].id(), 0);
+        assert_eq!(rows[0].get("sku"), "a");
+        assert_eq!(rows[0].get("double"), "4");
+
+        let ExecutionResponse::Select(rows) = run(
+            &mut exec,
+            "insert into stock select name, age from users returning *;",
+        ) else {
+            panic!("expected rows");
+        };
+        let ids: Vec<usize> = rows.iter().map(Row::id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        let names: Vec<&str> = rows[0].columns().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["sku", "qty"]);
+
+        // rows left alone by DO NOTHING are not returned, bob is stored
+        assert_eq!(
+            select(
+                &mut exec,
+                "insert into stock select name, id from users where id = 2 \
+                 union all select title, author from posts \
+                 on conflict do nothing returning sku, stock.qty;",
+            ),
+            [["hello", "1"], ["bye", "3"], ["again", "1"]]
+        );
+        assert_eq!(
+            select(
+                &mut exec,
+                "insert into stock values 'a', 5 on conflict (sku) \
+                 do update set qty = qty + excluded.qty returning qty;",
+            ),
+            [["7"]]
+        );
+
+        // a value that fails changes nothing
+        let query = SqlQuery::parse_format_error(
+            "insert into stock values 'z', 1 returning 1 / (qty - 1);",
+        )
+        .unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::DivisionByZero)
+        ));
+        assert_eq!(select(&mut exec, "select count(*) from stock;"), [["7"]]);
+
+        for (sql, expected) in [
+            (
+                "insert into stock values 'b', 1 returning price;",
+                "Column price does not exists",
+            ),
+            (
+                "insert into stock values 'b', 1 returning count(*);",
+                "Aggregate function count is not allowed here",
+            ),
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            assert_eq!(exec.run(query).unwrap_err().to_string(), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_columnar_matches_rows() {
+        let mut exec = Executor::new();
+        run(
+            &mut exec,
+            "create table users (id int, name string, age int);",
+        );
+        run(
+            &mut exec,
+            "create table userscol (id int, name string, age int) using columnar;",
+        );
+        for id in 0..3000 {
+            let name = ["ana", "bob", "carl"][id % 3];
+            run(
+                &mut exec,
+                &format!("insert into users values {id}, '{name}', {};", id % 7),
+            );
+            run(
+                &mut exec,
+                &format!("insert into userscol values {id}, '{name}', {};", id % 7),
+            );
+        }
+
+        let queries = [
+            "select * from {t} where age > 3 and id % 2 = 0;",
+            "select name, id * 2 as double from {t} where age = 1 or name = 'bob' limit 5 offset 100;",
+            "select count(*), sum(age), avg(age), min(name), max(id) from {t};",
+            "select age, count(*), sum(id) from {t} where id > 10 group by age order by age desc;",
+            "select count(id) from {t} where id > 100000;",
+            "select id, name from {t} where name like '%b%' and id between 10 and 50 and age in (1, 2);",
+        ];
+        for query in queries {
+            assert_eq!(
+                select(&mut exec, &query.replace("{t}", "users")),
+                select(&mut exec, &query.replace("{t}", "userscol")),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let mut exec = Executor::new();
+        run(
+            &mut exec,
+            "create table users (id int, name string, age int);",
+        );
+        run(
+            &mut exec,
+            "create table userscol (id int, name string, age int) using columnar;",
+        );
+        for id in 0..10000 {
+            let name = ["ana", "bob", "carl", "dan"][id % 4];
+            for table in ["users", "userscol"] {
+                run(
+                    &mut exec,
+                    &format!("insert into {table} values {id}, '{name}', {};", id % 9),
+                );
+            }
+        }
+
+        let queries = [
+            "select * from {t} where age > 3 and id % 2 = 0;",
+            "select name, id * 2 as double from {t} where age = 1 limit 5 offset 900;",
+            "select count(*), sum(age), avg(age), min(name), max(id) from {t};",
+            "select name, age, count(*), sum(id), avg(id) from {t} where id > 10 group by name, age;",
+            "select age, count(id) from {t} group by age order by age desc;",
+            "select count(*), min(id) from {t} where id > 100000;",
+        ];
+        for table in ["users", "userscol"] {
+            for query in queries {
+                let query = query.replace("{t}", table);
+                run(&mut exec, "set max_parallelism = 1;");
+                let serial = select(&mut exec, &query);
+                run(&mut exec, "set max_parallelism to 4;");
+                assert_eq!(select(&mut exec, &query), serial, "{query}");
+            }
+        }
+        assert_eq!(exec.settings().max_parallelism, 4);
+
+        let query = SqlQuery::parse_format_error("set max_parallelism = 0;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::InvalidSettingValue(..))
+        ));
+        let query = SqlQuery::parse_format_error("set missing = 1;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::SettingNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_prepared() {
+        let mut exec = executor();
+
+        let insert = SqlQuery::parse_format_error("insert into users values ?, ?, ? + 1;").unwrap();
+        let insert = exec.prepare(insert).unwrap();
+        assert_eq!(
+            insert.parameters(),
+            [
+                Some(SqlTypeInfo::Int),
+                Some(SqlTypeInfo::String),
+                Some(SqlTypeInfo::Int)
+            ]
+        );
+        let number = |n: u32| Value::Number(n.into());
+        for (id, name) in [(4, "dan"), (5, "o'hara")] {
+            let params = [number(id), Value::String(name.into()), number(40)];
+            exec.execute(&insert, &params).unwrap();
+        }
+
+        let query = SqlQuery::parse_format_error(
+            "select name from users where age > $2 and name <> $1 order by id;",
+        )
+        .unwrap();
+        let query = exec.prepare(query).unwrap();
+        assert_eq!(
+            query.parameters(),
+            [Some(SqlTypeInfo::String), Some(SqlTypeInfo::Int)]
+        );
+
+        let names = |response| match response {
+            ExecutionResponse::Select(rows) => rows
+                .iter()
+                .map(|row: &Row| row.values()[0].to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("expected rows, got {other}"),
+        };
+        let params = [Value::String("dan".into()), number(29)];
+        let response = exec.execute(&query, &params).unwrap();
+        assert_eq!(names(response), ["ana", "carl", "o'hara"]);
+
+        assert!(matches!(
+            exec.execute(&query, &[number(1)]),
+            Err(ExecutionError::ParameterCountMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            exec.execute(&query, &[number(1), number(2)]),
+            Err(ExecutionError::TypeMismatch(_))
+        ));
+
+        // function arguments take the declared types
+        let query = SqlQuery::parse_format_error(
+            "select id from users where substr(name, ?) = ? || upper(?);",
+        )
+        .unwrap();
+        assert_eq!(
+            exec.prepare(query).unwrap().parameters(),
+            [
+                Some(SqlTypeInfo::Int),
+                Some(SqlTypeInfo::String),
+                Some(SqlTypeInfo::String)
+            ]
+        );
+
+        // placeholders inside subqueries are typed by the same pass
+        let query = SqlQuery::parse_format_error(
+            "select id from users where id in (select age from users where age > ?) \
+             or exists (select 1 from users where name = ?);",
+        )
+        .unwrap();
+        let query = exec.prepare(query).unwrap();
+        assert_eq!(
+            query.parameters(),
+            [Some(SqlTypeInfo::Int), Some(SqlTypeInfo::String)]
+        );
+
+        let query = SqlQuery::parse_format_error("select id from users where id = ?;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::ParameterNotBound(..))
+        ));
+
+        let query = SqlQuery::parse_format_error("select id from users where id = ? and age = $1;")
+            .unwrap();
+        assert!(matches!(
+            exec.prepare(query),
+            Err(ExecutionError::MixedParameters)
+        ));
+    }
+
+    #[test]
+    fn test_script() {
+        let mut exec = Executor::new();
+
+        let script = Script::parse_format_error(
+            "create table t (a int); -- the table\n\
+             insert into t values 1; /* a row */ insert into t values 2;\n\
+             select b from t;\n\
+             insert into t values 3;",
+        )
+        .unwrap();
+        let error = exec.run_script(script).unwrap_err();
+
+        assert_eq!(error.index, 3);
+        assert!(matches!(
+            error.error,
+            ExecutionError::ColumnDoesNotExists(..)
+        ));
+        // the label points at the unknown column, not the whole statement
+        assert_eq!(error.span, (104, 1).into());
+        assert_eq!(error.responses.len(), 3);
+        assert_eq!(
+            select(&mut exec, "select a from t;"),
+            vec![vec!["1"], vec!["2"]]
+        );
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        let mut exec = executor();
+
+        let query = SqlQuery::parse_format_error("select name, count(*) from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::ColumnNotGrouped(..))
+        ));
+
+        let query = SqlQuery::parse_format_error("select id from users join posts on id = author;")
+            .unwrap();
+        assert!(exec.run(query).is_ok());
+
+        let query = SqlQuery::parse_format_error("select missing from users;").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::ColumnDoesNotExists(..))
+        ));
+
+        for (sql, span) in [
+            ("select missing from users;", (7, 7)),
+            ("select id from users where users.nope > 1;", (27, 10)),
+            ("select id from nowhere;", (15, 7)),
+            ("select id from users join posts on foo(id);", (35, 3)),
+            ("select name from users group by age;", (7, 4)),
+        ] {
+            let query = SqlQuery::parse_format_error(sql).unwrap();
+            let error = exec.run(query).unwrap_err();
+            assert_eq!(error.span(), Some(span.into()), "{sql}");
+        }
+
+        // every problem is reported, and nothing is stored
+        let query =
+            SqlQuery::parse_format_error("insert into users values 'x', nope, count(*);").unwrap();
+        assert!(matches!(
+            exec.run(query),
+            Err(ExecutionError::Semantic(errors)) if errors.len() == 3
+        ));
+        assert_eq!(select(&mut exec, "select count(*) from users;"), [["3"]]);
     }
 }
@@ -0,0 +1,537 @@
+use std::{borrow::Cow, cmp::Ordering, str::FromStr, sync::Arc};
+
+use bigdecimal::{BigDecimal, Zero};
+use sqlmicro_parser::{
+    expression::{BinaryOperator, UnaryOperator},
+    value::Value,
+    SqlTypeInfo,
+};
+
+use crate::{functions::ScalarFunction, vectorized::Batch, ExecutionError};
+
+/// Expression with its column references resolved to tuple positions
+#[derive(Debug, Clone, PartialEq)]
+pub enum PhysicalExpr {
+    Literal(Value),
+    Column(usize),
+    Binary {
+        left: Box<PhysicalExpr>,
+        op: BinaryOperator,
+        right: Box<PhysicalExpr>,
+    },
+    Unary {
+        op: UnaryOperator,
+        operand: Box<PhysicalExpr>,
+    },
+    /// `expr [NOT] IN (list)`
+    InList {
+        expr: Box<PhysicalExpr>,
+        list: Vec<PhysicalExpr>,
+        negated: bool,
+    },
+    /// `expr [NOT] LIKE | ILIKE pattern [ESCAPE escape]`
+    Like {
+        expr: Box<PhysicalExpr>,
+        pattern: Box<PhysicalExpr>,
+        escape: Option<Box<PhysicalExpr>>,
+        negated: bool,
+        case_insensitive: bool,
+    },
+    /// `CASE [operand] WHEN .. THEN .. [ELSE ..] END`, with an operand each
+    /// `WHEN` value is compared with it, otherwise it is a condition
+    Case {
+        operand: Option<Box<PhysicalExpr>>,
+        branches: Vec<(PhysicalExpr, PhysicalExpr)>,
+        else_result: Option<Box<PhysicalExpr>>,
+    },
+    /// The first of the arguments that is not `NULL`
+    Coalesce(Vec<PhysicalExpr>),
+    Cast {
+        expr: Box<PhysicalExpr>,
+        type_info: SqlTypeInfo,
+    },
+    /// Call of a scalar function of the registry
+    Function {
+        function: Arc<ScalarFunction>,
+        args: Vec<PhysicalExpr>,
+    },
+}
+
+impl PhysicalExpr {
+    pub fn evaluate(&self, tuple: &[Value]) -> Result<Value, ExecutionError> {
+        match self {
+            PhysicalExpr::Literal(value) => Ok(value.clone()),
+            PhysicalExpr::Column(index) => Ok(tuple[*index].clone()),
+            PhysicalExpr::Binary { left, op, right } => {
+                let left = left.evaluate(tuple)?;
+                // AND / OR only need the right side when the left one does not decide
+                match (op, &left) {
+                    (BinaryOperator::And, Value::Boolean(false)) => return Ok(left),
+                    (BinaryOperator::Or, Value::Boolean(true)) => return Ok(left),
+                    _ => {}
+                }
+                let right = right.evaluate(tuple)?;
+                binary_op(&left, *op, &right)
+            }
+            PhysicalExpr::Unary { op, operand } => unary_op(*op, &operand.evaluate(tuple)?),
+            PhysicalExpr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let list = list
+                    .iter()
+                    .map(|item| item.evaluate(tuple))
+                    .collect::<Result<Vec<_>, _>>()?;
+                in_list(&expr.evaluate(tuple)?, &list, *negated)
+            }
+            PhysicalExpr::Like {
+                expr,
+                pattern,
+                escape,
+                negated,
+                case_insensitive,
+            } => {
+                let escape = escape.as_ref().map(|e| e.evaluate(tuple)).transpose()?;
+                let matched = like(
+                    &expr.evaluate(tuple)?,
+                    &pattern.evaluate(tuple)?,
+                    escape.as_ref(),
+                    *case_insensitive,
+                )?;
+                match negated {
+                    true => unary_op(UnaryOperator::Not, &matched),
+                    false => Ok(matched),
+                }
+            }
+            PhysicalExpr::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                let operand = operand.as_ref().map(|o| o.evaluate(tuple)).transpose()?;
+                for (when, then) in branches {
+                    let when = when.evaluate(tuple)?;
+                    let matched = match &operand {
+                        Some(operand) => binary_op(operand, BinaryOperator::Eq, &when)?,
+                        None => when,
+                    };
+                    if truth(&matched)? {
+                        return then.evaluate(tuple);
+                    }
+                }
+                match else_result {
+                    Some(else_result) => else_result.evaluate(tuple),
+                    None => Ok(Value::Null),
+                }
+            }
+            PhysicalExpr::Coalesce(args) => {
+                for arg in args {
+                    match arg.evaluate(tuple)? {
+                        Value::Null => {}
+                        value => return Ok(value),
+                    }
+                }
+                Ok(Value::Null)
+            }
+            PhysicalExpr::Cast { expr, type_info } => cast(&expr.evaluate(tuple)?, *type_info),
+            PhysicalExpr::Function { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(tuple))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.call(&args)
+            }
+        }
+    }
+
+    /// Evaluate for every row of a batch at once
+    pub fn evaluate_batch<'b>(
+        &self,
+        batch: &'b Batch<'_>,
+    ) -> Result<Cow<'b, [Value]>, ExecutionError> {
+        match self {
+            PhysicalExpr::Literal(value) => Ok(Cow::Owned(vec![value.clone(); batch.len()])),
+            PhysicalExpr::Column(index) => Ok(Cow::Borrowed(batch.column(*index))),
+            PhysicalExpr::Binary { left, op, right } => {
+                let left = left.evaluate_batch(batch)?;
+                // as in `evaluate`, the right side of AND / OR is only computed
+                // for the rows the left side does not decide
+                let decided = match op {
+                    BinaryOperator::And => Some(Value::Boolean(false)),
+                    BinaryOperator::Or => Some(Value::Boolean(true)),
+                    _ => None,
+                };
+                let right = match decided {
+                    Some(decided) if left.contains(&decided) => {
+                        let pending: Vec<usize> =
+                            (0..batch.len()).filter(|&i| left[i] != decided).collect();
+                        let pending_batch = batch.gather(&pending);
+                        let partial = right.evaluate_batch(&pending_batch)?;
+                        let mut right = vec![Value::Null; batch.len()];
+                        for (i, value) in pending.into_iter().zip(partial.iter()) {
+                            right[i] = value.clone();
+                        }
+                        Cow::Owned(right)
+                    }
+                    _ => right.evaluate_batch(batch)?,
+                };
+                left.iter()
+                    .zip(right.iter())
+                    .map(|(l, r)| binary_op(l, *op, r))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Cow::Owned)
+            }
+            PhysicalExpr::Unary { op, operand } => operand
+                .evaluate_batch(batch)?
+                .iter()
+                .map(|value| unary_op(*op, value))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Cow::Owned),
+            PhysicalExpr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let values = expr.evaluate_batch(batch)?;
+                let list = list
+                    .iter()
+                    .map(|item| item.evaluate_batch(batch))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (0..batch.len())
+                    .map(|row| {
+                        let items: Vec<Value> = list.iter().map(|item| item[row].clone()).collect();
+                        in_list(&values[row], &items, *negated)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Cow::Owned)
+            }
+            PhysicalExpr::Like {
+                expr,
+                pattern,
+                escape,
+                negated,
+                case_insensitive,
+            } => {
+                let values = expr.evaluate_batch(batch)?;
+                let patterns = pattern.evaluate_batch(batch)?;
+                let escapes = escape
+                    .as_ref()
+                    .map(|escape| escape.evaluate_batch(batch))
+                    .transpose()?;
+                (0..batch.len())
+                    .map(|row| {
+                        let escape = escapes.as_ref().map(|escapes| &escapes[row]);
+                        let matched =
+                            like(&values[row], &patterns[row], escape, *case_insensitive)?;
+                        match negated {
+                            true => unary_op(UnaryOperator::Not, &matched),
+                            false => Ok(matched),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Cow::Owned)
+            }
+            // row by row, so a branch or an argument is only evaluated for
+            // the rows that reach it
+            PhysicalExpr::Case { .. } | PhysicalExpr::Coalesce(_) => (0..batch.len())
+                .map(|row| self.evaluate(&batch.row(row)))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Cow::Owned),
+            PhysicalExpr::Cast { expr, type_info } => expr
+                .evaluate_batch(batch)?
+                .iter()
+                .map(|value| cast(value, *type_info))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Cow::Owned),
+            PhysicalExpr::Function { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate_batch(batch))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (0..batch.len())
+                    .map(|row| {
+                        let values: Vec<Value> = args.iter().map(|arg| arg[row].clone()).collect();
+                        function.call(&values)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Cow::Owned)
+            }
+        }
+    }
+
+    /// Evaluate as a predicate, where only `TRUE` keeps the tuple
+    pub fn is_true(&self, tuple: &[Value]) -> Result<bool, ExecutionError> {
+        truth(&self.evaluate(tuple)?)
+    }
+}
+
+/// Whether a predicate result keeps its row, `NULL` counts as false
+pub fn truth(value: &Value) -> Result<bool, ExecutionError> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        Value::Null => Ok(false),
+        other => Err(ExecutionError::TypeMismatch(format!(
+            "expected a boolean condition, found {other}"
+        ))),
+    }
+}
+
+/// `value [NOT] IN (list)`: true when an item equals the value, and `NULL`
+/// rather than false when the value or an item that could have matched is
+/// `NULL`
+pub fn in_list(value: &Value, list: &[Value], negated: bool) -> Result<Value, ExecutionError> {
+    if list.is_empty() {
+        return Ok(Value::Boolean(negated));
+    }
+
+    let mut found = Value::Boolean(false);
+    for item in list {
+        match binary_op(value, BinaryOperator::Eq, item)? {
+            Value::Boolean(true) => {
+                found = Value::Boolean(true);
+                break;
+            }
+            Value::Null => found = Value::Null,
+            _ => {}
+        }
+    }
+
+    match (found, negated) {
+        (Value::Boolean(found), true) => Ok(Value::Boolean(!found)),
+        (found, _) => Ok(found),
+    }
+}
+
+/// Convert a value to `type_info`: a number is rounded to an `INT`, to the
+/// nearest integer with halves away from zero, a string has to hold a number
+/// which is rounded the same way and booleans are 1 or 0. Anything is a
+/// `STRING`, `NULL` stays `NULL`
+pub fn cast(value: &Value, type_info: SqlTypeInfo) -> Result<Value, ExecutionError> {
+    match (value, type_info) {
+        (Value::Null, _) => Ok(Value::Null),
+        (Value::Number(n), SqlTypeInfo::Int) => Ok(Value::Number(n.round(0))),
+        (Value::String(s), SqlTypeInfo::Int) => match BigDecimal::from_str(s.trim()) {
+            Ok(n) => Ok(Value::Number(n.round(0))),
+            Err(_) => Err(ExecutionError::InvalidCast(
+                value.to_sql(),
+                type_info.to_string(),
+            )),
+        },
+        (Value::Boolean(b), SqlTypeInfo::Int) => Ok(Value::Number(BigDecimal::from(*b as u8))),
+        (Value::String(_), SqlTypeInfo::String) => Ok(value.clone()),
+        (_, SqlTypeInfo::String) => Ok(Value::String(value.to_string())),
+    }
+}
+
+/// A piece of a `LIKE` pattern
+#[derive(Clone, Copy, PartialEq)]
+enum Wildcard {
+    /// `%`, any text, even empty
+    Any,
+    /// `_`, a single character
+    One,
+    Char(char),
+}
+
+/// `value LIKE pattern`: `%` and `_` match any text and any character, the
+/// escape character (`\` unless given, none when empty) makes the next one
+/// match itself. `NULL` when any of them is `NULL`
+pub fn like(
+    value: &Value,
+    pattern: &Value,
+    escape: Option<&Value>,
+    case_insensitive: bool,
+) -> Result<Value, ExecutionError> {
+    let escape = match escape {
+        None => Some('\\'),
+        Some(Value::Null) => return Ok(Value::Null),
+        Some(Value::String(escape)) => {
+            let mut chars = escape.chars();
+            match (chars.next(), chars.next()) {
+                (escape, None) => escape,
+                _ => {
+                    return Err(ExecutionError::InvalidPattern(format!(
+                        "ESCAPE has to be a single character, found '{escape}'"
+                    )))
+                }
+            }
+        }
+        Some(other) => {
+            return Err(ExecutionError::TypeMismatch(format!(
+                "ESCAPE expects a string, found {other}"
+            )))
+        }
+    };
+    let (value, pattern) = match (value, pattern) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+        (Value::String(value), Value::String(pattern)) => (value, pattern),
+        _ => return Err(mismatch("LIKE", value, pattern)),
+    };
+
+    let fold = |c: char| match case_insensitive {
+        true => c.to_lowercase().next().unwrap_or(c),
+        false => c,
+    };
+    let mut pieces = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        pieces.push(match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(c) => Wildcard::Char(fold(c)),
+                None => {
+                    return Err(ExecutionError::InvalidPattern(format!(
+                        "'{pattern}' ends with the escape character"
+                    )))
+                }
+            },
+            '%' => Wildcard::Any,
+            '_' => Wildcard::One,
+            c => Wildcard::Char(fold(c)),
+        });
+    }
+    let text: Vec<char> = value.chars().map(fold).collect();
+
+    Ok(Value::Boolean(wildcard_match(&text, &pieces)))
+}
+
+/// Match consuming as few characters as possible with each `%`, going back
+/// to the last one to take one more when the rest does not match
+fn wildcard_match(text: &[char], pattern: &[Wildcard]) -> bool {
+    let (mut t, mut p) = (0, 0);
+    // the last `%` seen and where in the text its match ends
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(Wildcard::Any) => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(Wildcard::One) => {
+                t += 1;
+                p += 1;
+            }
+            Some(Wildcard::Char(c)) if *c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                Some((any, end)) => {
+                    backtrack = Some((any, end + 1));
+                    p = any + 1;
+                    t = end + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|piece| *piece == Wildcard::Any)
+}
+
+fn mismatch(op: impl std::fmt::Display, left: &Value, right: &Value) -> ExecutionError {
+    ExecutionError::TypeMismatch(format!("cannot apply {op} to {left} and {right}"))
+}
+
+/// Compare two non null values of the same type
+pub fn compare(left: &Value, right: &Value) -> Result<Option<Ordering>, ExecutionError> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(None),
+        (Value::Number(l), Value::Number(r)) => Ok(Some(l.cmp(r))),
+        (Value::String(l), Value::String(r)) => Ok(Some(l.cmp(r))),
+        (Value::Boolean(l), Value::Boolean(r)) => Ok(Some(l.cmp(r))),
+        _ => Err(mismatch("comparison", left, right)),
+    }
+}
+
+/// Total order used for sorting, NULLs sort after every other value and
+/// values of different types are ordered by type
+pub fn sort_order(left: &Value, right: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Boolean(_) => 0,
+            Value::Number(_) => 1,
+            Value::String(_) => 2,
+            Value::Null => 3,
+        }
+    }
+
+    match compare(left, right) {
+        Ok(Some(ordering)) => ordering,
+        _ => rank(left).cmp(&rank(right)),
+    }
+}
+
+fn binary_op(left: &Value, op: BinaryOperator, right: &Value) -> Result<Value, ExecutionError> {
+    use BinaryOperator::*;
+
+    match op {
+        And => match (left, right) {
+            (Value::Boolean(false), _) | (_, Value::Boolean(false)) => Ok(Value::Boolean(false)),
+            (Value::Boolean(true), Value::Boolean(true)) => Ok(Value::Boolean(true)),
+            (Value::Null | Value::Boolean(_), Value::Null | Value::Boolean(_)) => Ok(Value::Null),
+            _ => Err(mismatch(op, left, right)),
+        },
+        Or => match (left, right) {
+            (Value::Boolean(true), _) | (_, Value::Boolean(true)) => Ok(Value::Boolean(true)),
+            (Value::Boolean(false), Value::Boolean(false)) => Ok(Value::Boolean(false)),
+            (Value::Null | Value::Boolean(_), Value::Null | Value::Boolean(_)) => Ok(Value::Null),
+            _ => Err(mismatch(op, left, right)),
+        },
+        Eq | NotEq | Lt | LtEq | Gt | GtEq => {
+            let Some(ordering) = compare(left, right)? else {
+                return Ok(Value::Null);
+            };
+            let result = match op {
+                Eq => ordering.is_eq(),
+                NotEq => ordering.is_ne(),
+                Lt => ordering.is_lt(),
+                LtEq => ordering.is_le(),
+                Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            };
+            Ok(Value::Boolean(result))
+        }
+        Plus | Minus | Multiply | Divide | Modulo => match (left, right) {
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (Value::Number(l), Value::Number(r)) => {
+                if matches!(op, Divide | Modulo) && r.is_zero() {
+                    return Err(ExecutionError::DivisionByZero);
+                }
+                let result = match op {
+                    Plus => l + r,
+                    Minus => l - r,
+                    Multiply => l * r,
+                    // integers divide to an integer, towards zero, a number
+                    // written with a fraction keeps the decimals
+                    Divide if integral(l) && integral(r) => (l / r).with_scale(0),
+                    Divide => l / r,
+                    _ => l % r,
+                };
+                Ok(Value::Number(result))
+            }
+            _ => Err(mismatch(op, left, right)),
+        },
+        Concat => match (left, right) {
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (Value::String(l), Value::String(r)) => Ok(Value::String(format!("{l}{r}"))),
+            _ => Err(mismatch(op, left, right)),
+        },
+    }
+}
+
+/// Whether a number has no fractional digits, as integer literals, the
+/// values of `INT` columns and the results of integer arithmetic
+fn integral(n: &BigDecimal) -> bool {
+    n.as_bigint_and_exponent().1 <= 0
+}
+
+fn unary_op(op: UnaryOperator, operand: &Value) -> Result<Value, ExecutionError> {
+    match (op, operand) {
+        (_, Value::Null) => Ok(Value::Null),
+        (UnaryOperator::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
+        (UnaryOperator::Minus, Value::Number(n)) => Ok(Value::Number(BigDecimal::zero() - n)),
+        _ => Err(ExecutionError::TypeMismatch(format!(
+            "cannot apply {op} to {operand}"
+        ))),
+    }
+}
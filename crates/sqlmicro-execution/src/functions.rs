@@ -0,0 +1,664 @@
+//! Scalar and user defined aggregate functions.
+//!
+//! The registry maps the name of each function to its signature, which the
+//! binder checks calls against, and to the code computing it for a row or a
+//! group. Applications add their own with
+//! [`crate::executor::Executor::register_scalar_function`] and
+//! [`crate::executor::Executor::register_aggregate_function`]. Scalar
+//! functions return `NULL` when any argument is `NULL`. There is no date
+//! type, timestamps are strings like `2024-05-17 13:45:00`, in UTC.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use sqlmicro_parser::value::Value;
+
+use crate::{
+    binder::DataType,
+    operator::{Accumulator, AggregateFunction, WindowFunction},
+    planner::conditional_arguments,
+    ExecutionError,
+};
+
+/// Computes a function from the values of its arguments
+pub type ScalarImplementation =
+    Arc<dyn Fn(&[Value]) -> Result<Value, ExecutionError> + Send + Sync>;
+
+/// A function computed for every row it is called on
+#[derive(Clone)]
+pub struct ScalarFunction {
+    name: String,
+    arguments: Vec<DataType>,
+    /// How many of the last arguments can be left out
+    optional: usize,
+    return_type: DataType,
+    implementation: ScalarImplementation,
+}
+
+impl ScalarFunction {
+    pub fn new(
+        name: impl Into<String>,
+        arguments: Vec<DataType>,
+        return_type: DataType,
+        implementation: impl Fn(&[Value]) -> Result<Value, ExecutionError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arguments,
+            optional: 0,
+            return_type,
+            implementation: Arc::new(implementation),
+        }
+    }
+
+    /// Let the last `count` arguments be left out
+    pub fn optional(mut self, count: usize) -> Self {
+        self.optional = count.min(self.arguments.len());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type of each argument, whether or not it can be left out
+    pub fn arguments(&self) -> &[DataType] {
+        &self.arguments
+    }
+
+    pub fn return_type(&self) -> DataType {
+        self.return_type
+    }
+
+    /// Whether a call can pass `count` arguments
+    pub fn accepts(&self, count: usize) -> bool {
+        (self.arguments.len() - self.optional..=self.arguments.len()).contains(&count)
+    }
+
+    /// The arguments a call can pass, as told in errors
+    pub fn arity(&self) -> String {
+        let count = |n: usize| match n {
+            0 => "no".to_string(),
+            1 => "one".to_string(),
+            2 => "two".to_string(),
+            3 => "three".to_string(),
+            n => n.to_string(),
+        };
+        let (min, max) = (self.arguments.len() - self.optional, self.arguments.len());
+        let plural = if max == 1 { "argument" } else { "arguments" };
+        match min == max {
+            true => format!("{} {plural}", count(max)),
+            false => format!("{} to {} {plural}", count(min), count(max)),
+        }
+    }
+
+    /// Compute the function, `NULL` when any argument is
+    pub fn call(&self, args: &[Value]) -> Result<Value, ExecutionError> {
+        match args.contains(&Value::Null) {
+            true => Ok(Value::Null),
+            false => returned(&self.name, self.return_type, (self.implementation)(args)?),
+        }
+    }
+}
+
+impl fmt::Debug for ScalarFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScalarFunction")
+            .field("name", &self.name)
+            .field("arguments", &self.arguments)
+            .field("optional", &self.optional)
+            .field("return_type", &self.return_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Functions are the same when they run the same code
+impl PartialEq for ScalarFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.implementation, &other.implementation)
+    }
+}
+
+/// Creates the accumulator of a group
+pub type AccumulatorFactory = Arc<dyn Fn() -> Box<dyn Accumulator> + Send + Sync>;
+
+/// An aggregate function of a single argument, computed by an
+/// [`Accumulator`] created for each group
+#[derive(Clone)]
+pub struct UserAggregate {
+    name: String,
+    argument: DataType,
+    return_type: DataType,
+    accumulator: AccumulatorFactory,
+}
+
+impl UserAggregate {
+    pub fn new<A: Accumulator + 'static>(
+        name: impl Into<String>,
+        argument: DataType,
+        return_type: DataType,
+        accumulator: impl Fn() -> A + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            argument,
+            return_type,
+            accumulator: Arc::new(move || Box::new(accumulator())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn argument(&self) -> DataType {
+        self.argument
+    }
+
+    pub fn return_type(&self) -> DataType {
+        self.return_type
+    }
+
+    /// A new accumulator, whose result is checked against the return type
+    pub fn accumulator(&self) -> Box<dyn Accumulator> {
+        Box::new(Checked {
+            name: self.name.clone(),
+            return_type: self.return_type,
+            inner: (self.accumulator)(),
+        })
+    }
+}
+
+impl fmt::Debug for UserAggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserAggregate")
+            .field("name", &self.name)
+            .field("argument", &self.argument)
+            .field("return_type", &self.return_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Aggregates are the same when they create the same accumulators
+impl PartialEq for UserAggregate {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.accumulator, &other.accumulator)
+    }
+}
+
+impl Eq for UserAggregate {}
+
+/// Accumulator of a user defined aggregate
+struct Checked {
+    name: String,
+    return_type: DataType,
+    inner: Box<dyn Accumulator>,
+}
+
+impl Accumulator for Checked {
+    fn update(&mut self, value: &Value) -> Result<(), ExecutionError> {
+        self.inner.update(value)
+    }
+
+    fn update_batch(&mut self, values: &[Value]) -> Result<(), ExecutionError> {
+        self.inner.update_batch(values)
+    }
+
+    fn state(&self) -> Vec<Value> {
+        self.inner.state()
+    }
+
+    fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError> {
+        self.inner.merge(state)
+    }
+
+    fn evaluate(&self) -> Result<Value, ExecutionError> {
+        returned(&self.name, self.return_type, self.inner.evaluate()?)
+    }
+}
+
+/// The result of a function, which has to be of the declared type
+fn returned(function: &str, return_type: DataType, value: Value) -> Result<Value, ExecutionError> {
+    match (&value, return_type) {
+        (Value::Null, _)
+        | (Value::Number(_), DataType::Int)
+        | (Value::String(_), DataType::String)
+        | (Value::Boolean(_), DataType::Boolean) => Ok(value),
+        _ => Err(ExecutionError::TypeMismatch(format!(
+            "function {function} returns {return_type}, found {value}"
+        ))),
+    }
+}
+
+/// The functions a statement can call by name
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry {
+    scalars: HashMap<String, Arc<ScalarFunction>>,
+    aggregates: HashMap<String, Arc<UserAggregate>>,
+}
+
+impl Default for FunctionRegistry {
+    /// The built-in functions
+    fn default() -> Self {
+        use DataType::*;
+
+        let mut registry = Self {
+            scalars: HashMap::new(),
+            aggregates: HashMap::new(),
+        };
+        for function in [
+            ScalarFunction::new("length", vec![String], Int, length),
+            ScalarFunction::new("upper", vec![String], String, upper),
+            ScalarFunction::new("lower", vec![String], String, lower),
+            ScalarFunction::new("substr", vec![String, Int, Int], String, substr).optional(1),
+            ScalarFunction::new("trim", vec![String], String, trim),
+            ScalarFunction::new("replace", vec![String, String, String], String, replace),
+            ScalarFunction::new("abs", vec![Int], Int, abs),
+            ScalarFunction::new("round", vec![Int, Int], Int, round).optional(1),
+            ScalarFunction::new("floor", vec![Int], Int, floor),
+            ScalarFunction::new("ceil", vec![Int], Int, ceil),
+            ScalarFunction::new("mod", vec![Int, Int], Int, modulo),
+            ScalarFunction::new("now", vec![], String, now),
+            ScalarFunction::new("date_trunc", vec![String, String], String, date_trunc),
+            ScalarFunction::new("extract", vec![String, String], Int, extract),
+        ] {
+            registry
+                .scalars
+                .insert(function.name().to_string(), Arc::new(function));
+        }
+        registry
+    }
+}
+
+impl FunctionRegistry {
+    /// Add a scalar function, replacing any function of the same name but
+    /// the built-in aggregates and window functions, `COALESCE` and `NULLIF`
+    pub fn register_scalar(&mut self, function: ScalarFunction) -> Result<(), ExecutionError> {
+        let name = self.replace(function.name())?;
+        self.scalars.insert(name, Arc::new(function));
+        Ok(())
+    }
+
+    /// Add an aggregate function, replacing any function of the same name
+    /// but the built-in aggregates and window functions, `COALESCE` and
+    /// `NULLIF`
+    pub fn register_aggregate(&mut self, function: UserAggregate) -> Result<(), ExecutionError> {
+        let name = self.replace(function.name())?;
+        self.aggregates.insert(name, Arc::new(function));
+        Ok(())
+    }
+
+    /// Remove the functions called `name`, which calls are resolved with
+    fn replace(&mut self, name: &str) -> Result<String, ExecutionError> {
+        // function names are case insensitive, the parser lowers them
+        let name = name.to_lowercase();
+        if AggregateFunction::from_name(&name).is_some()
+            || conditional_arguments(&name).is_some()
+            || WindowFunction::from_name(&name).is_some()
+        {
+            return Err(ExecutionError::BuiltinFunction(name));
+        }
+        self.scalars.remove(&name);
+        self.aggregates.remove(&name);
+        Ok(name)
+    }
+
+    pub fn scalar(&self, name: &str) -> Option<&Arc<ScalarFunction>> {
+        self.scalars.get(name)
+    }
+
+    /// The built-in or user defined aggregate called `name`
+    pub fn aggregate(&self, name: &str) -> Option<AggregateFunction> {
+        AggregateFunction::from_name(name).or_else(|| {
+            self.aggregates
+                .get(name)
+                .map(|function| AggregateFunction::User(function.clone()))
+        })
+    }
+}
+
+fn string(value: &Value) -> Result<&str, ExecutionError> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(ExecutionError::TypeMismatch(format!(
+            "expected a string, found {other}"
+        ))),
+    }
+}
+
+fn number(value: &Value) -> Result<&BigDecimal, ExecutionError> {
+    match value {
+        Value::Number(n) => Ok(n),
+        other => Err(ExecutionError::TypeMismatch(format!(
+            "expected a number, found {other}"
+        ))),
+    }
+}
+
+/// A number without a fractional part, for counts and positions
+fn integer(function: &str, value: &Value) -> Result<i64, ExecutionError> {
+    let n = number(value)?;
+    n.is_integer()
+        .then(|| n.to_i64())
+        .flatten()
+        .ok_or_else(|| invalid(function, format!("{n} is not an integer")))
+}
+
+fn invalid(function: &str, message: String) -> ExecutionError {
+    ExecutionError::InvalidArgument(function.to_string(), message)
+}
+
+fn length(args: &[Value]) -> Result<Value, ExecutionError> {
+    let length = string(&args[0])?.chars().count();
+    Ok(Value::Number((length as u64).into()))
+}
+
+fn upper(args: &[Value]) -> Result<Value, ExecutionError> {
+    Ok(Value::String(string(&args[0])?.to_uppercase()))
+}
+
+fn lower(args: &[Value]) -> Result<Value, ExecutionError> {
+    Ok(Value::String(string(&args[0])?.to_lowercase()))
+}
+
+/// `substr(s, start [, length])`, counting characters from 1. A start before
+/// the first character shortens the length by as many
+fn substr(args: &[Value]) -> Result<Value, ExecutionError> {
+    let s = string(&args[0])?;
+    let start = integer("substr", &args[1])?;
+    let end = match args.get(2) {
+        Some(length) => match integer("substr", length)? {
+            length if length < 0 => {
+                return Err(invalid("substr", format!("negative length {length}")))
+            }
+            length => start.saturating_add(length),
+        },
+        None => i64::MAX,
+    };
+    let skip = (start.max(1) - 1) as usize;
+    let take = (end.max(1) - start.max(1)) as usize;
+    Ok(Value::String(s.chars().skip(skip).take(take).collect()))
+}
+
+fn trim(args: &[Value]) -> Result<Value, ExecutionError> {
+    Ok(Value::String(string(&args[0])?.trim().to_string()))
+}
+
+fn replace(args: &[Value]) -> Result<Value, ExecutionError> {
+    let s = string(&args[0])?;
+    let from = string(&args[1])?;
+    match from.is_empty() {
+        true => Ok(Value::String(s.to_string())),
+        false => Ok(Value::String(s.replace(from, string(&args[2])?))),
+    }
+}
+
+fn abs(args: &[Value]) -> Result<Value, ExecutionError> {
+    Ok(Value::Number(number(&args[0])?.abs()))
+}
+
+/// `round(n [, digits])`, halves away from zero
+fn round(args: &[Value]) -> Result<Value, ExecutionError> {
+    let n = number(&args[0])?;
+    let digits = match args.get(1) {
+        Some(digits) => integer("round", digits)?,
+        None => 0,
+    };
+    let rounded = n.round(digits);
+    // keep it printed as a plain integer
+    Ok(Value::Number(match digits < 0 {
+        true => rounded.with_scale(0),
+        false => rounded,
+    }))
+}
+
+fn floor(args: &[Value]) -> Result<Value, ExecutionError> {
+    let n = number(&args[0])?;
+    // the scale change rounds towards zero
+    let truncated = n.with_scale(0);
+    Ok(Value::Number(match truncated > *n {
+        true => truncated - BigDecimal::from(1),
+        false => truncated,
+    }))
+}
+
+fn ceil(args: &[Value]) -> Result<Value, ExecutionError> {
+    let n = number(&args[0])?;
+    let truncated = n.with_scale(0);
+    Ok(Value::Number(match truncated < *n {
+        true => truncated + BigDecimal::from(1),
+        false => truncated,
+    }))
+}
+
+/// `mod(a, b)`, with the sign of `a` like `%`
+fn modulo(args: &[Value]) -> Result<Value, ExecutionError> {
+    let (a, b) = (number(&args[0])?, number(&args[1])?);
+    if b.is_zero() {
+        return Err(ExecutionError::DivisionByZero);
+    }
+    Ok(Value::Number(a % b))
+}
+
+fn now(_args: &[Value]) -> Result<Value, ExecutionError> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    Ok(Value::String(Timestamp::from_epoch(seconds).to_string()))
+}
+
+/// `date_trunc(field, timestamp)`, the start of the year, month, week, day,
+/// hour, minute or second the timestamp is in
+fn date_trunc(args: &[Value]) -> Result<Value, ExecutionError> {
+    let field = string(&args[0])?.to_lowercase();
+    let ts = Timestamp::parse("date_trunc", string(&args[1])?)?;
+    let (year, month, _) = ts.date();
+    let truncated = match field.as_str() {
+        "year" => Timestamp::new(days_from_civil(year, 1, 1), 0),
+        "month" => Timestamp::new(days_from_civil(year, month, 1), 0),
+        // weeks start on monday
+        "week" => Timestamp::new(ts.days - (ts.weekday() + 6) % 7, 0),
+        "day" => Timestamp::new(ts.days, 0),
+        "hour" => Timestamp::new(ts.days, ts.seconds - ts.seconds % 3600),
+        "minute" => Timestamp::new(ts.days, ts.seconds - ts.seconds % 60),
+        "second" => ts,
+        _ => return Err(invalid("date_trunc", format!("unknown field '{field}'"))),
+    };
+    Ok(Value::String(truncated.to_string()))
+}
+
+/// `extract(field, timestamp)`, also written `EXTRACT(field FROM timestamp)`
+fn extract(args: &[Value]) -> Result<Value, ExecutionError> {
+    let field = string(&args[0])?.to_lowercase();
+    let ts = Timestamp::parse("extract", string(&args[1])?)?;
+    let (year, month, day) = ts.date();
+    let value = match field.as_str() {
+        "year" => year,
+        "month" => month,
+        "day" => day,
+        "hour" => ts.seconds / 3600,
+        "minute" => ts.seconds / 60 % 60,
+        "second" => ts.seconds % 60,
+        // from 0 on sunday to 6 on saturday
+        "dow" => ts.weekday(),
+        "doy" => ts.days - days_from_civil(year, 1, 1) + 1,
+        "epoch" => ts.days * SECONDS_PER_DAY + ts.seconds,
+        _ => return Err(invalid("extract", format!("unknown field '{field}'"))),
+    };
+    Ok(Value::Number(value.into()))
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A point in time to the second
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Timestamp {
+    /// Since 1970-01-01
+    days: i64,
+    /// Into the day
+    seconds: i64,
+}
+
+impl Timestamp {
+    fn new(days: i64, seconds: i64) -> Self {
+        Self { days, seconds }
+    }
+
+    fn from_epoch(seconds: i64) -> Self {
+        Self::new(
+            seconds.div_euclid(SECONDS_PER_DAY),
+            seconds.rem_euclid(SECONDS_PER_DAY),
+        )
+    }
+
+    /// `YYYY-MM-DD`, followed by `HH:MM[:SS]` after a space or a `T`.
+    /// Fractions of a second are dropped
+    fn parse(function: &str, text: &str) -> Result<Self, ExecutionError> {
+        fn fields<const N: usize>(text: &str, separator: char) -> Option<[i64; N]> {
+            let mut fields = [0; N];
+            let mut parts = text.split(separator);
+            for field in &mut fields {
+                let part = parts.next()?;
+                if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                *field = part.parse().ok()?;
+            }
+            parts.next().is_none().then_some(fields)
+        }
+
+        let text = text.trim();
+        let (date, time) = match text.split_once([' ', 'T']) {
+            Some((date, time)) => (date, Some(time.split('.').next().unwrap_or_default())),
+            None => (text, None),
+        };
+        let timestamp = fields::<3>(date, '-').and_then(|[year, month, day]| {
+            if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+                return None;
+            }
+            let [hour, minute, second] = match time {
+                None => [0; 3],
+                Some(time) => fields::<3>(time, ':')
+                    .or_else(|| fields::<2>(time, ':').map(|[hour, minute]| [hour, minute, 0]))?,
+            };
+            (hour < 24 && minute < 60 && second < 60).then(|| {
+                Self::new(
+                    days_from_civil(year, month, day),
+                    hour * 3600 + minute * 60 + second,
+                )
+            })
+        });
+        timestamp.ok_or_else(|| invalid(function, format!("'{text}' is not a timestamp")))
+    }
+
+    /// Year, month and day
+    fn date(&self) -> (i64, i64, i64) {
+        civil_from_days(self.days)
+    }
+
+    /// From 0 on sunday to 6 on saturday
+    fn weekday(&self) -> i64 {
+        // 1970-01-01 was a thursday
+        (self.days + 4).rem_euclid(7)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.date();
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+            self.seconds / 3600,
+            self.seconds / 60 % 60,
+            self.seconds % 60
+        )
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a date of the proleptic gregorian calendar,
+/// counting in eras of 400 years that start on march 1st
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of [`days_from_civil`]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_days() {
+        for (date, days) in [
+            ((1970, 1, 1), 0),
+            ((2000, 2, 29), 11016),
+            ((2024, 3, 1), 19783),
+            ((1969, 12, 31), -1),
+        ] {
+            assert_eq!(days_from_civil(date.0, date.1, date.2), days, "{date:?}");
+            assert_eq!(civil_from_days(days), date);
+        }
+    }
+
+    #[test]
+    fn test_timestamps() {
+        for (text, expected) in [
+            ("2024-02-29", Some("2024-02-29 00:00:00")),
+            ("2024-02-29T13:05", Some("2024-02-29 13:05:00")),
+            (" 1999-12-31 23:59:59.75 ", Some("1999-12-31 23:59:59")),
+            ("2023-02-29", None),
+            ("2024-1-1 24:00:00", None),
+            ("2024-01-01 10", None),
+            ("yesterday", None),
+        ] {
+            let parsed = Timestamp::parse("test", text).ok();
+            assert_eq!(
+                parsed.map(|ts| ts.to_string()).as_deref(),
+                expected,
+                "{text}"
+            );
+        }
+        assert_eq!(Timestamp::from_epoch(-1).to_string(), "1969-12-31 23:59:59");
+    }
+}
@@ -1,6 +1,17 @@
+mod binder;
 pub mod error;
 pub mod executor;
+pub mod expression;
+pub mod functions;
+pub mod operator;
+pub mod parallel;
+mod planner;
+pub mod prepared;
 pub mod row;
+pub mod schema;
+pub mod settings;
 pub mod table;
+pub mod vectorized;
 
+pub use binder::DataType;
 pub use error::*;
@@ -0,0 +1,355 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use sqlmicro_parser::value::Value;
+
+use crate::{
+    expression::{sort_order, PhysicalExpr},
+    functions::UserAggregate,
+    schema::Schema,
+    ExecutionError,
+};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Running state of an aggregate function over one group
+pub trait Accumulator: Send {
+    fn update(&mut self, value: &Value) -> Result<(), ExecutionError>;
+
+    /// Update with every value of a column slice
+    fn update_batch(&mut self, values: &[Value]) -> Result<(), ExecutionError> {
+        values.iter().try_for_each(|value| self.update(value))
+    }
+
+    /// Intermediate state, so partial results computed in parallel can be
+    /// combined with `merge`
+    fn state(&self) -> Vec<Value>;
+
+    /// Combine the `state` of another accumulator of the same function
+    fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError>;
+
+    fn evaluate(&self) -> Result<Value, ExecutionError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    /// Registered by the application
+    User(Arc<UserAggregate>),
+}
+
+impl AggregateFunction {
+    /// The built-in aggregate called `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Self::Count),
+            "sum" => Some(Self::Sum),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "avg" => Some(Self::Avg),
+            _ => None,
+        }
+    }
+
+    pub fn accumulator(&self) -> Box<dyn Accumulator> {
+        match self {
+            Self::Count => Box::<CountAccumulator>::default(),
+            Self::Sum => Box::<SumAccumulator>::default(),
+            Self::Min => Box::new(ExtremeAccumulator::new(true)),
+            Self::Max => Box::new(ExtremeAccumulator::new(false)),
+            Self::Avg => Box::<AvgAccumulator>::default(),
+            Self::User(function) => function.accumulator(),
+        }
+    }
+}
+
+fn expect_number(value: &Value) -> Result<&BigDecimal, ExecutionError> {
+    match value {
+        Value::Number(n) => Ok(n),
+        other => Err(ExecutionError::TypeMismatch(format!(
+            "expected a number, found {other}"
+        ))),
+    }
+}
+
+#[derive(Default)]
+struct CountAccumulator {
+    count: usize,
+}
+
+impl Accumulator for CountAccumulator {
+    fn update(&mut self, value: &Value) -> Result<(), ExecutionError> {
+        if *value != Value::Null {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn update_batch(&mut self, values: &[Value]) -> Result<(), ExecutionError> {
+        self.count += values.iter().filter(|value| **value != Value::Null).count();
+        Ok(())
+    }
+
+    fn state(&self) -> Vec<Value> {
+        vec![Value::Number(BigDecimal::from(self.count as u64))]
+    }
+
+    fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError> {
+        let count = expect_number(&state[0])?;
+        self.count += count.to_u64().unwrap_or_default() as usize;
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<Value, ExecutionError> {
+        Ok(Value::Number(BigDecimal::from(self.count as u64)))
+    }
+}
+
+#[derive(Default)]
+struct SumAccumulator {
+    sum: Option<BigDecimal>,
+}
+
+impl Accumulator for SumAccumulator {
+    fn update(&mut self, value: &Value) -> Result<(), ExecutionError> {
+        if *value != Value::Null {
+            let sum = self.sum.get_or_insert_with(BigDecimal::zero);
+            *sum += expect_number(value)?;
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> Vec<Value> {
+        vec![self.sum.clone().map_or(Value::Null, Value::Number)]
+    }
+
+    fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError> {
+        self.update(&state[0])
+    }
+
+    fn evaluate(&self) -> Result<Value, ExecutionError> {
+        Ok(self.sum.clone().map_or(Value::Null, Value::Number))
+    }
+}
+
+#[derive(Default)]
+struct AvgAccumulator {
+    sum: BigDecimal,
+    count: usize,
+}
+
+impl Accumulator for AvgAccumulator {
+    fn update(&mut self, value: &Value) -> Result<(), ExecutionError> {
+        if *value != Value::Null {
+            self.sum += expect_number(value)?;
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> Vec<Value> {
+        vec![
+            Value::Number(self.sum.clone()),
+            Value::Number(BigDecimal::from(self.count as u64)),
+        ]
+    }
+
+    fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError> {
+        self.sum += expect_number(&state[0])?;
+        self.count += expect_number(&state[1])?.to_u64().unwrap_or_default() as usize;
+        Ok(())
+    }
+
+    fn evaluate(&self) -> Result<Value, ExecutionError> {
+        if self.count == 0 {
+            return Ok(Value::Null);
+        }
+        Ok(Value::Number(
+            &self.sum / BigDecimal::from(self.count as u64),
+        ))
+    }
+}
+
+/// Keeps the smallest (or largest) value seen
+struct ExtremeAccumulator {
+    min: bool,
+    value: Value,
+}
+
+impl ExtremeAccumulator {
+    fn new(min: bool) -> Self {
+        Self {
+            min,
+            value: Value::Null,
+        }
+    }
+}
+
+impl Accumulator for ExtremeAccumulator {
+    fn update(&mut self, value: &Value) -> Result<(), ExecutionError> {
+        let ordering = sort_order(value, &self.value);
+        let replace = match self.min {
+            true => ordering.is_lt(),
+            false => self.value == Value::Null || ordering.is_gt(),
+        };
+        if *value != Value::Null && replace {
+            self.value = value.clone();
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> Vec<Value> {
+        vec![self.value.clone()]
+    }
+
+    fn merge(&mut self, state: &[Value]) -> Result<(), ExecutionError> {
+        self.update(&state[0])
+    }
+
+    fn evaluate(&self) -> Result<Value, ExecutionError> {
+        Ok(self.value.clone())
+    }
+}
+
+/// An aggregate function applied to an argument, no argument means `count(*)`
+#[derive(Debug, Clone)]
+pub struct AggregateExpr {
+    pub function: AggregateFunction,
+    pub arg: Option<PhysicalExpr>,
+}
+
+/// Accumulators of every group seen so far, in the order groups were first
+/// seen. Without GROUP BY there is exactly one group, even for no input
+pub(crate) struct Groups {
+    functions: Vec<AggregateFunction>,
+    positions: HashMap<Tuple, usize>,
+    groups: Vec<(Tuple, Vec<Box<dyn Accumulator>>)>,
+}
+
+impl Groups {
+    pub fn new(aggregates: &[AggregateExpr], grouped: bool) -> Self {
+        let mut groups = Self {
+            functions: aggregates.iter().map(|agg| agg.function.clone()).collect(),
+            positions: HashMap::new(),
+            groups: Vec::new(),
+        };
+        if !grouped {
+            groups.group(Vec::new());
+        }
+        groups
+    }
+
+    /// Accumulators of the group with this key, created if needed
+    pub fn group(&mut self, key: Tuple) -> &mut [Box<dyn Accumulator>] {
+        let position = match self.positions.get(&key) {
+            Some(position) => *position,
+            None => {
+                let accumulators = self.functions.iter().map(|f| f.accumulator()).collect();
+                self.positions.insert(key.clone(), self.groups.len());
+                self.groups.push((key, accumulators));
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[position].1
+    }
+
+    /// Fold in the groups of `other`, keeping the first seen order of `self`
+    /// followed by the groups only `other` has
+    pub fn merge(&mut self, other: Groups) -> Result<(), ExecutionError> {
+        for (key, accumulators) in other.groups {
+            for (acc, other) in self.group(key).iter_mut().zip(accumulators) {
+                acc.merge(&other.state())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One tuple per group, the key followed by the aggregate values
+    pub fn finish(self) -> Result<Vec<Tuple>, ExecutionError> {
+        self.groups
+            .into_iter()
+            .map(|(mut key, accumulators)| {
+                for acc in accumulators {
+                    key.push(acc.evaluate()?);
+                }
+                Ok(key)
+            })
+            .collect()
+    }
+}
+
+/// Hash aggregation, yields the group by values followed by one value per
+/// aggregate for every group, in the order groups were first seen
+pub struct Aggregate<'a> {
+    input: BoxedOperator<'a>,
+    group_by: Vec<PhysicalExpr>,
+    aggregates: Vec<AggregateExpr>,
+    schema: Schema,
+    output: std::vec::IntoIter<Tuple>,
+}
+
+impl<'a> Aggregate<'a> {
+    pub fn new(
+        input: BoxedOperator<'a>,
+        group_by: Vec<PhysicalExpr>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            input,
+            group_by,
+            aggregates,
+            schema,
+            output: Vec::new().into_iter(),
+        }
+    }
+
+    /// Consume the whole input into per group accumulators
+    pub(crate) fn accumulate(&mut self) -> Result<Groups, ExecutionError> {
+        self.input.open()?;
+
+        let mut groups = Groups::new(&self.aggregates, !self.group_by.is_empty());
+
+        while let Some(tuple) = self.input.next()? {
+            let key = self
+                .group_by
+                .iter()
+                .map(|expr| expr.evaluate(&tuple))
+                .collect::<Result<Tuple, _>>()?;
+
+            for (agg, acc) in self.aggregates.iter().zip(groups.group(key)) {
+                match &agg.arg {
+                    Some(arg) => acc.update(&arg.evaluate(&tuple)?)?,
+                    // count(*) counts rows, so feed it any non null value
+                    None => acc.update(&Value::Boolean(true))?,
+                }
+            }
+        }
+
+        Ok(groups)
+    }
+}
+
+impl<'a> Operator for Aggregate<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.output = self.accumulate()?.finish()?.into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        Ok(self.output.next())
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.output = Vec::new().into_iter();
+        self.input.close()
+    }
+}
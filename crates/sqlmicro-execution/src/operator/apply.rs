@@ -0,0 +1,142 @@
+use sqlmicro_parser::{value::Value, visit::VisitorMut, SelectStatement};
+
+use crate::{
+    expression::{in_list, PhysicalExpr},
+    planner::Planner,
+    prepared::Bind,
+    schema::{Field, Schema},
+    ExecutionError,
+};
+
+use super::{tuples, BoxedOperator, Operator, Tuple};
+
+/// A subquery with its references to the outer row replaced by `$1`, `$2`,
+/// ... so it can be run for a row by binding the row's values
+#[derive(Debug, Clone)]
+pub(crate) struct Template {
+    pub select: SelectStatement,
+    /// Position in the outer tuple of the column each placeholder stands for
+    pub outer: Vec<usize>,
+}
+
+impl Template {
+    pub fn is_correlated(&self) -> bool {
+        !self.outer.is_empty()
+    }
+}
+
+/// How the rows of a subquery become a value
+#[derive(Debug, Clone)]
+pub(crate) enum SubqueryKind {
+    /// The single value returned, `NULL` when there is no row
+    Scalar,
+    /// Whether any row is returned
+    Exists,
+    /// Whether `test`, evaluated on the outer row, is among the values
+    In { test: PhysicalExpr, negated: bool },
+}
+
+impl SubqueryKind {
+    /// Run `select`, the rows of a subquery used as a value can only have
+    /// one column
+    pub fn run(
+        &self,
+        planner: &Planner<'_>,
+        select: &SelectStatement,
+    ) -> Result<Vec<Tuple>, ExecutionError> {
+        let plan = planner.plan_select(select)?;
+        let columns = plan.schema().len();
+        if columns != 1 && !matches!(self, SubqueryKind::Exists) {
+            return Err(ExecutionError::SubqueryColumnCount(columns));
+        }
+        tuples(plan)
+    }
+
+    /// Value of the subquery for the outer tuple `outer`
+    pub fn value(&self, rows: Vec<Tuple>, outer: &[Value]) -> Result<Value, ExecutionError> {
+        match self {
+            SubqueryKind::Scalar => scalar(rows),
+            SubqueryKind::Exists => Ok(Value::Boolean(!rows.is_empty())),
+            SubqueryKind::In { test, negated } => {
+                let values: Vec<Value> = rows.into_iter().map(first).collect();
+                in_list(&test.evaluate(outer)?, &values, *negated)
+            }
+        }
+    }
+}
+
+pub(crate) fn scalar(rows: Vec<Tuple>) -> Result<Value, ExecutionError> {
+    if rows.len() > 1 {
+        return Err(ExecutionError::SubqueryTooManyRows);
+    }
+    Ok(rows.into_iter().next().map_or(Value::Null, first))
+}
+
+pub(crate) fn first(tuple: Tuple) -> Value {
+    tuple.into_iter().next().unwrap_or(Value::Null)
+}
+
+/// A subquery that reads the outer row, run again for every tuple
+#[derive(Debug, Clone)]
+pub(crate) struct Correlated {
+    pub kind: SubqueryKind,
+    pub template: Template,
+}
+
+/// Appends to every input tuple the value of each correlated subquery for
+/// it, expressions over the input read them as the columns after its own
+pub(crate) struct Apply<'a> {
+    input: BoxedOperator<'a>,
+    subqueries: Vec<Correlated>,
+    planner: Planner<'a>,
+    schema: Schema,
+}
+
+impl<'a> Apply<'a> {
+    pub fn new(
+        input: BoxedOperator<'a>,
+        subqueries: Vec<Correlated>,
+        planner: Planner<'a>,
+    ) -> Self {
+        let mut fields = input.schema().fields().to_vec();
+        fields.extend(subqueries.iter().map(|_| Field::new(None, "?column?")));
+        Self {
+            input,
+            subqueries,
+            planner,
+            schema: Schema::new(fields),
+        }
+    }
+}
+
+impl<'a> Operator for Apply<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        let Some(mut tuple) = self.input.next()? else {
+            return Ok(None);
+        };
+
+        let mut values = Vec::with_capacity(self.subqueries.len());
+        for Correlated { kind, template } in &self.subqueries {
+            let params: Vec<Value> = template.outer.iter().map(|&i| tuple[i].clone()).collect();
+            let mut select = template.select.clone();
+            Bind(&params).visit_select_mut(&mut select);
+            let rows = kind.run(&self.planner, &select)?;
+            values.push(kind.value(rows, &tuple)?);
+        }
+        tuple.extend(values);
+
+        Ok(Some(tuple))
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.input.close()
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use sqlmicro_parser::value::Value;
+
+use crate::{expression::PhysicalExpr, schema::Schema, ExecutionError};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Yields the first tuple of each distinct value of the keys, NULLs being
+/// equal to each other
+pub struct HashDistinct<'a> {
+    input: BoxedOperator<'a>,
+    keys: Vec<PhysicalExpr>,
+    seen: HashSet<Vec<Value>>,
+}
+
+impl<'a> HashDistinct<'a> {
+    pub fn new(input: BoxedOperator<'a>, keys: Vec<PhysicalExpr>) -> Self {
+        Self {
+            input,
+            keys,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Distinct over every column of the input
+    pub fn rows(input: BoxedOperator<'a>) -> Self {
+        let keys = (0..input.schema().len())
+            .map(PhysicalExpr::Column)
+            .collect();
+        Self::new(input, keys)
+    }
+}
+
+impl<'a> Operator for HashDistinct<'a> {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.seen.clear();
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        while let Some(tuple) = self.input.next()? {
+            let key = self
+                .keys
+                .iter()
+                .map(|key| key.evaluate(&tuple))
+                .collect::<Result<Vec<_>, _>>()?;
+            if self.seen.insert(key) {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.seen.clear();
+        self.input.close()
+    }
+}
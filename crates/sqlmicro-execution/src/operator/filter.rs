@@ -0,0 +1,38 @@
+use crate::{expression::PhysicalExpr, schema::Schema, ExecutionError};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Yields the input tuples for which the predicate is true
+pub struct Filter<'a> {
+    input: BoxedOperator<'a>,
+    predicate: PhysicalExpr,
+}
+
+impl<'a> Filter<'a> {
+    pub fn new(input: BoxedOperator<'a>, predicate: PhysicalExpr) -> Self {
+        Self { input, predicate }
+    }
+}
+
+impl<'a> Operator for Filter<'a> {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        while let Some(tuple) = self.input.next()? {
+            if self.predicate.is_true(&tuple)? {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.input.close()
+    }
+}
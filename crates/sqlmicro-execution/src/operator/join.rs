@@ -0,0 +1,152 @@
+use crate::{expression::PhysicalExpr, schema::Schema, ExecutionError};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Inner join that materializes the right input and checks the condition
+/// against every pair of tuples
+pub struct NestedLoopJoin<'a> {
+    left: BoxedOperator<'a>,
+    right: BoxedOperator<'a>,
+    on: PhysicalExpr,
+    schema: Schema,
+    right_tuples: Vec<Tuple>,
+    current: Option<Tuple>,
+    position: usize,
+}
+
+impl<'a> NestedLoopJoin<'a> {
+    pub fn new(left: BoxedOperator<'a>, right: BoxedOperator<'a>, on: PhysicalExpr) -> Self {
+        let schema = left.schema().join(right.schema());
+        Self {
+            left,
+            right,
+            on,
+            schema,
+            right_tuples: Vec::new(),
+            current: None,
+            position: 0,
+        }
+    }
+}
+
+impl<'a> Operator for NestedLoopJoin<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.left.open()?;
+        self.right.open()?;
+
+        self.right_tuples.clear();
+        while let Some(tuple) = self.right.next()? {
+            self.right_tuples.push(tuple);
+        }
+        self.right.close()?;
+
+        self.current = None;
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        loop {
+            let Some(left) = &self.current else {
+                match self.left.next()? {
+                    Some(tuple) => {
+                        self.current = Some(tuple);
+                        self.position = 0;
+                        continue;
+                    }
+                    None => return Ok(None),
+                }
+            };
+
+            let Some(right) = self.right_tuples.get(self.position) else {
+                self.current = None;
+                continue;
+            };
+            self.position += 1;
+
+            let joined: Tuple = left.iter().chain(right).cloned().collect();
+            if self.on.is_true(&joined)? {
+                return Ok(Some(joined));
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.right_tuples.clear();
+        self.current = None;
+        self.left.close()
+    }
+}
+
+/// Keeps the outer tuples for which some inner tuple satisfies the
+/// condition, or with `anti` the ones for which none does. It is what an
+/// `EXISTS` or `IN` subquery that reads the outer row becomes. The condition
+/// is written in terms of the subquery, so it sees the inner tuple followed
+/// by the outer one
+pub struct SemiJoin<'a> {
+    outer: BoxedOperator<'a>,
+    inner: BoxedOperator<'a>,
+    on: PhysicalExpr,
+    anti: bool,
+    inner_tuples: Vec<Tuple>,
+}
+
+impl<'a> SemiJoin<'a> {
+    pub fn new(
+        outer: BoxedOperator<'a>,
+        inner: BoxedOperator<'a>,
+        on: PhysicalExpr,
+        anti: bool,
+    ) -> Self {
+        Self {
+            outer,
+            inner,
+            on,
+            anti,
+            inner_tuples: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Operator for SemiJoin<'a> {
+    fn schema(&self) -> &Schema {
+        self.outer.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.outer.open()?;
+        self.inner.open()?;
+
+        self.inner_tuples.clear();
+        while let Some(tuple) = self.inner.next()? {
+            self.inner_tuples.push(tuple);
+        }
+        self.inner.close()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        while let Some(outer) = self.outer.next()? {
+            let mut matched = false;
+            for inner in &self.inner_tuples {
+                let joined: Tuple = inner.iter().chain(&outer).cloned().collect();
+                if self.on.is_true(&joined)? {
+                    matched = true;
+                    break;
+                }
+            }
+            if matched != self.anti {
+                return Ok(Some(outer));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.inner_tuples.clear();
+        self.outer.close()
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{schema::Schema, ExecutionError};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Skips `offset` tuples then yields at most `limit` of them
+pub struct Limit<'a> {
+    input: BoxedOperator<'a>,
+    limit: Option<usize>,
+    offset: usize,
+    produced: usize,
+}
+
+impl<'a> Limit<'a> {
+    pub fn new(input: BoxedOperator<'a>, limit: Option<usize>, offset: usize) -> Self {
+        Self {
+            input,
+            limit,
+            offset,
+            produced: 0,
+        }
+    }
+}
+
+impl<'a> Operator for Limit<'a> {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.produced = 0;
+        self.input.open()?;
+        for _ in 0..self.offset {
+            if self.input.next()?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        if self.limit.is_some_and(|limit| self.produced >= limit) {
+            return Ok(None);
+        }
+
+        let tuple = self.input.next()?;
+        if tuple.is_some() {
+            self.produced += 1;
+        }
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.input.close()
+    }
+}
@@ -0,0 +1,81 @@
+//! Pull based (volcano style) physical operators.
+//!
+//! Every operator yields tuples one at a time from `next`, pulling from its
+//! inputs as needed, so a query is executed by composing operators into a tree
+//! and draining the root.
+
+mod aggregate;
+mod apply;
+mod distinct;
+mod filter;
+mod join;
+mod limit;
+mod project;
+mod scan;
+mod set_operation;
+mod sort;
+mod window;
+
+use std::rc::Rc;
+
+pub(crate) use aggregate::Groups;
+pub use aggregate::*;
+pub(crate) use apply::*;
+pub use distinct::*;
+pub use filter::*;
+pub use join::*;
+pub use limit::*;
+pub use project::*;
+pub(crate) use scan::table_schema;
+pub use scan::*;
+pub use set_operation::*;
+pub use sort::*;
+use sqlmicro_parser::value::Value;
+pub(crate) use window::window_frame;
+pub use window::*;
+
+use crate::{row::Row, schema::Schema, ExecutionError};
+
+pub type Tuple = Vec<Value>;
+
+pub trait Operator {
+    /// Fields of the tuples returned by `next`
+    fn schema(&self) -> &Schema;
+
+    /// Prepare the operator (and its inputs) to produce tuples
+    fn open(&mut self) -> Result<(), ExecutionError>;
+
+    /// Produce the next tuple, `None` once the operator is exhausted
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError>;
+
+    /// Release any state held since `open`
+    fn close(&mut self) -> Result<(), ExecutionError>;
+}
+
+pub type BoxedOperator<'a> = Box<dyn Operator + 'a>;
+
+/// Run an operator tree to completion, keeping only the values
+pub(crate) fn tuples(mut root: BoxedOperator<'_>) -> Result<Vec<Tuple>, ExecutionError> {
+    root.open()?;
+    let mut tuples = Vec::new();
+    while let Some(tuple) = root.next()? {
+        tuples.push(tuple);
+    }
+    root.close()?;
+
+    Ok(tuples)
+}
+
+/// Run an operator tree to completion
+pub fn collect(mut root: BoxedOperator<'_>) -> Result<Vec<Row>, ExecutionError> {
+    let schema = Rc::new(root.schema().clone());
+
+    root.open()?;
+    let mut rows = Vec::new();
+    while let Some(tuple) = root.next()? {
+        rows.push(Row::new(schema.clone(), rows.len(), tuple));
+    }
+    root.close()?;
+
+    Ok(rows)
+}
@@ -0,0 +1,48 @@
+use crate::{expression::PhysicalExpr, schema::Schema, ExecutionError};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Evaluates one expression per output field for every input tuple
+pub struct Project<'a> {
+    input: BoxedOperator<'a>,
+    exprs: Vec<PhysicalExpr>,
+    schema: Schema,
+}
+
+impl<'a> Project<'a> {
+    pub fn new(input: BoxedOperator<'a>, exprs: Vec<PhysicalExpr>, schema: Schema) -> Self {
+        Self {
+            input,
+            exprs,
+            schema,
+        }
+    }
+}
+
+impl<'a> Operator for Project<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        let Some(tuple) = self.input.next()? else {
+            return Ok(None);
+        };
+
+        let projected = self
+            .exprs
+            .iter()
+            .map(|expr| expr.evaluate(&tuple))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(projected))
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.input.close()
+    }
+}
@@ -0,0 +1,108 @@
+use std::{ops::Range, rc::Rc};
+
+use crate::{
+    schema::{Field, Schema},
+    table::Table,
+    ExecutionError,
+};
+
+use super::{Operator, Tuple};
+
+/// Scan of a stored table, in row id order
+pub struct Scan<'a> {
+    table: &'a Table,
+    schema: Schema,
+    range: Option<Range<usize>>,
+    tuples: Option<Box<dyn Iterator<Item = Tuple> + 'a>>,
+}
+
+impl<'a> Scan<'a> {
+    pub(crate) fn new(name: &str, table: &'a Table) -> Self {
+        Self {
+            table,
+            schema: table_schema(name, table),
+            range: None,
+            tuples: None,
+        }
+    }
+
+    /// Scan only the rows of one partition, see [`Table::partitions`]
+    pub(crate) fn partition(name: &str, table: &'a Table, range: Range<usize>) -> Self {
+        Self {
+            range: Some(range),
+            ..Self::new(name, table)
+        }
+    }
+}
+
+/// Scan of rows computed while the query was planned, like those of a
+/// common table expression
+pub struct MaterializedScan {
+    schema: Schema,
+    rows: Rc<Vec<Tuple>>,
+    position: usize,
+}
+
+impl MaterializedScan {
+    pub fn new(schema: Schema, rows: Rc<Vec<Tuple>>) -> Self {
+        Self {
+            schema,
+            rows,
+            position: 0,
+        }
+    }
+}
+
+impl Operator for MaterializedScan {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        let tuple = self.rows.get(self.position).cloned();
+        self.position += 1;
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        Ok(())
+    }
+}
+
+pub(crate) fn table_schema(name: &str, table: &Table) -> Schema {
+    Schema::new(
+        table
+            .columns()
+            .iter()
+            .map(|col| Field::new(Some(name.to_string()), col.name.node.clone()))
+            .collect(),
+    )
+}
+
+impl<'a> Operator for Scan<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.tuples = Some(match &self.range {
+            Some(range) => self.table.tuples_in(range.clone()),
+            None => self.table.tuples(),
+        });
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        Ok(self.tuples.as_mut().and_then(|tuples| tuples.next()))
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.tuples = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlmicro_parser::SetOperator;
+
+use crate::{schema::Schema, ExecutionError};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+/// Combines the tuples of two inputs with the same number of columns.
+/// Without `all` the result has no duplicates, NULLs being equal to each
+/// other; with it a tuple is kept as many times as the operator allows: all
+/// of them for `UNION`, the smaller count for `INTERSECT` and the difference
+/// of counts for `EXCEPT`
+pub struct HashSetOperation<'a> {
+    left: BoxedOperator<'a>,
+    right: BoxedOperator<'a>,
+    op: SetOperator,
+    all: bool,
+    schema: Schema,
+    /// How many times each tuple of the right input is still available to
+    /// match, for `INTERSECT` and `EXCEPT`
+    right_counts: HashMap<Tuple, usize>,
+    seen: HashSet<Tuple>,
+    left_done: bool,
+}
+
+impl<'a> HashSetOperation<'a> {
+    /// The columns are named after the left input's
+    pub fn new(
+        left: BoxedOperator<'a>,
+        right: BoxedOperator<'a>,
+        op: SetOperator,
+        all: bool,
+    ) -> Self {
+        let schema = left.schema().unqualified();
+        Self {
+            left,
+            right,
+            op,
+            all,
+            schema,
+            right_counts: HashMap::new(),
+            seen: HashSet::new(),
+            left_done: false,
+        }
+    }
+
+    /// Whether a tuple of the left input is part of the result
+    fn keep(&mut self, tuple: &Tuple) -> bool {
+        let matched = match self.right_counts.get_mut(tuple) {
+            Some(count) if *count > 0 => {
+                if self.all {
+                    *count -= 1;
+                }
+                true
+            }
+            _ => false,
+        };
+        let kept = match self.op {
+            SetOperator::Union => true,
+            SetOperator::Intersect => matched,
+            SetOperator::Except => !matched,
+        };
+        kept && (self.all || self.seen.insert(tuple.clone()))
+    }
+}
+
+impl<'a> Operator for HashSetOperation<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.left.open()?;
+        self.right.open()?;
+
+        self.right_counts.clear();
+        self.seen.clear();
+        self.left_done = false;
+        if self.op != SetOperator::Union {
+            while let Some(tuple) = self.right.next()? {
+                *self.right_counts.entry(tuple).or_default() += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        while !self.left_done {
+            match self.left.next()? {
+                Some(tuple) => {
+                    if self.keep(&tuple) {
+                        return Ok(Some(tuple));
+                    }
+                }
+                None => self.left_done = true,
+            }
+        }
+
+        if self.op == SetOperator::Union {
+            while let Some(tuple) = self.right.next()? {
+                if self.all || self.seen.insert(tuple.clone()) {
+                    return Ok(Some(tuple));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.right_counts.clear();
+        self.seen.clear();
+        self.left.close()?;
+        self.right.close()
+    }
+}
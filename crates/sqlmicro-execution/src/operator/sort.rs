@@ -0,0 +1,82 @@
+use std::cmp::Ordering;
+
+use crate::{
+    expression::{sort_order, PhysicalExpr},
+    schema::Schema,
+    ExecutionError,
+};
+
+use super::{BoxedOperator, Operator, Tuple};
+
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub expr: PhysicalExpr,
+    pub asc: bool,
+}
+
+/// Materializes the whole input on `open` and yields it ordered by the keys
+pub struct Sort<'a> {
+    input: BoxedOperator<'a>,
+    keys: Vec<SortKey>,
+    sorted: std::vec::IntoIter<Tuple>,
+}
+
+impl<'a> Sort<'a> {
+    pub fn new(input: BoxedOperator<'a>, keys: Vec<SortKey>) -> Self {
+        Self {
+            input,
+            keys,
+            sorted: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a> Operator for Sort<'a> {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()?;
+
+        let mut keyed = Vec::new();
+        while let Some(tuple) = self.input.next()? {
+            let key = self
+                .keys
+                .iter()
+                .map(|key| key.expr.evaluate(&tuple))
+                .collect::<Result<Vec<_>, _>>()?;
+            keyed.push((key, tuple));
+        }
+
+        // stable sort so ties keep their input order
+        keyed.sort_by(|(left, _), (right, _)| {
+            left.iter()
+                .zip(right)
+                .zip(&self.keys)
+                .map(|((l, r), key)| match key.asc {
+                    true => sort_order(l, r),
+                    false => sort_order(r, l),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+
+        self.sorted = keyed
+            .into_iter()
+            .map(|(_, tuple)| tuple)
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        Ok(self.sorted.next())
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.sorted = Vec::new().into_iter();
+        self.input.close()
+    }
+}
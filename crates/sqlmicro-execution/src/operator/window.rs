@@ -0,0 +1,431 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use sqlmicro_parser::{
+    expression::{self, FrameBound, FrameUnits, WindowFrame},
+    value::Value,
+};
+
+use crate::{
+    expression::{sort_order, PhysicalExpr},
+    schema::Schema,
+    ExecutionError,
+};
+
+use super::{Accumulator, AggregateFunction, BoxedOperator, Operator, SortKey, Tuple};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowFunction {
+    RowNumber,
+    /// Position of the first peer of the row, leaving gaps after ties
+    Rank,
+    /// Number of distinct peer groups up to the row, without gaps
+    DenseRank,
+    /// `lag(value [, offset [, default]])`, the value of the row `offset`
+    /// rows before, or the default when there is none
+    Lag,
+    /// `lead(value [, offset [, default]])`, like `lag` with the rows after
+    Lead,
+    /// The value of the first row of the frame
+    FirstValue,
+    /// An aggregate over the frame of each row
+    Aggregate(AggregateFunction),
+}
+
+impl WindowFunction {
+    /// The function called `name` that can only be called with `OVER`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "row_number" => Some(Self::RowNumber),
+            "rank" => Some(Self::Rank),
+            "dense_rank" => Some(Self::DenseRank),
+            "lag" => Some(Self::Lag),
+            "lead" => Some(Self::Lead),
+            "first_value" => Some(Self::FirstValue),
+            _ => None,
+        }
+    }
+
+    /// Whether it can be called with `count` arguments
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Self::RowNumber | Self::Rank | Self::DenseRank => count == 0,
+            Self::Lag | Self::Lead => (1..=3).contains(&count),
+            Self::FirstValue | Self::Aggregate(_) => count == 1,
+        }
+    }
+
+    /// The arguments it takes, for errors
+    pub fn arity(&self) -> &'static str {
+        match self {
+            Self::RowNumber | Self::Rank | Self::DenseRank => "no arguments",
+            Self::Lag | Self::Lead => "one to three arguments",
+            Self::FirstValue | Self::Aggregate(_) => "one argument",
+        }
+    }
+}
+
+/// The frame of a window, the default one when it has none. Fails for
+/// frames whose start comes after their end whatever the row, and for
+/// `RANGE` offsets without a single `ORDER BY` value to apply them to
+pub(crate) fn window_frame(over: &expression::Window) -> Result<WindowFrame, ExecutionError> {
+    let frame = over.frame.unwrap_or(WindowFrame {
+        units: FrameUnits::Range,
+        start: FrameBound::UnboundedPreceding,
+        end: FrameBound::CurrentRow,
+    });
+
+    let rank = |bound| match bound {
+        FrameBound::UnboundedPreceding => 0,
+        FrameBound::Preceding(_) => 1,
+        FrameBound::CurrentRow => 2,
+        FrameBound::Following(_) => 3,
+        FrameBound::UnboundedFollowing => 4,
+    };
+    let offset = |bound| matches!(bound, FrameBound::Preceding(_) | FrameBound::Following(_));
+
+    let message = if frame.start == FrameBound::UnboundedFollowing {
+        "it can not start at UNBOUNDED FOLLOWING".to_string()
+    } else if frame.end == FrameBound::UnboundedPreceding {
+        "it can not end at UNBOUNDED PRECEDING".to_string()
+    } else if rank(frame.start) > rank(frame.end) {
+        format!(
+            "it can not start at {} and end at {}",
+            frame.start, frame.end
+        )
+    } else if frame.units == FrameUnits::Range
+        && (offset(frame.start) || offset(frame.end))
+        && over.order_by.len() != 1
+    {
+        "RANGE with an offset needs exactly one ORDER BY expression".to_string()
+    } else {
+        return Ok(frame);
+    };
+    Err(ExecutionError::InvalidFrame(message))
+}
+
+/// A window function with its arguments, no argument for `count(*)`, and the
+/// window it reads
+#[derive(Debug, Clone)]
+pub struct WindowExpr {
+    pub function: WindowFunction,
+    pub args: Vec<PhysicalExpr>,
+    pub partition_by: Vec<PhysicalExpr>,
+    pub order_by: Vec<SortKey>,
+    pub frame: WindowFrame,
+}
+
+impl WindowExpr {
+    /// The value of the function for each tuple
+    fn evaluate(&self, tuples: &[Tuple]) -> Result<Vec<Value>, ExecutionError> {
+        let mut positions = HashMap::new();
+        let mut partitions: Vec<Vec<usize>> = Vec::new();
+        let mut keys = Vec::with_capacity(tuples.len());
+        let mut args = Vec::with_capacity(tuples.len());
+        for (index, tuple) in tuples.iter().enumerate() {
+            let partition = self
+                .partition_by
+                .iter()
+                .map(|expr| expr.evaluate(tuple))
+                .collect::<Result<Tuple, _>>()?;
+            let position = *positions.entry(partition).or_insert_with(|| {
+                partitions.push(Vec::new());
+                partitions.len() - 1
+            });
+            partitions[position].push(index);
+
+            keys.push(
+                self.order_by
+                    .iter()
+                    .map(|key| key.expr.evaluate(tuple))
+                    .collect::<Result<Tuple, _>>()?,
+            );
+            args.push(
+                self.args
+                    .iter()
+                    .map(|arg| arg.evaluate(tuple))
+                    .collect::<Result<Tuple, _>>()?,
+            );
+        }
+
+        let mut values = vec![Value::Null; tuples.len()];
+        for mut rows in partitions {
+            // stable sort so peers keep their input order
+            rows.sort_by(|&left, &right| self.compare(&keys[left], &keys[right]));
+            let partition = Partition {
+                keys: rows.iter().map(|&row| keys[row].as_slice()).collect(),
+                args: rows.iter().map(|&row| args[row].as_slice()).collect(),
+            };
+            for (row, value) in rows.iter().zip(self.partition(&partition)?) {
+                values[*row] = value;
+            }
+        }
+        Ok(values)
+    }
+
+    fn compare(&self, left: &[Value], right: &[Value]) -> Ordering {
+        left.iter()
+            .zip(right)
+            .zip(&self.order_by)
+            .map(|((l, r), key)| match key.asc {
+                true => sort_order(l, r),
+                false => sort_order(r, l),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// The values of the rows of a partition, in window order
+    fn partition(&self, partition: &Partition<'_>) -> Result<Vec<Value>, ExecutionError> {
+        let count = partition.keys.len();
+        let peers = self.peers(&partition.keys);
+        let number = |n: usize| Value::Number(BigDecimal::from(n as u64));
+
+        match &self.function {
+            WindowFunction::RowNumber => Ok((1..=count).map(number).collect()),
+            WindowFunction::Rank => Ok(peers.iter().map(|(first, _)| number(first + 1)).collect()),
+            WindowFunction::DenseRank => {
+                let mut rank = 0;
+                Ok(peers
+                    .iter()
+                    .enumerate()
+                    .map(|(position, (first, _))| {
+                        if *first == position {
+                            rank += 1;
+                        }
+                        number(rank)
+                    })
+                    .collect())
+            }
+            WindowFunction::Lag | WindowFunction::Lead => (0..count)
+                .map(|position| {
+                    let args = partition.args[position];
+                    let offset = match args.get(1) {
+                        None => 1,
+                        Some(Value::Null) => return Ok(Value::Null),
+                        Some(Value::Number(n)) if n.is_integer() && *n >= BigDecimal::from(0) => {
+                            n.to_usize().unwrap_or(usize::MAX)
+                        }
+                        Some(other) => {
+                            let name = match self.function {
+                                WindowFunction::Lag => "lag",
+                                _ => "lead",
+                            };
+                            return Err(ExecutionError::InvalidArgument(
+                                name.to_string(),
+                                format!("the offset can not be {other}"),
+                            ));
+                        }
+                    };
+                    let target = match self.function {
+                        WindowFunction::Lag => position.checked_sub(offset),
+                        _ => position
+                            .checked_add(offset)
+                            .filter(|target| *target < count),
+                    };
+                    Ok(match target {
+                        Some(target) => partition.args[target][0].clone(),
+                        None => args.get(2).cloned().unwrap_or(Value::Null),
+                    })
+                })
+                .collect(),
+            WindowFunction::FirstValue => (0..count)
+                .map(|position| {
+                    let (start, end) = self.frame(position, partition, &peers)?;
+                    Ok(match start < end {
+                        true => partition.args[start][0].clone(),
+                        false => Value::Null,
+                    })
+                })
+                .collect(),
+            // a frame that keeps its start and grows is only fed the rows
+            // it gained, which makes running totals linear
+            WindowFunction::Aggregate(function) => {
+                let mut values = Vec::with_capacity(count);
+                let mut current: Option<(usize, usize, Box<dyn Accumulator>)> = None;
+                for position in 0..count {
+                    let (start, end) = self.frame(position, partition, &peers)?;
+                    let (from, mut accumulator) = match current.take() {
+                        Some((first, last, accumulator)) if first == start && last <= end => {
+                            (last, accumulator)
+                        }
+                        _ => (start, function.accumulator()),
+                    };
+                    for args in &partition.args[from..end] {
+                        match args.first() {
+                            Some(value) => accumulator.update(value)?,
+                            // count(*) counts rows, so feed it any non null value
+                            None => accumulator.update(&Value::Boolean(true))?,
+                        }
+                    }
+                    values.push(accumulator.evaluate()?);
+                    current = Some((start, end, accumulator));
+                }
+                Ok(values)
+            }
+        }
+    }
+
+    /// First and one past the last peer of every row, the rows with the same
+    /// `ORDER BY` values
+    fn peers(&self, keys: &[&[Value]]) -> Vec<(usize, usize)> {
+        let mut peers = Vec::with_capacity(keys.len());
+        let mut first = 0;
+        while first < keys.len() {
+            let last = (first + 1..keys.len())
+                .find(|&next| self.compare(keys[first], keys[next]).is_ne())
+                .unwrap_or(keys.len());
+            peers.extend(std::iter::repeat_n((first, last), last - first));
+            first = last;
+        }
+        peers
+    }
+
+    /// First and one past the last row of the frame of the row at
+    /// `position`, the frame is empty when the start is not before the end
+    fn frame(
+        &self,
+        position: usize,
+        partition: &Partition<'_>,
+        peers: &[(usize, usize)],
+    ) -> Result<(usize, usize), ExecutionError> {
+        let start = self.bound(self.frame.start, false, position, partition, peers)?;
+        let end = self.bound(self.frame.end, true, position, partition, peers)?;
+        Ok((start, end.max(start)))
+    }
+
+    fn bound(
+        &self,
+        bound: FrameBound,
+        end: bool,
+        position: usize,
+        partition: &Partition<'_>,
+        peers: &[(usize, usize)],
+    ) -> Result<usize, ExecutionError> {
+        let count = partition.keys.len();
+        let offset = match (bound, self.frame.units) {
+            (FrameBound::UnboundedPreceding, _) => return Ok(0),
+            (FrameBound::UnboundedFollowing, _) => return Ok(count),
+            (FrameBound::CurrentRow, FrameUnits::Rows) => return Ok(position + end as usize),
+            (FrameBound::CurrentRow, FrameUnits::Range) => {
+                let (first, last) = peers[position];
+                return Ok(if end { last } else { first });
+            }
+            (FrameBound::Preceding(n), FrameUnits::Rows) => {
+                return Ok((position + end as usize).saturating_sub(n))
+            }
+            (FrameBound::Following(n), FrameUnits::Rows) => {
+                return Ok((position + n + end as usize).min(count))
+            }
+            (FrameBound::Preceding(n), FrameUnits::Range) => -BigDecimal::from(n as u64),
+            (FrameBound::Following(n), FrameUnits::Range) => BigDecimal::from(n as u64),
+        };
+
+        // a NULL is only within range of the other NULLs, its peers
+        let asc = self.order_by[0].asc;
+        let value = match partition.keys[position][0] {
+            Value::Null => {
+                let (first, last) = peers[position];
+                return Ok(if end { last } else { first });
+            }
+            ref value => number(value)?,
+        };
+        let limit = match asc {
+            true => value + offset,
+            false => value - offset,
+        };
+
+        // the rows are sorted, the frame starts at the first one not before
+        // the limit and ends after the last one not past it
+        let mut before = Ok(());
+        let bound = partition.keys.partition_point(|keys| match &keys[0] {
+            Value::Null => !asc,
+            value => match number(value) {
+                Ok(value) => match (asc, end) {
+                    (true, false) => *value < limit,
+                    (true, true) => *value <= limit,
+                    (false, false) => *value > limit,
+                    (false, true) => *value >= limit,
+                },
+                Err(error) => {
+                    before = Err(error);
+                    false
+                }
+            },
+        });
+        before.map(|_| bound)
+    }
+}
+
+fn number(value: &Value) -> Result<&BigDecimal, ExecutionError> {
+    match value {
+        Value::Number(n) => Ok(n),
+        other => Err(ExecutionError::TypeMismatch(format!(
+            "RANGE with an offset needs a numeric ORDER BY value, found {other}"
+        ))),
+    }
+}
+
+/// What a window function reads of the rows of a partition, in window order
+struct Partition<'r> {
+    keys: Vec<&'r [Value]>,
+    args: Vec<&'r [Value]>,
+}
+
+/// Computes window functions over the whole input, yields every input tuple
+/// followed by the value of each window function for it, in input order
+pub struct Window<'a> {
+    input: BoxedOperator<'a>,
+    windows: Vec<WindowExpr>,
+    schema: Schema,
+    output: std::vec::IntoIter<Tuple>,
+}
+
+impl<'a> Window<'a> {
+    pub fn new(input: BoxedOperator<'a>, windows: Vec<WindowExpr>, schema: Schema) -> Self {
+        Self {
+            input,
+            windows,
+            schema,
+            output: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a> Operator for Window<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()?;
+
+        let mut tuples = Vec::new();
+        while let Some(tuple) = self.input.next()? {
+            tuples.push(tuple);
+        }
+
+        let mut columns = self
+            .windows
+            .iter()
+            .map(|window| window.evaluate(&tuples))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (row, tuple) in tuples.iter_mut().enumerate() {
+            for column in &mut columns {
+                tuple.push(std::mem::replace(&mut column[row], Value::Null));
+            }
+        }
+
+        self.output = tuples.into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        Ok(self.output.next())
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.output = Vec::new().into_iter();
+        self.input.close()
+    }
+}
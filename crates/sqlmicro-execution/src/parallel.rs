@@ -0,0 +1,225 @@
+//! Intra query parallelism.
+//!
+//! A table is split into contiguous partitions of row ids, each worker thread
+//! runs the scan and filter of its partition and results are combined in
+//! partition order, so a parallel plan returns exactly what the serial one
+//! does.
+
+use std::{ops::Range, thread};
+
+use crate::{
+    expression::PhysicalExpr,
+    operator::{table_schema, Aggregate, AggregateExpr, Operator, Scan, Tuple},
+    planner::Pipeline,
+    schema::Schema,
+    table::Table,
+    vectorized::{BatchAggregate, BatchOperator, ColumnarScan},
+    ExecutionError,
+};
+
+/// Fewest rows worth handing to a worker thread
+pub const MIN_PARTITION_ROWS: usize = 1024;
+
+/// Scan of one table, and optionally a filter, split across partitions
+pub(crate) struct PartitionedScan<'a> {
+    name: String,
+    table: &'a Table,
+    schema: Schema,
+    filter: Option<PhysicalExpr>,
+    partitions: Vec<Range<usize>>,
+}
+
+impl<'a> PartitionedScan<'a> {
+    /// `None` when the table is too small to be split in `max_parallelism`
+    /// partitions of at least [`MIN_PARTITION_ROWS`] rows
+    pub fn new(name: &str, table: &'a Table, max_parallelism: usize) -> Option<Self> {
+        let count = max_parallelism.min(table.len() / MIN_PARTITION_ROWS);
+        if count <= 1 {
+            return None;
+        }
+
+        Some(Self {
+            name: name.to_string(),
+            table,
+            schema: table_schema(name, table),
+            filter: None,
+            partitions: table.partitions(count),
+        })
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn filter(self, predicate: PhysicalExpr) -> Self {
+        Self {
+            filter: Some(predicate),
+            ..self
+        }
+    }
+
+    /// Scan and filter of a single partition
+    fn pipeline(&self, range: Range<usize>) -> Pipeline<'a> {
+        let plan = match ColumnarScan::partition(&self.name, self.table, range.clone()) {
+            Some(scan) => Pipeline::Batches(Box::new(scan)),
+            None => Pipeline::Rows(Box::new(Scan::partition(&self.name, self.table, range))),
+        };
+        match &self.filter {
+            Some(predicate) => plan.filter(predicate.clone()),
+            None => plan,
+        }
+    }
+
+    /// Run `work` on every partition, one thread each, results are returned
+    /// in partition order
+    fn run<T, F>(&self, work: F) -> Result<Vec<T>, ExecutionError>
+    where
+        T: Send,
+        F: Fn(Pipeline<'a>) -> Result<T, ExecutionError> + Sync,
+    {
+        thread::scope(|scope| {
+            let workers: Vec<_> = self
+                .partitions
+                .iter()
+                .map(|range| scope.spawn(|| work(self.pipeline(range.clone()))))
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        })
+    }
+}
+
+/// Yields the rows of every partition, in table order
+pub struct Gather<'a> {
+    scan: PartitionedScan<'a>,
+    output: std::vec::IntoIter<Tuple>,
+}
+
+impl<'a> Gather<'a> {
+    pub(crate) fn new(scan: PartitionedScan<'a>) -> Self {
+        Self {
+            scan,
+            output: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a> Operator for Gather<'a> {
+    fn schema(&self) -> &Schema {
+        self.scan.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        let partitions = self.scan.run(|pipeline| {
+            let mut plan = pipeline.into_rows();
+            plan.open()?;
+            let mut tuples = Vec::new();
+            while let Some(tuple) = plan.next()? {
+                tuples.push(tuple);
+            }
+            plan.close()?;
+            Ok(tuples)
+        })?;
+
+        self.output = partitions
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        Ok(self.output.next())
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.output = Vec::new().into_iter();
+        Ok(())
+    }
+}
+
+/// Hash aggregation where every partition is aggregated on its own thread
+/// and the partial groups are merged, same output as [`Aggregate`]
+pub struct ParallelAggregate<'a> {
+    scan: PartitionedScan<'a>,
+    group_by: Vec<PhysicalExpr>,
+    aggregates: Vec<AggregateExpr>,
+    schema: Schema,
+    output: std::vec::IntoIter<Tuple>,
+}
+
+impl<'a> ParallelAggregate<'a> {
+    pub(crate) fn new(
+        scan: PartitionedScan<'a>,
+        group_by: Vec<PhysicalExpr>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            scan,
+            group_by,
+            aggregates,
+            schema,
+            output: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a> Operator for ParallelAggregate<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        let schema = &self.schema;
+        let group_by = &self.group_by;
+        let aggregates = &self.aggregates;
+
+        let partials = self.scan.run(|pipeline| {
+            let (group_by, aggregates, schema) =
+                (group_by.clone(), aggregates.clone(), schema.clone());
+            match pipeline {
+                Pipeline::Rows(plan) => {
+                    let mut aggregate = Aggregate::new(plan, group_by, aggregates, schema);
+                    let groups = aggregate.accumulate()?;
+                    aggregate.close()?;
+                    Ok(groups)
+                }
+                Pipeline::Batches(plan) => {
+                    let mut aggregate = BatchAggregate::new(plan, group_by, aggregates, schema);
+                    let groups = aggregate.accumulate()?;
+                    aggregate.close()?;
+                    Ok(groups)
+                }
+            }
+        })?;
+
+        // merging in partition order keeps groups in the order a serial scan
+        // first sees them
+        let mut partials = partials.into_iter();
+        let mut groups = partials.next().expect("at least two partitions");
+        for partial in partials {
+            groups.merge(partial)?;
+        }
+
+        self.output = groups.finish()?.into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        Ok(self.output.next())
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.output = Vec::new().into_iter();
+        Ok(())
+    }
+}
@@ -0,0 +1,1671 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use bigdecimal::ToPrimitive;
+
+use sqlmicro_parser::{
+    expression::{BinaryOperator, Expression, Parameter, UnaryOperator},
+    parse::{Span, Spanned},
+    value::Value,
+    visit::{walk_expression, walk_expression_mut, walk_select_mut, Visitor, VisitorMut},
+    Cte, Distinct, SelectItem, SelectStatement, SetOperation, SetOperator, TableRef, With,
+};
+
+use crate::{
+    expression::PhysicalExpr,
+    functions::FunctionRegistry,
+    operator::{
+        first, table_schema, tuples, window_frame, Aggregate, AggregateExpr, AggregateFunction,
+        Apply, BoxedOperator, Correlated, Filter, HashDistinct, HashSetOperation, Limit,
+        MaterializedScan, NestedLoopJoin, Project, Scan, SemiJoin, Sort, SortKey, SubqueryKind,
+        Template, Tuple, Window, WindowExpr, WindowFunction,
+    },
+    parallel::{Gather, ParallelAggregate, PartitionedScan},
+    schema::{Field, Schema},
+    settings::Settings,
+    table::Table,
+    vectorized::{
+        BatchAggregate, BatchFilter, BatchProject, BoxedBatchOperator, ColumnarScan, Unbatch,
+    },
+    ExecutionError,
+};
+
+/// A plan under construction, it stays vectorized while every operator added
+/// has a batch implementation
+pub(crate) enum Pipeline<'a> {
+    Rows(BoxedOperator<'a>),
+    Batches(BoxedBatchOperator<'a>),
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn schema(&self) -> &Schema {
+        match self {
+            Pipeline::Rows(plan) => plan.schema(),
+            Pipeline::Batches(plan) => plan.schema(),
+        }
+    }
+
+    pub fn into_rows(self) -> BoxedOperator<'a> {
+        match self {
+            Pipeline::Rows(plan) => plan,
+            Pipeline::Batches(plan) => Box::new(Unbatch::new(plan)),
+        }
+    }
+
+    pub fn filter(self, predicate: PhysicalExpr) -> Self {
+        match self {
+            Pipeline::Rows(plan) => Pipeline::Rows(Box::new(Filter::new(plan, predicate))),
+            Pipeline::Batches(plan) => {
+                Pipeline::Batches(Box::new(BatchFilter::new(plan, predicate)))
+            }
+        }
+    }
+
+    pub fn aggregate(
+        self,
+        group_by: Vec<PhysicalExpr>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    ) -> Self {
+        match self {
+            Pipeline::Rows(plan) => {
+                Pipeline::Rows(Box::new(Aggregate::new(plan, group_by, aggregates, schema)))
+            }
+            Pipeline::Batches(plan) => Pipeline::Batches(Box::new(BatchAggregate::new(
+                plan, group_by, aggregates, schema,
+            ))),
+        }
+    }
+
+    pub fn project(self, exprs: Vec<PhysicalExpr>, schema: Schema) -> Self {
+        match self {
+            Pipeline::Rows(plan) => Pipeline::Rows(Box::new(Project::new(plan, exprs, schema))),
+            Pipeline::Batches(plan) => {
+                Pipeline::Batches(Box::new(BatchProject::new(plan, exprs, schema)))
+            }
+        }
+    }
+}
+
+/// Rows of a common table expression, computed once for the whole query
+#[derive(Clone)]
+struct Materialized {
+    schema: Schema,
+    rows: Rc<Vec<Tuple>>,
+}
+
+/// Builds operator trees for queries over the stored tables
+#[derive(Clone)]
+pub(crate) struct Planner<'a> {
+    tables: &'a HashMap<String, Table>,
+    settings: &'a Settings,
+    functions: &'a FunctionRegistry,
+    /// Common table expressions in scope, which hide stored tables of the
+    /// same name
+    ctes: HashMap<String, Materialized>,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(
+        tables: &'a HashMap<String, Table>,
+        settings: &'a Settings,
+        functions: &'a FunctionRegistry,
+    ) -> Self {
+        Self {
+            tables,
+            settings,
+            functions,
+            ctes: HashMap::new(),
+        }
+    }
+
+    fn table(&self, name: &Spanned<String>) -> Result<&'a Table, ExecutionError> {
+        self.tables
+            .get(&name.node)
+            .ok_or_else(|| ExecutionError::TableNotFound(name.to_string(), name.span.into()))
+    }
+
+    /// The stored table a select reads from, `None` when it reads a derived
+    /// table or a common table expression
+    fn stored<'t>(&self, table: &'t TableRef) -> Option<&'t Spanned<String>> {
+        match table {
+            TableRef::Table { name, .. } if !self.ctes.contains_key(&name.node) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Read a stored table or a common table expression, or run a derived
+    /// table, and qualify the columns with its alias if it has one
+    fn scan(&self, table: &TableRef) -> Result<BoxedOperator<'a>, ExecutionError> {
+        let qualifier = table.name();
+        match table {
+            TableRef::Table { name, .. } => match self.ctes.get(&name.node) {
+                Some(cte) => Ok(Box::new(MaterializedScan::new(
+                    cte.schema.qualified(qualifier),
+                    cte.rows.clone(),
+                ))),
+                None => Ok(Box::new(Scan::new(qualifier, self.table(name)?))),
+            },
+            TableRef::Subquery { query, .. } => {
+                let plan = self.plan_select(query)?;
+                let exprs = (0..plan.schema().len()).map(PhysicalExpr::Column).collect();
+                let schema = plan.schema().qualified(qualifier);
+                Ok(Box::new(Project::new(plan, exprs, schema)))
+            }
+        }
+    }
+
+    /// Fields of the tuples a select reads, before its filter
+    fn input_schema(&self, select: &SelectStatement) -> Result<Schema, ExecutionError> {
+        let schema = |table: &TableRef| match self.stored(table) {
+            Some(name) => Ok(table_schema(table.name(), self.table(name)?)),
+            None => Ok(self.scan(table)?.schema().clone()),
+        };
+
+        let mut from = schema(&select.table)?;
+        for join in &select.joins {
+            from = from.join(&schema(&join.table)?);
+        }
+        Ok(from)
+    }
+
+    /// Columnar tables are read in batches unless they take part in a join
+    fn source(&self, select: &SelectStatement) -> Result<Pipeline<'a>, ExecutionError> {
+        if let (Some(name), true) = (self.stored(&select.table), select.joins.is_empty()) {
+            if let Some(scan) = ColumnarScan::new(select.table.name(), self.table(name)?) {
+                return Ok(Pipeline::Batches(Box::new(scan)));
+            }
+        }
+
+        let mut plan = self.scan(&select.table)?;
+        for join in &select.joins {
+            let right = self.scan(&join.table)?;
+            let on = self.compile(&join.on, &plan.schema().join(right.schema()))?;
+            plan = Box::new(NestedLoopJoin::new(plan, right, on));
+        }
+        Ok(Pipeline::Rows(plan))
+    }
+
+    /// Scan and filter split across worker threads, when the settings allow
+    /// it and the query reads a single table large enough
+    fn partitioned(
+        &self,
+        select: &SelectStatement,
+    ) -> Result<Option<PartitionedScan<'a>>, ExecutionError> {
+        let Some(name) = self.stored(&select.table) else {
+            return Ok(None);
+        };
+        if !select.joins.is_empty() || select.filter.as_ref().is_some_and(has_subquery) {
+            return Ok(None);
+        }
+
+        let table = self.table(name)?;
+        let qualifier = select.table.name();
+        let Some(scan) = PartitionedScan::new(qualifier, table, self.settings.max_parallelism)
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(match &select.filter {
+            Some(filter) => {
+                let predicate = self.compile(filter, scan.schema())?;
+                scan.filter(predicate)
+            }
+            None => scan,
+        }))
+    }
+
+    /// Run the common table expressions of a `WITH`, in order, so the
+    /// queries planned after can read them
+    fn with(&self, with: &With) -> Result<Planner<'a>, ExecutionError> {
+        let mut planner = self.clone();
+        for cte in &with.ctes {
+            let materialized = planner.materialize(cte, with.recursive)?;
+            planner.ctes.insert(cte.name.node.clone(), materialized);
+        }
+        Ok(planner)
+    }
+
+    /// Rows of a common table expression. The select after the last `UNION`
+    /// of a recursive one runs again over the rows the previous run added,
+    /// until it adds none
+    fn materialize(&self, cte: &Cte, recursive: bool) -> Result<Materialized, ExecutionError> {
+        let step = recursive.then(|| recursive_step(&cte.query)).flatten();
+        let plan = match &step {
+            Some((anchor, _)) => self.plan_select(anchor)?,
+            None => self.plan_select(&cte.query)?,
+        };
+        let output = plan.schema().clone();
+        let names: Vec<String> = match cte.columns.is_empty() {
+            true => output.fields().iter().map(|f| f.name.clone()).collect(),
+            false if cte.columns.len() == output.len() => {
+                cte.columns.iter().map(|name| name.node.clone()).collect()
+            }
+            false => {
+                return Err(ExecutionError::CteColumnCount(
+                    cte.name.to_string(),
+                    cte.columns.len(),
+                    output.len(),
+                    cte.name.span.into(),
+                ))
+            }
+        };
+        let schema = Schema::new(
+            names
+                .into_iter()
+                .map(|name| Field::new(Some(cte.name.node.clone()), name))
+                .collect(),
+        );
+        let mut rows = tuples(plan)?;
+
+        let Some((_, union)) = step else {
+            return Ok(Materialized {
+                schema,
+                rows: Rc::new(rows),
+            });
+        };
+
+        // UNION keeps the first of equal rows, NULLs being equal to each other
+        let mut seen = HashSet::new();
+        if !union.all {
+            rows.retain(|row| seen.insert(row.clone()));
+        }
+
+        let mut added = rows.clone();
+        for iteration in 1.. {
+            let mut planner = self.clone();
+            let working = Materialized {
+                schema: schema.clone(),
+                rows: Rc::new(added),
+            };
+            planner.ctes.insert(cte.name.node.clone(), working);
+
+            let plan = planner.plan_select(&union.query)?;
+            if plan.schema().len() != schema.len() {
+                return Err(ExecutionError::SetOperationColumnCount(
+                    union.op.to_string(),
+                    schema.len(),
+                    plan.schema().len(),
+                ));
+            }
+            added = tuples(plan)?;
+            if !union.all {
+                added.retain(|row| seen.insert(row.clone()));
+            }
+
+            if added.is_empty() {
+                break;
+            }
+            if iteration > self.settings.recursion_limit {
+                return Err(ExecutionError::RecursionLimit(
+                    cte.name.to_string(),
+                    self.settings.recursion_limit,
+                    cte.name.span.into(),
+                ));
+            }
+            rows.extend(added.iter().cloned());
+        }
+
+        // ORDER BY and LIMIT apply once every row is found
+        if !cte.query.order_by.is_empty() || cte.query.limit.is_some() {
+            let scan = MaterializedScan::new(output.unqualified(), Rc::new(rows));
+            rows = tuples(self.order(Box::new(scan), &cte.query)?)?;
+        }
+
+        Ok(Materialized {
+            schema,
+            rows: Rc::new(rows),
+        })
+    }
+
+    /// Plan a select, after running the common table expressions it has
+    pub fn plan_select(
+        &self,
+        select: &SelectStatement,
+    ) -> Result<BoxedOperator<'a>, ExecutionError> {
+        match &select.with {
+            Some(with) => self.with(with)?.plan_query(select),
+            None => self.plan_query(select),
+        }
+    }
+
+    /// A select combined with those of its set operations, `INTERSECT`
+    /// binding tighter than `UNION` and `EXCEPT`, then sorted and limited
+    fn plan_query(&self, select: &SelectStatement) -> Result<BoxedOperator<'a>, ExecutionError> {
+        if select.compound.is_empty() {
+            return self.plan_core(select);
+        }
+
+        let first = self.plan_core(&SelectStatement {
+            with: None,
+            compound: vec![],
+            order_by: vec![],
+            limit: None,
+            offset: None,
+            ..select.clone()
+        })?;
+        let columns = first.schema().len();
+
+        // the operands of UNION and EXCEPT, each one an INTERSECT chain
+        let mut terms = vec![(SetOperator::Union, false, first)];
+        for operation in &select.compound {
+            let plan = self.plan_core(&operation.query)?;
+            if plan.schema().len() != columns {
+                return Err(ExecutionError::SetOperationColumnCount(
+                    operation.op.to_string(),
+                    columns,
+                    plan.schema().len(),
+                ));
+            }
+            match operation.op {
+                SetOperator::Intersect => {
+                    let (op, all, left) = terms.pop().expect("the first select is a term");
+                    let plan = HashSetOperation::new(left, plan, operation.op, operation.all);
+                    terms.push((op, all, Box::new(plan)));
+                }
+                op => terms.push((op, operation.all, plan)),
+            }
+        }
+
+        let mut terms = terms.into_iter();
+        let (_, _, mut plan) = terms.next().expect("the first select is a term");
+        for (op, all, right) in terms {
+            plan = Box::new(HashSetOperation::new(plan, right, op, all));
+        }
+
+        self.order(plan, select)
+    }
+
+    /// `ORDER BY` and `LIMIT` of a select over the rows of its set
+    /// operations, the sort keys can only read the output columns or name
+    /// their position
+    fn order(
+        &self,
+        mut plan: BoxedOperator<'a>,
+        select: &SelectStatement,
+    ) -> Result<BoxedOperator<'a>, ExecutionError> {
+        if !select.order_by.is_empty() {
+            let width = plan.schema().len();
+            let keys = select
+                .order_by
+                .iter()
+                .map(|order| {
+                    let expr = match position(&order.expr) {
+                        Some(n) if (1..=width).contains(&n) => PhysicalExpr::Column(n - 1),
+                        Some(n) => return Err(ExecutionError::PositionNotInSelectList(n)),
+                        None => self.compile(&order.expr, plan.schema())?,
+                    };
+                    Ok(SortKey {
+                        expr,
+                        asc: order.asc,
+                    })
+                })
+                .collect::<Result<_, ExecutionError>>()?;
+            plan = Box::new(Sort::new(plan, keys));
+        }
+        Ok(limit(plan, select))
+    }
+
+    /// scan -> join -> filter -> aggregate -> sort -> distinct -> project -> limit
+    fn plan_core(&self, select: &SelectStatement) -> Result<BoxedOperator<'a>, ExecutionError> {
+        let items: Vec<&SelectItem> = select.fields.iter().collect();
+        let distinct_on = match &select.distinct {
+            Some(Distinct::On(exprs)) => exprs.as_slice(),
+            _ => &[],
+        };
+        // only a position past a `*` needs the columns it stands for
+        let wildcard = match items
+            .iter()
+            .any(|item| matches!(item, SelectItem::Wildcard(_)))
+            && select
+                .order_by
+                .iter()
+                .map(|order| &order.expr)
+                .chain(distinct_on)
+                .any(|expr| position(expr).is_some())
+        {
+            true => wildcard_columns(&self.input_schema(select)?),
+            false => vec![],
+        };
+        let order_by: Vec<(Expression, bool)> = select
+            .order_by
+            .iter()
+            .map(|order| Ok((resolve_output(&order.expr, &items, &wildcard)?, order.asc)))
+            .collect::<Result<_, ExecutionError>>()?;
+        let distinct_on: Vec<Expression> = distinct_on
+            .iter()
+            .map(|expr| resolve_output(expr, &items, &wildcard))
+            .collect::<Result<_, _>>()?;
+
+        let mut aggregates = Vec::new();
+        for item in &items {
+            if let SelectItem::Expression { expr, .. } = item {
+                collect_aggregates(self.functions, expr, &mut aggregates);
+            }
+        }
+        for (expr, _) in &order_by {
+            collect_aggregates(self.functions, expr, &mut aggregates);
+        }
+        for expr in &distinct_on {
+            collect_aggregates(self.functions, expr, &mut aggregates);
+        }
+
+        let mut windows = Vec::new();
+        for item in &items {
+            if let SelectItem::Expression { expr, .. } = item {
+                collect_windows(expr, &mut windows);
+            }
+        }
+        for expr in order_by.iter().map(|(expr, _)| expr).chain(&distinct_on) {
+            collect_windows(expr, &mut windows);
+        }
+
+        // after grouping, expressions can only reference the aggregate output
+        let mut grouping = match aggregates.is_empty() && select.group_by.is_empty() {
+            true => None,
+            false => Some(Grouping {
+                group_by: select.group_by.clone(),
+                aggregates,
+                windows: Vec::new(),
+            }),
+        };
+
+        let mut plan = match self.partitioned(select)? {
+            Some(scan) => match &grouping {
+                Some(grouping) => {
+                    let (group_by, aggregates, schema) = grouping.physical(self, scan.schema())?;
+                    Pipeline::Rows(Box::new(ParallelAggregate::new(
+                        scan, group_by, aggregates, schema,
+                    )))
+                }
+                None => Pipeline::Rows(Box::new(Gather::new(scan))),
+            },
+            None => {
+                let mut plan = self.source(select)?;
+
+                if let Some(filter) = &select.filter {
+                    plan = self.filter(plan, filter)?;
+                }
+
+                if let Some(grouping) = &grouping {
+                    let (group_by, aggregates, schema) = grouping.physical(self, plan.schema())?;
+                    plan = plan.aggregate(group_by, aggregates, schema);
+                }
+                plan
+            }
+        };
+
+        // window functions read the rows left after grouping, their values
+        // follow the columns of each row
+        let columns = plan.schema().len();
+        if !windows.is_empty() {
+            let input = plan.schema().clone();
+            let mut compiler = Compiler {
+                planner: self,
+                schema: &input,
+                params: &[],
+                correlated: None,
+                windows: &[],
+            };
+            let physical = windows
+                .iter()
+                .map(|window| {
+                    self.window(window, |expr| match &grouping {
+                        Some(grouping) => grouping.compile(&mut compiler, expr),
+                        None => compiler.compile(expr),
+                    })
+                })
+                .collect::<Result<_, ExecutionError>>()?;
+            let fields = input
+                .fields()
+                .iter()
+                .cloned()
+                .chain(windows.iter().map(|window| output_field(window, None)))
+                .collect();
+            plan = Pipeline::Rows(Box::new(Window::new(
+                plan.into_rows(),
+                physical,
+                Schema::new(fields),
+            )));
+            if let Some(grouping) = &mut grouping {
+                grouping.windows.clone_from(&windows);
+            }
+        }
+
+        // correlated subqueries of the select list and sort keys are computed
+        // once per row, or per group, before sorting
+        let schema = plan.schema().clone();
+        let mut correlated = Vec::new();
+        let mut compiler = Compiler {
+            planner: self,
+            schema: &schema,
+            params: &[],
+            correlated: Some(&mut correlated),
+            windows: &windows,
+        };
+        let mut compile_output = |expr: &Expression| match &grouping {
+            Some(grouping) => grouping.compile(&mut compiler, expr),
+            None => compiler.compile(expr),
+        };
+
+        let keys: Vec<SortKey> = order_by
+            .iter()
+            .map(|(expr, asc)| {
+                Ok(SortKey {
+                    expr: compile_output(expr)?,
+                    asc: *asc,
+                })
+            })
+            .collect::<Result<_, ExecutionError>>()?;
+        let distinct_keys = distinct_on
+            .iter()
+            .map(&mut compile_output)
+            .collect::<Result<Vec<_>, ExecutionError>>()?;
+
+        let mut exprs = Vec::new();
+        let mut fields = Vec::new();
+        for item in items {
+            match item {
+                SelectItem::Wildcard(star) => {
+                    if grouping.is_some() {
+                        return Err(ExecutionError::ColumnNotGrouped(
+                            "*".to_string(),
+                            star.span.into(),
+                        ));
+                    }
+                    for (index, field) in schema.fields().iter().take(columns).enumerate() {
+                        exprs.push(PhysicalExpr::Column(index));
+                        fields.push(field.clone());
+                    }
+                }
+                SelectItem::Expression { expr, alias } => {
+                    exprs.push(compile_output(expr)?);
+                    fields.push(output_field(expr, alias.as_deref()));
+                }
+            }
+        }
+
+        if !correlated.is_empty() {
+            plan = Pipeline::Rows(Box::new(Apply::new(
+                plan.into_rows(),
+                correlated,
+                self.clone(),
+            )));
+        }
+
+        // sorting needs the rows, so the pipeline stops being vectorized here
+        if !keys.is_empty() {
+            plan = Pipeline::Rows(Box::new(Sort::new(plan.into_rows(), keys)));
+        }
+
+        // DISTINCT ON keeps the first row of each key in sort order
+        if !distinct_keys.is_empty() {
+            plan = Pipeline::Rows(Box::new(HashDistinct::new(plan.into_rows(), distinct_keys)));
+        }
+
+        let mut plan = plan.project(exprs, Schema::new(fields)).into_rows();
+        if select.distinct == Some(Distinct::All) {
+            plan = Box::new(HashDistinct::rows(plan));
+        }
+        Ok(limit(plan, select))
+    }
+
+    /// Keep the tuples matching a WHERE clause, `EXISTS` and `IN` conjuncts
+    /// over correlated subqueries become semi joins and the other correlated
+    /// subqueries are run for every tuple
+    fn filter(
+        &self,
+        plan: Pipeline<'a>,
+        filter: &Expression,
+    ) -> Result<Pipeline<'a>, ExecutionError> {
+        let schema = plan.schema().clone();
+
+        let mut semi_joins = Vec::new();
+        let mut remaining = Vec::new();
+        for conjunct in conjuncts(filter) {
+            match self.semi_join(&schema, conjunct)? {
+                Some(semi_join) => semi_joins.push(semi_join),
+                None => remaining.push(conjunct.clone()),
+            }
+        }
+
+        let mut plan = match remaining.into_iter().reduce(and) {
+            Some(predicate) => {
+                let mut correlated = Vec::new();
+                let predicate = Compiler {
+                    planner: self,
+                    schema: &schema,
+                    params: &[],
+                    correlated: Some(&mut correlated),
+                    windows: &[],
+                }
+                .compile(&predicate)?;
+
+                match correlated.is_empty() {
+                    true => plan.filter(predicate),
+                    false => {
+                        let apply = Apply::new(plan.into_rows(), correlated, self.clone());
+                        let columns = (0..schema.len()).map(PhysicalExpr::Column).collect();
+                        Pipeline::Rows(Box::new(apply))
+                            .filter(predicate)
+                            .project(columns, schema)
+                    }
+                }
+            }
+            None => plan,
+        };
+
+        for (inner, on, anti) in semi_joins {
+            plan = Pipeline::Rows(Box::new(SemiJoin::new(plan.into_rows(), inner, on, anti)));
+        }
+        Ok(plan)
+    }
+
+    /// Turn `[NOT] EXISTS (subquery)` or `expr IN (subquery)` into a semi (or
+    /// anti) join, when the subquery only reads the outer tuple in top level
+    /// conjuncts of its WHERE clause. Returns the inner plan and the join
+    /// condition, evaluated on inner tuples followed by outer ones
+    #[allow(clippy::type_complexity)]
+    fn semi_join(
+        &self,
+        outer: &Schema,
+        conjunct: &Expression,
+    ) -> Result<Option<(BoxedOperator<'a>, PhysicalExpr, bool)>, ExecutionError> {
+        let (select, test, anti) = match conjunct {
+            Expression::Exists(select) => (select, None, false),
+            Expression::Unary {
+                op: UnaryOperator::Not,
+                operand,
+            } => match &**operand {
+                Expression::Exists(select) => (select, None, true),
+                _ => return Ok(None),
+            },
+            Expression::InSubquery {
+                expr,
+                subquery,
+                negated: false,
+            } if !has_subquery(expr) => (subquery, Some(expr), false),
+            _ => return Ok(None),
+        };
+
+        let aggregated = select.fields.iter().any(|item| match item {
+            SelectItem::Expression { expr, .. } => {
+                let mut aggregates = Vec::new();
+                collect_aggregates(self.functions, expr, &mut aggregates);
+                !aggregates.is_empty()
+            }
+            SelectItem::Wildcard(_) => false,
+        });
+        if aggregated
+            || !select.group_by.is_empty()
+            || !select.compound.is_empty()
+            || select.limit.is_some()
+            || select.offset.is_some()
+        {
+            return Ok(None);
+        }
+
+        let template = self.correlate(select, outer)?;
+        if !template.is_correlated() {
+            return Ok(None);
+        }
+
+        let mut inner = template.select;
+        let value = match (test, inner.fields.as_slice()) {
+            (None, _) => None,
+            (Some(_), [SelectItem::Expression { expr, .. }]) => Some(expr.clone()),
+            (Some(_), _) => return Ok(None),
+        };
+        let (correlated, filter): (Vec<Expression>, Vec<Expression>) = inner
+            .filter
+            .iter()
+            .flat_map(conjuncts)
+            .cloned()
+            .partition(has_parameter);
+        inner.fields = vec![SelectItem::Wildcard(().into())];
+        inner.order_by.clear();
+        inner.filter = filter.into_iter().reduce(and);
+
+        let mut rest = Parameters(false);
+        rest.visit_select(&inner);
+        if rest.0
+            || value.as_ref().is_some_and(has_parameter)
+            || correlated.iter().any(has_subquery)
+        {
+            return Ok(None);
+        }
+
+        let inner = self.plan_select(&inner)?;
+        let width = inner.schema().len();
+        let params: Vec<PhysicalExpr> = template
+            .outer
+            .iter()
+            .map(|index| PhysicalExpr::Column(width + index))
+            .collect();
+        let mut compiler = Compiler {
+            planner: self,
+            schema: inner.schema(),
+            params: &params,
+            correlated: None,
+            windows: &[],
+        };
+
+        let mut on = correlated
+            .iter()
+            .map(|expr| compiler.compile(expr))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let (Some(test), Some(value)) = (test, value) {
+            on.push(PhysicalExpr::Binary {
+                left: Box::new(shift(self.compile(test, outer)?, width)),
+                op: BinaryOperator::Eq,
+                right: Box::new(compiler.compile(&value)?),
+            });
+        }
+        let on = on
+            .into_iter()
+            .reduce(|left, right| PhysicalExpr::Binary {
+                left: Box::new(left),
+                op: BinaryOperator::And,
+                right: Box::new(right),
+            })
+            .unwrap_or(PhysicalExpr::Literal(Value::Boolean(true)));
+
+        Ok(Some((inner, on, anti)))
+    }
+
+    /// Resolve an expression evaluated on tuples of `schema`, uncorrelated
+    /// subqueries are run right away
+    pub fn compile(
+        &self,
+        expr: &Expression,
+        schema: &Schema,
+    ) -> Result<PhysicalExpr, ExecutionError> {
+        Compiler {
+            planner: self,
+            schema,
+            params: &[],
+            correlated: None,
+            windows: &[],
+        }
+        .compile(expr)
+    }
+
+    /// A call of anything but an aggregate, with its arguments compiled by
+    /// `compile`
+    fn function(
+        &self,
+        name: &Spanned<String>,
+        args: &[Expression],
+        compile: impl FnMut(&Expression) -> Result<PhysicalExpr, ExecutionError>,
+    ) -> Result<PhysicalExpr, ExecutionError> {
+        if self.functions.aggregate(name).is_some() {
+            return Err(ExecutionError::AggregateNotAllowed(
+                name.to_string(),
+                name.span.into(),
+            ));
+        }
+        if conditional_arguments(name).is_some() {
+            return conditional(name, args, compile);
+        }
+        if WindowFunction::from_name(name).is_some() {
+            return Err(ExecutionError::WindowRequired(
+                name.to_string(),
+                name.span.into(),
+            ));
+        }
+        let function = self
+            .functions
+            .scalar(name)
+            .ok_or_else(|| ExecutionError::FunctionNotFound(name.to_string(), name.span.into()))?;
+        if !function.accepts(args.len()) {
+            return Err(ExecutionError::WrongArgumentCount(
+                name.to_string(),
+                function.arity(),
+                name.span.into(),
+            ));
+        }
+        Ok(PhysicalExpr::Function {
+            function: function.clone(),
+            args: args.iter().map(compile).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// A window function call, with the expressions it reads compiled by
+    /// `compile`
+    fn window(
+        &self,
+        expr: &Expression,
+        mut compile: impl FnMut(&Expression) -> Result<PhysicalExpr, ExecutionError>,
+    ) -> Result<WindowExpr, ExecutionError> {
+        let Expression::Window { name, args, over } = expr else {
+            unreachable!("only window function calls are collected as windows")
+        };
+        let function = match WindowFunction::from_name(name) {
+            Some(function) => function,
+            None => WindowFunction::Aggregate(self.functions.aggregate(name).ok_or_else(|| {
+                ExecutionError::FunctionNotFound(name.to_string(), name.span.into())
+            })?),
+        };
+        let args = match (&function, args.as_slice()) {
+            (WindowFunction::Aggregate(AggregateFunction::Count), [Expression::Wildcard]) => {
+                Vec::new()
+            }
+            (function, args)
+                if function.accepts(args.len()) && !args.contains(&Expression::Wildcard) =>
+            {
+                args.iter().map(&mut compile).collect::<Result<_, _>>()?
+            }
+            (function, _) => {
+                return Err(ExecutionError::WrongArgumentCount(
+                    name.to_string(),
+                    function.arity().to_string(),
+                    name.span.into(),
+                ))
+            }
+        };
+
+        Ok(WindowExpr {
+            function,
+            args,
+            partition_by: over
+                .partition_by
+                .iter()
+                .map(&mut compile)
+                .collect::<Result<_, _>>()?,
+            order_by: over
+                .order_by
+                .iter()
+                .map(|order| {
+                    Ok(SortKey {
+                        expr: compile(&order.expr)?,
+                        asc: order.asc,
+                    })
+                })
+                .collect::<Result<_, ExecutionError>>()?,
+            frame: window_frame(over)?,
+        })
+    }
+
+    /// Replace the columns of `outer` a subquery reads with placeholders, so
+    /// it can be planned on its own
+    fn correlate(
+        &self,
+        select: &SelectStatement,
+        outer: &Schema,
+    ) -> Result<Template, ExecutionError> {
+        let mut select = select.clone();
+        let mut correlate = Correlate {
+            planner: self.clone(),
+            outer,
+            scopes: Vec::new(),
+            columns: Vec::new(),
+            error: None,
+        };
+        correlate.visit_select_mut(&mut select);
+
+        match correlate.error {
+            Some(error) => Err(error),
+            None => Ok(Template {
+                select,
+                outer: correlate.columns,
+            }),
+        }
+    }
+}
+
+/// Resolves expressions to the positions of the columns they read
+struct Compiler<'p, 'a> {
+    planner: &'p Planner<'a>,
+    schema: &'p Schema,
+    /// What each `$n` placeholder left by [`Planner::correlate`] reads
+    params: &'p [PhysicalExpr],
+    /// Where correlated subqueries go, their values are read as the columns
+    /// following those of `schema`
+    correlated: Option<&'p mut Vec<Correlated>>,
+    /// Window function calls computed before, read as the last columns of
+    /// `schema`
+    windows: &'p [Expression],
+}
+
+impl Compiler<'_, '_> {
+    fn compile(&mut self, expr: &Expression) -> Result<PhysicalExpr, ExecutionError> {
+        match expr {
+            Expression::Literal(value) => Ok(PhysicalExpr::Literal(value.node.clone())),
+            Expression::Column { table, name } => Ok(PhysicalExpr::Column(
+                self.schema
+                    .index_of(table.as_deref().map(String::as_str), name)
+                    .map_err(|e| e.at(column_span(table, name)))?,
+            )),
+            Expression::Binary { left, op, right } => Ok(PhysicalExpr::Binary {
+                left: Box::new(self.compile(left)?),
+                op: *op,
+                right: Box::new(self.compile(right)?),
+            }),
+            Expression::Unary { op, operand } => Ok(PhysicalExpr::Unary {
+                op: *op,
+                operand: Box::new(self.compile(operand)?),
+            }),
+            Expression::Function { name, args } => {
+                let planner = self.planner;
+                planner.function(name, args, |arg| self.compile(arg))
+            }
+            Expression::Parameter(parameter) => match parameter.node {
+                Parameter::Positional(n) if n <= self.params.len() => {
+                    Ok(self.params[n - 1].clone())
+                }
+                _ => Err(ExecutionError::ParameterNotBound(
+                    parameter.to_string(),
+                    parameter.span.into(),
+                )),
+            },
+            Expression::Wildcard => Err(ExecutionError::ColumnDoesNotExists(
+                "*".to_string(),
+                Span::default().into(),
+            )),
+            Expression::Subquery(select) => self.subquery(SubqueryKind::Scalar, select),
+            Expression::Exists(select) => self.subquery(SubqueryKind::Exists, select),
+            Expression::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let test = self.compile(expr)?;
+                let kind = SubqueryKind::In {
+                    test,
+                    negated: *negated,
+                };
+                self.subquery(kind, subquery)
+            }
+            Expression::InList { .. } | Expression::Between { .. } | Expression::Like { .. } => {
+                predicate(expr, |expr| self.compile(expr))
+            }
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => case(operand, branches, else_result, |expr| self.compile(expr)),
+            Expression::Cast { expr, type_info } => Ok(PhysicalExpr::Cast {
+                expr: Box::new(self.compile(expr)?),
+                type_info: *type_info,
+            }),
+            Expression::Window { name, .. } => {
+                let index = self
+                    .windows
+                    .iter()
+                    .position(|window| window == expr)
+                    .ok_or_else(|| {
+                        ExecutionError::WindowNotAllowed(name.to_string(), name.span.into())
+                    })?;
+                Ok(PhysicalExpr::Column(
+                    self.schema.len() - self.windows.len() + index,
+                ))
+            }
+        }
+    }
+
+    fn subquery(
+        &mut self,
+        kind: SubqueryKind,
+        select: &SelectStatement,
+    ) -> Result<PhysicalExpr, ExecutionError> {
+        let template = self.planner.correlate(select, self.schema)?;
+
+        if !template.is_correlated() {
+            let rows = kind.run(self.planner, &template.select)?;
+            return match kind {
+                SubqueryKind::In { test, negated } => Ok(PhysicalExpr::InList {
+                    expr: Box::new(test),
+                    list: rows
+                        .into_iter()
+                        .map(|row| PhysicalExpr::Literal(first(row)))
+                        .collect(),
+                    negated,
+                }),
+                kind => Ok(PhysicalExpr::Literal(kind.value(rows, &[])?)),
+            };
+        }
+
+        match &mut self.correlated {
+            Some(correlated) => {
+                let index = self.schema.len() + correlated.len();
+                correlated.push(Correlated { kind, template });
+                Ok(PhysicalExpr::Column(index))
+            }
+            None => Err(ExecutionError::CorrelatedSubqueryNotAllowed),
+        }
+    }
+}
+
+/// Replaces the columns of the outer query a subquery reads with `$1`, `$2`,
+/// ... in order of appearance, columns of the subquery's own tables (or of
+/// those of a subquery nested in it) shadow the outer ones
+struct Correlate<'p, 'a> {
+    /// Planner of the select being visited, with its common table
+    /// expressions
+    planner: Planner<'a>,
+    outer: &'p Schema,
+    scopes: Vec<Schema>,
+    columns: Vec<usize>,
+    error: Option<ExecutionError>,
+}
+
+impl VisitorMut for Correlate<'_, '_> {
+    fn visit_select_mut(&mut self, select: &mut SelectStatement) {
+        let planner = match &select.with {
+            Some(with) => self.planner.with(with),
+            None => Ok(self.planner.clone()),
+        };
+        let schema = planner.and_then(|planner| {
+            let schema = planner.input_schema(select)?;
+            Ok((planner, schema))
+        });
+
+        match schema {
+            Ok((planner, schema)) => {
+                let outer = std::mem::replace(&mut self.planner, planner);
+                self.scopes.push(schema);
+                walk_select_mut(self, select);
+                self.scopes.pop();
+                self.planner = outer;
+            }
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        let Expression::Column { table, name } = expr else {
+            return walk_expression_mut(self, expr);
+        };
+
+        let table_name = table.as_deref().map(String::as_str);
+        let inner = self.scopes.iter().any(|scope| {
+            !matches!(
+                scope.index_of(table_name, name),
+                Err(ExecutionError::ColumnDoesNotExists(..))
+            )
+        });
+        if inner {
+            return;
+        }
+
+        if let Ok(index) = self.outer.index_of(table_name, name) {
+            let n = match self.columns.iter().position(|&column| column == index) {
+                Some(n) => n,
+                None => {
+                    self.columns.push(index);
+                    self.columns.len() - 1
+                }
+            };
+            let span = column_span(table, name);
+            *expr = Expression::Parameter(Spanned::new(Parameter::Positional(n + 1), span));
+        }
+    }
+}
+
+/// The top level operands of a chain of ANDs
+fn conjuncts(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::Binary {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let mut all = conjuncts(left);
+            all.extend(conjuncts(right));
+            all
+        }
+        expr => vec![expr],
+    }
+}
+
+fn and(left: Expression, right: Expression) -> Expression {
+    Expression::Binary {
+        left: Box::new(left),
+        op: BinaryOperator::And,
+        right: Box::new(right),
+    }
+}
+
+/// Whether a placeholder appears anywhere in what is visited
+struct Parameters(bool);
+
+impl Visitor for Parameters {
+    fn visit_parameter(&mut self, _parameter: &Parameter) {
+        self.0 = true;
+    }
+}
+
+fn has_parameter(expr: &Expression) -> bool {
+    let mut parameters = Parameters(false);
+    parameters.visit_expression(expr);
+    parameters.0
+}
+
+fn has_subquery(expr: &Expression) -> bool {
+    struct Subqueries(bool);
+
+    impl Visitor for Subqueries {
+        fn visit_select(&mut self, _select: &SelectStatement) {
+            self.0 = true;
+        }
+    }
+
+    let mut subqueries = Subqueries(false);
+    subqueries.visit_expression(expr);
+    subqueries.0
+}
+
+/// The same expression over tuples with `by` more columns in front
+fn shift(expr: PhysicalExpr, by: usize) -> PhysicalExpr {
+    match expr {
+        PhysicalExpr::Column(index) => PhysicalExpr::Column(index + by),
+        PhysicalExpr::Literal(value) => PhysicalExpr::Literal(value),
+        PhysicalExpr::Binary { left, op, right } => PhysicalExpr::Binary {
+            left: Box::new(shift(*left, by)),
+            op,
+            right: Box::new(shift(*right, by)),
+        },
+        PhysicalExpr::Unary { op, operand } => PhysicalExpr::Unary {
+            op,
+            operand: Box::new(shift(*operand, by)),
+        },
+        PhysicalExpr::InList {
+            expr,
+            list,
+            negated,
+        } => PhysicalExpr::InList {
+            expr: Box::new(shift(*expr, by)),
+            list: list.into_iter().map(|item| shift(item, by)).collect(),
+            negated,
+        },
+        PhysicalExpr::Like {
+            expr,
+            pattern,
+            escape,
+            negated,
+            case_insensitive,
+        } => PhysicalExpr::Like {
+            expr: Box::new(shift(*expr, by)),
+            pattern: Box::new(shift(*pattern, by)),
+            escape: escape.map(|escape| Box::new(shift(*escape, by))),
+            negated,
+            case_insensitive,
+        },
+        PhysicalExpr::Case {
+            operand,
+            branches,
+            else_result,
+        } => PhysicalExpr::Case {
+            operand: operand.map(|operand| Box::new(shift(*operand, by))),
+            branches: branches
+                .into_iter()
+                .map(|(when, then)| (shift(when, by), shift(then, by)))
+                .collect(),
+            else_result: else_result.map(|else_result| Box::new(shift(*else_result, by))),
+        },
+        PhysicalExpr::Coalesce(args) => {
+            PhysicalExpr::Coalesce(args.into_iter().map(|arg| shift(arg, by)).collect())
+        }
+        PhysicalExpr::Cast { expr, type_info } => PhysicalExpr::Cast {
+            expr: Box::new(shift(*expr, by)),
+            type_info,
+        },
+        PhysicalExpr::Function { function, args } => PhysicalExpr::Function {
+            function,
+            args: args.into_iter().map(|arg| shift(arg, by)).collect(),
+        },
+    }
+}
+
+/// Functions compiled to conditional expressions rather than called, so the
+/// arguments that are not needed are not evaluated, with the arguments they
+/// take
+pub(crate) fn conditional_arguments(name: &str) -> Option<&'static str> {
+    match name {
+        "coalesce" => Some("at least one argument"),
+        "nullif" => Some("two arguments"),
+        _ => None,
+    }
+}
+
+/// `COALESCE(args..)` or `NULLIF(a, b)`, which is `CASE WHEN a = b THEN NULL
+/// ELSE a END`
+fn conditional(
+    name: &Spanned<String>,
+    args: &[Expression],
+    mut compile: impl FnMut(&Expression) -> Result<PhysicalExpr, ExecutionError>,
+) -> Result<PhysicalExpr, ExecutionError> {
+    match (name.as_str(), args) {
+        ("coalesce", [_, ..]) => Ok(PhysicalExpr::Coalesce(
+            args.iter().map(compile).collect::<Result<_, _>>()?,
+        )),
+        ("nullif", [left, right]) => {
+            let left = compile(left)?;
+            Ok(PhysicalExpr::Case {
+                operand: None,
+                branches: vec![(
+                    PhysicalExpr::Binary {
+                        left: Box::new(left.clone()),
+                        op: BinaryOperator::Eq,
+                        right: Box::new(compile(right)?),
+                    },
+                    PhysicalExpr::Literal(Value::Null),
+                )],
+                else_result: Some(Box::new(left)),
+            })
+        }
+        _ => Err(ExecutionError::WrongArgumentCount(
+            name.to_string(),
+            conditional_arguments(name).unwrap_or_default().to_string(),
+            name.span.into(),
+        )),
+    }
+}
+
+/// `IN (list)`, `BETWEEN` or `LIKE` over operands compiled by `compile`
+fn predicate(
+    expr: &Expression,
+    mut compile: impl FnMut(&Expression) -> Result<PhysicalExpr, ExecutionError>,
+) -> Result<PhysicalExpr, ExecutionError> {
+    match expr {
+        Expression::InList {
+            expr,
+            list,
+            negated,
+        } => Ok(PhysicalExpr::InList {
+            expr: Box::new(compile(expr)?),
+            list: list.iter().map(&mut compile).collect::<Result<_, _>>()?,
+            negated: *negated,
+        }),
+        // `low <= expr AND expr <= high`, so a NULL end only decides when the
+        // other comparison does not
+        Expression::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            let expr = compile(expr)?;
+            let between = PhysicalExpr::Binary {
+                left: Box::new(PhysicalExpr::Binary {
+                    left: Box::new(expr.clone()),
+                    op: BinaryOperator::GtEq,
+                    right: Box::new(compile(low)?),
+                }),
+                op: BinaryOperator::And,
+                right: Box::new(PhysicalExpr::Binary {
+                    left: Box::new(expr),
+                    op: BinaryOperator::LtEq,
+                    right: Box::new(compile(high)?),
+                }),
+            };
+            Ok(match negated {
+                true => PhysicalExpr::Unary {
+                    op: UnaryOperator::Not,
+                    operand: Box::new(between),
+                },
+                false => between,
+            })
+        }
+        Expression::Like {
+            expr,
+            pattern,
+            escape,
+            negated,
+            case_insensitive,
+        } => Ok(PhysicalExpr::Like {
+            expr: Box::new(compile(expr)?),
+            pattern: Box::new(compile(pattern)?),
+            escape: match escape {
+                Some(escape) => Some(Box::new(compile(escape)?)),
+                None => None,
+            },
+            negated: *negated,
+            case_insensitive: *case_insensitive,
+        }),
+        _ => unreachable!("only IN lists, BETWEEN and LIKE are predicates"),
+    }
+}
+
+fn case(
+    operand: &Option<Box<Expression>>,
+    branches: &[(Expression, Expression)],
+    else_result: &Option<Box<Expression>>,
+    mut compile: impl FnMut(&Expression) -> Result<PhysicalExpr, ExecutionError>,
+) -> Result<PhysicalExpr, ExecutionError> {
+    Ok(PhysicalExpr::Case {
+        operand: match operand {
+            Some(operand) => Some(Box::new(compile(operand)?)),
+            None => None,
+        },
+        branches: branches
+            .iter()
+            .map(|(when, then)| Ok((compile(when)?, compile(then)?)))
+            .collect::<Result<_, ExecutionError>>()?,
+        else_result: match else_result {
+            Some(else_result) => Some(Box::new(compile(else_result)?)),
+            None => None,
+        },
+    })
+}
+
+/// From the table qualifier, if any, to the end of the column name
+pub(crate) fn column_span(table: &Option<Spanned<String>>, name: &Spanned<String>) -> Span {
+    match table {
+        Some(table) if table.span.len > 0 => Span {
+            offset: table.span.offset,
+            len: name.span.offset + name.span.len - table.span.offset,
+        },
+        _ => name.span,
+    }
+}
+
+/// The query of a recursive common table expression split in the anchor,
+/// without `ORDER BY` and `LIMIT`, and the select after its last `UNION`
+/// that runs until it finds no new rows
+pub(crate) fn recursive_step(query: &SelectStatement) -> Option<(SelectStatement, &SetOperation)> {
+    let (last, rest) = query.compound.split_last()?;
+    if last.op != SetOperator::Union {
+        return None;
+    }
+    let anchor = SelectStatement {
+        compound: rest.to_vec(),
+        order_by: vec![],
+        limit: None,
+        offset: None,
+        ..query.clone()
+    };
+    Some((anchor, last))
+}
+
+/// The `LIMIT` and `OFFSET` of a select over its plan
+fn limit<'a>(plan: BoxedOperator<'a>, select: &SelectStatement) -> BoxedOperator<'a> {
+    match select.limit.is_some() || select.offset.is_some() {
+        true => Box::new(Limit::new(
+            plan,
+            select.limit,
+            select.offset.unwrap_or_default(),
+        )),
+        false => plan,
+    }
+}
+
+/// ORDER BY and DISTINCT ON can name a select list alias, or the position of
+/// an output column from 1, instead of repeating its expression. `wildcard`
+/// holds the columns a `*` of the list stands for
+pub(crate) fn resolve_output(
+    expr: &Expression,
+    items: &[&SelectItem],
+    wildcard: &[Expression],
+) -> Result<Expression, ExecutionError> {
+    if let Some(n) = position(expr) {
+        let mut outputs = items.iter().flat_map(|item| match item {
+            SelectItem::Wildcard(_) => wildcard.iter().collect(),
+            SelectItem::Expression { expr, .. } => vec![expr],
+        });
+        return match n.checked_sub(1).and_then(|index| outputs.nth(index)) {
+            Some(output) => Ok(output.clone()),
+            None => Err(ExecutionError::PositionNotInSelectList(n)),
+        };
+    }
+    if let Expression::Column { table: None, name } = expr {
+        for item in items {
+            if let SelectItem::Expression {
+                expr: aliased,
+                alias: Some(alias),
+            } = item
+            {
+                if *alias == name.node {
+                    return Ok(aliased.clone());
+                }
+            }
+        }
+    }
+    Ok(expr.clone())
+}
+
+/// An integer literal in ORDER BY or DISTINCT ON, which stands for the output
+/// column at that position
+pub(crate) fn position(expr: &Expression) -> Option<usize> {
+    match expr {
+        Expression::Literal(Spanned {
+            node: Value::Number(n),
+            ..
+        }) if n.is_integer() => n.to_usize(),
+        _ => None,
+    }
+}
+
+/// The columns `*` stands for over `schema`, qualified by their table
+fn wildcard_columns(schema: &Schema) -> Vec<Expression> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| Expression::Column {
+            table: field.table.as_deref().map(Into::into),
+            name: field.name.as_str().into(),
+        })
+        .collect()
+}
+
+fn collect_aggregates(functions: &FunctionRegistry, expr: &Expression, out: &mut Vec<Expression>) {
+    struct Aggregates<'a>(&'a FunctionRegistry, &'a mut Vec<Expression>);
+
+    impl Visitor for Aggregates<'_> {
+        // aggregates of a subquery belong to it
+        fn visit_select(&mut self, _select: &SelectStatement) {}
+
+        fn visit_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::Function { name, .. } if self.0.aggregate(name).is_some() => {
+                    if !self.1.contains(expr) {
+                        self.1.push(expr.clone());
+                    }
+                }
+                expr => walk_expression(self, expr),
+            }
+        }
+    }
+
+    Aggregates(functions, out).visit_expression(expr);
+}
+
+/// Window function calls of an expression, those in the arguments of one
+/// are not allowed and left for the compiler to report
+fn collect_windows(expr: &Expression, out: &mut Vec<Expression>) {
+    struct Windows<'a>(&'a mut Vec<Expression>);
+
+    impl Visitor for Windows<'_> {
+        // window functions of a subquery belong to it
+        fn visit_select(&mut self, _select: &SelectStatement) {}
+
+        fn visit_expression(&mut self, expr: &Expression) {
+            match expr {
+                Expression::Window { .. } => {
+                    if !self.0.contains(expr) {
+                        self.0.push(expr.clone());
+                    }
+                }
+                expr => walk_expression(self, expr),
+            }
+        }
+    }
+
+    Windows(out).visit_expression(expr);
+}
+
+pub(crate) fn output_field(expr: &Expression, alias: Option<&str>) -> Field {
+    match (alias, expr) {
+        (Some(alias), _) => Field::new(None, alias),
+        (None, Expression::Column { table, name }) => {
+            Field::new(table.as_ref().map(|t| t.to_string()), name.to_string())
+        }
+        (None, Expression::Function { name, .. } | Expression::Window { name, .. }) => {
+            Field::new(None, name.to_string())
+        }
+        _ => Field::new(None, "?column?"),
+    }
+}
+
+/// Group by expressions and aggregate calls of a query, the aggregate
+/// operator outputs the group by values followed by the aggregate results
+struct Grouping {
+    group_by: Vec<Expression>,
+    aggregates: Vec<Expression>,
+    /// Window function calls computed over the groups, read after the
+    /// aggregate results
+    windows: Vec<Expression>,
+}
+
+impl Grouping {
+    /// Group by expressions, aggregates and output schema of the aggregate
+    /// operator reading from `input`
+    fn physical(
+        &self,
+        planner: &Planner<'_>,
+        input: &Schema,
+    ) -> Result<(Vec<PhysicalExpr>, Vec<AggregateExpr>, Schema), ExecutionError> {
+        let group_by: Vec<PhysicalExpr> = self
+            .group_by
+            .iter()
+            .map(|expr| planner.compile(expr, input))
+            .collect::<Result<_, _>>()?;
+
+        let aggregates = self
+            .aggregates
+            .iter()
+            .map(|expr| {
+                let Expression::Function { name, args } = expr else {
+                    unreachable!("only function calls are collected as aggregates")
+                };
+                let function = planner.functions.aggregate(name).ok_or_else(|| {
+                    ExecutionError::FunctionNotFound(name.to_string(), name.span.into())
+                })?;
+                let arg = match (&function, args.as_slice()) {
+                    (AggregateFunction::Count, [Expression::Wildcard]) => None,
+                    (_, [arg]) => Some(planner.compile(arg, input)?),
+                    _ => {
+                        return Err(ExecutionError::WrongArgumentCount(
+                            name.to_string(),
+                            "one argument".to_string(),
+                            name.span.into(),
+                        ))
+                    }
+                };
+                Ok(AggregateExpr { function, arg })
+            })
+            .collect::<Result<_, ExecutionError>>()?;
+
+        // a grouped column keeps its table, so a correlated subquery can read
+        // it however it is qualified
+        let fields = self
+            .group_by
+            .iter()
+            .zip(&group_by)
+            .map(|(expr, physical)| match (expr, physical) {
+                (Expression::Column { .. }, PhysicalExpr::Column(index)) => {
+                    input.fields()[*index].clone()
+                }
+                _ => output_field(expr, None),
+            })
+            .chain(self.aggregates.iter().map(|expr| output_field(expr, None)))
+            .collect();
+
+        Ok((group_by, aggregates, Schema::new(fields)))
+    }
+
+    /// Resolve an expression evaluated on the aggregate output, `compiler`
+    /// reads that output and correlates the subqueries with it
+    fn compile(
+        &self,
+        compiler: &mut Compiler<'_, '_>,
+        expr: &Expression,
+    ) -> Result<PhysicalExpr, ExecutionError> {
+        if let Some(index) = self.group_by.iter().position(|group| group == expr) {
+            return Ok(PhysicalExpr::Column(index));
+        }
+
+        let planner = compiler.planner;
+
+        match expr {
+            Expression::Function { name, .. } if planner.functions.aggregate(name).is_some() => {
+                let index = self
+                    .aggregates
+                    .iter()
+                    .position(|agg| agg == expr)
+                    .ok_or_else(|| {
+                        ExecutionError::AggregateNotAllowed(name.to_string(), name.span.into())
+                    })?;
+                Ok(PhysicalExpr::Column(self.group_by.len() + index))
+            }
+            Expression::Function { name, args } => {
+                planner.function(name, args, |arg| self.compile(compiler, arg))
+            }
+            Expression::Column { table, name } => {
+                let display_name = match table {
+                    Some(table) => format!("{table}.{name}"),
+                    None => name.to_string(),
+                };
+                Err(ExecutionError::ColumnNotGrouped(
+                    display_name,
+                    column_span(table, name).into(),
+                ))
+            }
+            Expression::Binary { left, op, right } => Ok(PhysicalExpr::Binary {
+                left: Box::new(self.compile(compiler, left)?),
+                op: *op,
+                right: Box::new(self.compile(compiler, right)?),
+            }),
+            Expression::Unary { op, operand } => Ok(PhysicalExpr::Unary {
+                op: *op,
+                operand: Box::new(self.compile(compiler, operand)?),
+            }),
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => case(operand, branches, else_result, |expr| {
+                self.compile(compiler, expr)
+            }),
+            Expression::InList { .. } | Expression::Between { .. } | Expression::Like { .. } => {
+                predicate(expr, |expr| self.compile(compiler, expr))
+            }
+            Expression::Cast { expr, type_info } => Ok(PhysicalExpr::Cast {
+                expr: Box::new(self.compile(compiler, expr)?),
+                type_info: *type_info,
+            }),
+            // the subquery reads the grouped columns of the aggregate output
+            Expression::Subquery(_) | Expression::Exists(_) => compiler.compile(expr),
+            Expression::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let kind = SubqueryKind::In {
+                    test: self.compile(compiler, expr)?,
+                    negated: *negated,
+                };
+                compiler.subquery(kind, subquery)
+            }
+            Expression::Window { name, .. } => {
+                let index = self
+                    .windows
+                    .iter()
+                    .position(|window| window == expr)
+                    .ok_or_else(|| {
+                        ExecutionError::WindowNotAllowed(name.to_string(), name.span.into())
+                    })?;
+                Ok(PhysicalExpr::Column(
+                    self.group_by.len() + self.aggregates.len() + index,
+                ))
+            }
+            Expression::Literal(_) | Expression::Parameter(_) | Expression::Wildcard => {
+                compiler.compile(expr)
+            }
+        }
+    }
+}
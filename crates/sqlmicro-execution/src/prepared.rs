@@ -0,0 +1,130 @@
+//! Prepared statements.
+//!
+//! A statement is parsed once with `?` or `$n` placeholders where values go,
+//! [`crate::executor::Executor::prepare`] numbers the placeholders and has the
+//! binder check the statement and infer the type each one expects from what it
+//! is compared with or passed to, and every
+//! [`crate::executor::Executor::execute`] binds new values into the already
+//! parsed statement.
+
+use std::collections::HashMap;
+
+use sqlmicro_parser::{
+    expression::{Expression, Parameter},
+    parse::Spanned,
+    query::SqlQuery,
+    value::Value,
+    visit::{walk_expression_mut, VisitorMut},
+    SqlTypeInfo,
+};
+
+use crate::{binder::Binder, functions::FunctionRegistry, table::Table, ExecutionError};
+
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    query: SqlQuery,
+    parameters: Vec<Option<SqlTypeInfo>>,
+}
+
+impl PreparedStatement {
+    pub(crate) fn new(
+        mut query: SqlQuery,
+        tables: &HashMap<String, Table>,
+        functions: &FunctionRegistry,
+    ) -> Result<Self, ExecutionError> {
+        let mut numbering = Numbering::default();
+        numbering.visit_query_mut(&mut query);
+        if numbering.anonymous > 0 && numbering.positional {
+            return Err(ExecutionError::MixedParameters);
+        }
+
+        let parameters = Binder::new(tables, functions).parameters(&query, numbering.count)?;
+
+        Ok(Self { query, parameters })
+    }
+
+    /// The statement, with every placeholder numbered
+    pub fn query(&self) -> &SqlQuery {
+        &self.query
+    }
+
+    /// Type expected by each parameter, `None` when nothing in the statement
+    /// constrains it
+    pub fn parameters(&self) -> &[Option<SqlTypeInfo>] {
+        &self.parameters
+    }
+
+    /// Copy of the statement with every placeholder replaced by its value
+    pub(crate) fn bind(&self, values: &[Value]) -> Result<SqlQuery, ExecutionError> {
+        if values.len() != self.parameters.len() {
+            return Err(ExecutionError::ParameterCountMismatch {
+                expected: self.parameters.len(),
+                found: values.len(),
+            });
+        }
+
+        for (n, (value, type_info)) in values.iter().zip(&self.parameters).enumerate() {
+            let matches = matches!(
+                (value, type_info),
+                (Value::Null, _)
+                    | (_, None)
+                    | (Value::Number(_), Some(SqlTypeInfo::Int))
+                    | (Value::String(_), Some(SqlTypeInfo::String))
+            );
+            if let (false, Some(type_info)) = (matches, type_info) {
+                return Err(ExecutionError::TypeMismatch(format!(
+                    "parameter ${} expects {type_info}, found {value}",
+                    n + 1
+                )));
+            }
+        }
+
+        let mut query = self.query.clone();
+        Bind(values).visit_query_mut(&mut query);
+        Ok(query)
+    }
+}
+
+/// Turns `?` placeholders into `$n` in order of appearance, and counts the
+/// parameters of the statement
+#[derive(Default)]
+struct Numbering {
+    anonymous: usize,
+    /// Whether the statement has a `$n` of its own, which a `?` could
+    /// silently share a number with
+    positional: bool,
+    count: usize,
+}
+
+impl VisitorMut for Numbering {
+    fn visit_parameter_mut(&mut self, parameter: &mut Parameter) {
+        match parameter {
+            Parameter::Anonymous => {
+                self.anonymous += 1;
+                *parameter = Parameter::Positional(self.anonymous);
+            }
+            Parameter::Positional(_) => self.positional = true,
+        }
+        if let Parameter::Positional(n) = parameter {
+            self.count = self.count.max(*n);
+        }
+    }
+}
+
+/// Replaces every placeholder with its value
+pub(crate) struct Bind<'a>(pub &'a [Value]);
+
+impl VisitorMut for Bind<'_> {
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        match expr {
+            // the value stands where the placeholder was
+            Expression::Parameter(Spanned {
+                node: Parameter::Positional(n),
+                span,
+            }) => {
+                *expr = Expression::Literal(Spanned::new(self.0[*n - 1].clone(), *span));
+            }
+            expr => walk_expression_mut(self, expr),
+        }
+    }
+}
@@ -1,35 +1,40 @@
 use std::rc::Rc;
 
+use sqlmicro_parser::value::Value;
+
 use crate::{
-    table::{ColumnInfo, StoredRow},
+    operator::Tuple,
+    schema::{Field, Schema},
     ExecutionError,
 };
 
-#[derive(Debug)]
-pub struct Row<'a> {
+#[derive(Debug, Clone)]
+pub struct Row {
     id: usize,
-    columns: Rc<ColumnInfo>,
-    data: &'a StoredRow,
+    schema: Rc<Schema>,
+    data: Tuple,
 }
 
-impl<'a> Row<'a> {
-    pub fn new(columns: Rc<ColumnInfo>, id: usize, data: &'a StoredRow) -> Self {
-        Self { id, columns, data }
+impl Row {
+    pub fn new(schema: Rc<Schema>, id: usize, data: Tuple) -> Self {
+        Self { id, schema, data }
     }
 
-    pub fn columns(&self) -> &ColumnInfo {
-        &self.columns.as_ref()
+    pub fn columns(&self) -> &[Field] {
+        self.schema.fields()
     }
 
-    pub fn get(&self, column: &String) -> String {
+    pub fn get(&self, column: &str) -> String {
         self.try_get(column).unwrap()
     }
 
-    pub fn try_get(&self, column: &String) -> Result<String, ExecutionError> {
-        self.data.get(column).map_or_else(
-            || Err(ExecutionError::ColumnDoesNotExists(column.to_owned())),
-            |val| Ok(val.to_string()),
-        )
+    pub fn try_get(&self, column: &str) -> Result<String, ExecutionError> {
+        let index = self.schema.index_of(None, column)?;
+        Ok(self.data[index].to_string())
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.data
     }
 
     pub fn id(&self) -> usize {
@@ -0,0 +1,93 @@
+use sqlmicro_parser::parse::Span;
+
+use crate::ExecutionError;
+
+/// A column produced by an operator, optionally qualified by the table it
+/// comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub table: Option<String>,
+    pub name: String,
+}
+
+impl Field {
+    pub fn new(table: Option<String>, name: impl Into<String>) -> Self {
+        Self {
+            table,
+            name: name.into(),
+        }
+    }
+}
+
+/// Ordered list of the fields of the tuples an operator yields
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self { fields }
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Position of a (possibly qualified) column, errors are not pointed at
+    /// the column's name yet
+    pub fn index_of(&self, table: Option<&str>, name: &str) -> Result<usize, ExecutionError> {
+        let unknown = || Span::default().into();
+        let display_name = || match table {
+            Some(table) => format!("{table}.{name}"),
+            None => name.to_string(),
+        };
+
+        let mut matches = self.fields.iter().enumerate().filter(|(_, field)| {
+            field.name == name && table.is_none_or(|t| field.table.as_deref() == Some(t))
+        });
+
+        let (index, _) = matches
+            .next()
+            .ok_or_else(|| ExecutionError::ColumnDoesNotExists(display_name(), unknown()))?;
+
+        if matches.next().is_some() {
+            return Err(ExecutionError::AmbiguousColumn(display_name(), unknown()));
+        }
+
+        Ok(index)
+    }
+
+    /// The same columns without the tables they come from
+    pub fn unqualified(&self) -> Schema {
+        Schema::new(
+            self.fields
+                .iter()
+                .map(|field| Field::new(None, field.name.clone()))
+                .collect(),
+        )
+    }
+
+    /// The same columns, all from `table`
+    pub fn qualified(&self, table: &str) -> Schema {
+        Schema::new(
+            self.fields
+                .iter()
+                .map(|field| Field::new(Some(table.to_string()), field.name.clone()))
+                .collect(),
+        )
+    }
+
+    /// Schema of the concatenation of tuples from `self` and `other`
+    pub fn join(&self, other: &Schema) -> Schema {
+        Schema::new(self.fields.iter().chain(&other.fields).cloned().collect())
+    }
+}
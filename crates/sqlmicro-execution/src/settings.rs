@@ -0,0 +1,46 @@
+use sqlmicro_parser::value::Value;
+
+use crate::ExecutionError;
+
+/// Per connection options, changed with `SET <name> = <value>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Most worker threads a single query may use, 1 runs every query serially
+    pub max_parallelism: usize,
+    /// Most iterations a recursive common table expression may take before
+    /// the query fails
+    pub recursion_limit: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_parallelism: 1,
+            recursion_limit: 100,
+        }
+    }
+}
+
+impl Settings {
+    pub fn set(&mut self, name: &str, value: &Value) -> Result<(), ExecutionError> {
+        let invalid = || ExecutionError::InvalidSettingValue(name.to_string(), value.to_string());
+
+        let count = || match value {
+            Value::Number(n) => n
+                .to_string()
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        };
+
+        match name {
+            "max_parallelism" => self.max_parallelism = count()?,
+            "recursion_limit" => self.recursion_limit = count()?,
+            _ => return Err(ExecutionError::SettingNotFound(name.to_string())),
+        }
+
+        Ok(())
+    }
+}
@@ -1,81 +1,289 @@
 use std::{
     collections::{BTreeMap, HashMap},
-    rc::Rc,
+    ops::Range,
 };
 
 use serde::{Deserialize, Serialize};
-use sqlmicro_parser::Column;
+use sqlmicro_parser::{value::Value, Column, KeyKind, TableLayout};
 
-use crate::row::Row;
+use crate::{expression::cast, operator::Tuple, ExecutionError};
 
-pub type StoredRow = HashMap<String, String>;
+pub type StoredRow = Vec<Value>;
 
 pub type ColumnInfo = Vec<Column>;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Storage {
+    /// Rows by id
+    Rows(BTreeMap<usize, StoredRow>),
+    /// One vector per column, the row id is the position in the vectors
+    Columns(Vec<Vec<Value>>),
+}
+
+/// A key of the table, with the id of the row holding each of its values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Index {
+    kind: KeyKind,
+    /// Positions of the columns of the key
+    columns: Vec<usize>,
+    rows: HashMap<Vec<Value>, usize>,
+}
+
+impl Index {
+    /// Values of the key in `row`, `None` when one is `NULL` as such rows
+    /// never conflict
+    fn key(&self, row: &[Value]) -> Option<Vec<Value>> {
+        let key: Vec<Value> = self.columns.iter().map(|&c| row[c].clone()).collect();
+        (!key.contains(&Value::Null)).then_some(key)
+    }
+}
+
+/// A change made to a table, kept to undo the changes of a statement that
+/// fails part way
+#[derive(Debug)]
+pub(crate) enum Change {
+    Inserted(usize),
+    /// The row with its values before the update
+    Updated(usize, StoredRow),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Table {
-    rows: BTreeMap<usize, StoredRow>,
+    storage: Storage,
     columns: ColumnInfo,
+    keys: Vec<Index>,
 }
 
 impl Table {
-    pub fn new(columns: Vec<Column>) -> Self {
+    pub fn new(columns: Vec<Column>, layout: TableLayout) -> Self {
+        let storage = match layout {
+            TableLayout::Row => Storage::Rows(BTreeMap::new()),
+            TableLayout::Columnar => Storage::Columns(vec![Vec::new(); columns.len()]),
+        };
         Self {
-            rows: BTreeMap::new(),
+            storage,
             columns,
+            keys: Vec::new(),
         }
     }
 
-    pub fn insert(&mut self, values: Vec<String>) {
-        let id = self
-            .rows
-            .last_key_value()
-            .map_or(0, |(max_id, _)| max_id + 1);
+    /// Add a key over the columns at `columns`, to a table without rows
+    pub fn add_key(&mut self, kind: KeyKind, columns: Vec<usize>) {
+        debug_assert_eq!(self.len(), 0, "keys are added to new tables");
+        self.keys.push(Index {
+            kind,
+            columns,
+            rows: HashMap::new(),
+        });
+    }
 
-        let row: StoredRow = values
-            .into_iter()
-            .zip(self.columns.iter())
-            .map(|(v, col)| (col.name.to_owned(), v))
-            .collect();
+    /// Kind and column positions of each key
+    pub fn keys(&self) -> impl Iterator<Item = (KeyKind, &[usize])> {
+        self.keys
+            .iter()
+            .map(|index| (index.kind, index.columns.as_slice()))
+    }
 
-        self.rows.insert(id, row);
+    /// Id of the row with the same values as `row` for the `key`th key
+    pub fn conflict(&self, key: usize, row: &[Value]) -> Option<usize> {
+        let index = &self.keys[key];
+        index.key(row).and_then(|key| index.rows.get(&key).copied())
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = Row> {
-        self.into_iter()
+    /// Convert `values` to the types of their columns the way `CAST` does, so
+    /// a number stored in an `INT` column is rounded to an integer
+    pub fn coerce(&self, values: &mut [Value]) -> Result<(), ExecutionError> {
+        for (value, column) in values.iter_mut().zip(&self.columns) {
+            *value = cast(value, column.type_info)?;
+        }
+        Ok(())
     }
-}
 
-pub(crate) struct TableIter<'a> {
-    map_iter: std::collections::btree_map::Iter<'a, usize, StoredRow>,
-    columns: Rc<ColumnInfo>,
-}
+    /// Store a row and return its id, missing trailing values are stored as
+    /// `NULL` and extra values are dropped. Fails when a key of the row is
+    /// already in the table
+    pub fn insert(&mut self, mut values: Vec<Value>) -> Result<usize, ExecutionError> {
+        values.resize(self.columns.len(), Value::Null);
+        self.coerce(&mut values)?;
+        self.check(&values, None)?;
 
-impl<'a> TableIter<'a> {
-    pub fn new(
-        map_iter: std::collections::btree_map::Iter<'a, usize, StoredRow>,
-        columns: Rc<ColumnInfo>,
-    ) -> Self {
-        Self { map_iter, columns }
+        let id = match &self.storage {
+            Storage::Rows(rows) => rows.last_key_value().map_or(0, |(max_id, _)| max_id + 1),
+            Storage::Columns(_) => self.len(),
+        };
+        self.index(id, &values);
+        match &mut self.storage {
+            Storage::Rows(rows) => {
+                rows.insert(id, values);
+            }
+            Storage::Columns(columns) => {
+                for (column, value) in columns.iter_mut().zip(values) {
+                    column.push(value);
+                }
+            }
+        }
+        Ok(id)
     }
-}
 
-impl<'a> Iterator for TableIter<'a> {
-    type Item = Row<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.map_iter
-            .next()
-            .map(|(id, data)| Row::new(self.columns.clone(), id.clone(), data))
+    /// Values of the row `id`
+    pub fn get(&self, id: usize) -> StoredRow {
+        match &self.storage {
+            Storage::Rows(rows) => rows[&id].clone(),
+            Storage::Columns(columns) => columns.iter().map(|column| column[id].clone()).collect(),
+        }
     }
-}
 
-impl<'a> IntoIterator for &'a Table {
-    type Item = Row<'a>;
+    /// Replace the values of the row `id` and return the previous ones,
+    /// fails when a key of the new values is in another row
+    pub fn update(
+        &mut self,
+        id: usize,
+        mut values: StoredRow,
+    ) -> Result<StoredRow, ExecutionError> {
+        self.coerce(&mut values)?;
+        self.check(&values, Some(id))?;
+        Ok(self.replace(id, values))
+    }
 
-    type IntoIter = TableIter<'a>;
+    /// Revert a change, the changes of a statement are undone last first
+    pub fn undo(&mut self, change: Change) {
+        match change {
+            Change::Inserted(id) => {
+                let values = self.get(id);
+                self.unindex(&values);
+                match &mut self.storage {
+                    Storage::Rows(rows) => {
+                        rows.remove(&id);
+                    }
+                    Storage::Columns(columns) => {
+                        for column in columns {
+                            column.truncate(id);
+                        }
+                    }
+                }
+            }
+            Change::Updated(id, values) => {
+                self.replace(id, values);
+            }
+        }
+    }
+
+    fn replace(&mut self, id: usize, values: StoredRow) -> StoredRow {
+        let previous = self.get(id);
+        self.unindex(&previous);
+        self.index(id, &values);
+        match &mut self.storage {
+            Storage::Rows(rows) => {
+                rows.insert(id, values);
+            }
+            Storage::Columns(columns) => {
+                for (column, value) in columns.iter_mut().zip(values) {
+                    column[id] = value;
+                }
+            }
+        }
+        previous
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        let col_info = Rc::new(self.columns.clone());
-        TableIter::new(self.rows.iter(), col_info)
+    /// Check `values` can be stored as the row `id`, or as a new row
+    fn check(&self, values: &[Value], id: Option<usize>) -> Result<(), ExecutionError> {
+        for index in &self.keys {
+            let names = || {
+                let names: Vec<&str> = index
+                    .columns
+                    .iter()
+                    .map(|&c| self.columns[c].name.as_str())
+                    .collect();
+                names.join(", ")
+            };
+            let Some(key) = index.key(values) else {
+                if index.kind == KeyKind::Primary {
+                    return Err(ExecutionError::NullKey(names()));
+                }
+                continue;
+            };
+            if index.rows.get(&key).is_some_and(|row| Some(*row) != id) {
+                let key: Vec<String> = key.iter().map(Value::to_string).collect();
+                return Err(ExecutionError::UniqueViolation(
+                    format!("{} ({})", index.kind, names()),
+                    key.join(", "),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn index(&mut self, id: usize, values: &[Value]) {
+        for index in &mut self.keys {
+            if let Some(key) = index.key(values) {
+                index.rows.insert(key, id);
+            }
+        }
+    }
+
+    fn unindex(&mut self, values: &[Value]) {
+        for index in &mut self.keys {
+            if let Some(key) = index.key(values) {
+                index.rows.remove(&key);
+            }
+        }
+    }
+
+    pub fn columns(&self) -> &ColumnInfo {
+        &self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Rows(rows) => rows.len(),
+            Storage::Columns(columns) => columns.first().map_or(0, Vec::len),
+        }
+    }
+
+    /// Column vectors of a columnar table
+    pub fn column_data(&self) -> Option<&[Vec<Value>]> {
+        match &self.storage {
+            Storage::Rows(_) => None,
+            Storage::Columns(columns) => Some(columns),
+        }
+    }
+
+    /// Row ids span of the table, the positions for a columnar table
+    fn bounds(&self) -> Range<usize> {
+        match &self.storage {
+            Storage::Rows(rows) => match (rows.first_key_value(), rows.last_key_value()) {
+                (Some((first, _)), Some((last, _))) => *first..last + 1,
+                _ => 0..0,
+            },
+            Storage::Columns(_) => 0..self.len(),
+        }
+    }
+
+    /// Split the table into at most `count` contiguous, non empty ranges of
+    /// row ids that together cover every row in order
+    pub fn partitions(&self, count: usize) -> Vec<Range<usize>> {
+        let bounds = self.bounds();
+        let size = bounds.len().div_ceil(count.max(1)).max(1);
+
+        bounds
+            .clone()
+            .step_by(size)
+            .map(|start| start..(start + size).min(bounds.end))
+            .collect()
+    }
+
+    /// Every row as an owned tuple, whatever the layout
+    pub fn tuples(&self) -> Box<dyn Iterator<Item = Tuple> + '_> {
+        self.tuples_in(self.bounds())
+    }
+
+    /// Rows whose id falls in `range`
+    pub fn tuples_in(&self, range: Range<usize>) -> Box<dyn Iterator<Item = Tuple> + '_> {
+        match &self.storage {
+            Storage::Rows(rows) => Box::new(rows.range(range).map(|(_, row)| row.clone())),
+            Storage::Columns(columns) => {
+                Box::new(range.map(|i| columns.iter().map(|column| column[i].clone()).collect()))
+            }
+        }
     }
 }
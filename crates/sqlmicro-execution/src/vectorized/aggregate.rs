@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+
+use sqlmicro_parser::value::Value;
+
+use crate::{
+    expression::PhysicalExpr,
+    operator::{AggregateExpr, Groups, Tuple},
+    schema::Schema,
+    ExecutionError,
+};
+
+use super::{Batch, BatchOperator, BoxedBatchOperator};
+
+/// Hash aggregation over batches, same output as [`crate::operator::Aggregate`]
+/// as a single batch
+pub struct BatchAggregate<'a> {
+    input: BoxedBatchOperator<'a>,
+    group_by: Vec<PhysicalExpr>,
+    aggregates: Vec<AggregateExpr>,
+    schema: Schema,
+    output: Option<Batch<'a>>,
+}
+
+impl<'a> BatchAggregate<'a> {
+    pub fn new(
+        input: BoxedBatchOperator<'a>,
+        group_by: Vec<PhysicalExpr>,
+        aggregates: Vec<AggregateExpr>,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            input,
+            group_by,
+            aggregates,
+            schema,
+            output: None,
+        }
+    }
+
+    /// Aggregate arguments of a batch, `count(*)` gets a non null per row
+    fn arguments<'b>(&self, batch: &'b Batch<'_>) -> Result<Vec<Cow<'b, [Value]>>, ExecutionError> {
+        self.aggregates
+            .iter()
+            .map(|agg| match &agg.arg {
+                Some(arg) => arg.evaluate_batch(batch),
+                None => Ok(Cow::Owned(vec![Value::Boolean(true); batch.len()])),
+            })
+            .collect()
+    }
+
+    /// Consume the whole input into per group accumulators
+    pub(crate) fn accumulate(&mut self) -> Result<Groups, ExecutionError> {
+        self.input.open()?;
+
+        let mut groups = Groups::new(&self.aggregates, !self.group_by.is_empty());
+
+        while let Some(batch) = self.input.next_batch()? {
+            let arguments = self.arguments(&batch)?;
+
+            // a single group takes whole column slices
+            if self.group_by.is_empty() {
+                for (acc, values) in groups.group(Vec::new()).iter_mut().zip(&arguments) {
+                    acc.update_batch(values)?;
+                }
+                continue;
+            }
+
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| expr.evaluate_batch(&batch))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for row in 0..batch.len() {
+                let key: Tuple = keys.iter().map(|column| column[row].clone()).collect();
+                for (acc, values) in groups.group(key).iter_mut().zip(&arguments) {
+                    acc.update(&values[row])?;
+                }
+            }
+        }
+
+        Ok(groups)
+    }
+}
+
+impl<'a> BatchOperator<'a> for BatchAggregate<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        let tuples = self.accumulate()?.finish()?;
+
+        let mut columns = vec![Vec::with_capacity(tuples.len()); self.schema.len()];
+        let len = tuples.len();
+        for tuple in tuples {
+            for (column, value) in columns.iter_mut().zip(tuple) {
+                column.push(value);
+            }
+        }
+
+        self.output = Some(Batch::new(
+            columns.into_iter().map(Cow::Owned).collect(),
+            len,
+        ));
+        Ok(())
+    }
+
+    fn next_batch(&mut self) -> Result<Option<Batch<'a>>, ExecutionError> {
+        Ok(self.output.take().filter(|batch| !batch.is_empty()))
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.output = None;
+        self.input.close()
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    expression::{truth, PhysicalExpr},
+    schema::Schema,
+    ExecutionError,
+};
+
+use super::{Batch, BatchOperator, BoxedBatchOperator};
+
+/// Keeps the rows of each batch for which the predicate is true
+pub struct BatchFilter<'a> {
+    input: BoxedBatchOperator<'a>,
+    predicate: PhysicalExpr,
+}
+
+impl<'a> BatchFilter<'a> {
+    pub fn new(input: BoxedBatchOperator<'a>, predicate: PhysicalExpr) -> Self {
+        Self { input, predicate }
+    }
+}
+
+impl<'a> BatchOperator<'a> for BatchFilter<'a> {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<Batch<'a>>, ExecutionError> {
+        while let Some(batch) = self.input.next_batch()? {
+            let mask = self.predicate.evaluate_batch(&batch)?;
+
+            let mut selected = Vec::with_capacity(batch.len());
+            for (i, value) in mask.iter().enumerate() {
+                if truth(value)? {
+                    selected.push(i);
+                }
+            }
+
+            // only copy the rows when some were filtered out
+            match selected.len() {
+                0 => continue,
+                n if n == batch.len() => return Ok(Some(batch)),
+                _ => return Ok(Some(batch.gather(&selected))),
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.input.close()
+    }
+}
@@ -0,0 +1,135 @@
+//! Batch at a time operators over column vectors.
+//!
+//! Columnar tables are scanned in batches of [`BATCH_SIZE`] rows that borrow
+//! the table storage, so filters, projections and aggregates work on whole
+//! column slices instead of building a tuple per row. [`Unbatch`] turns a
+//! batch pipeline back into a row [`Operator`] for the rest of the plan.
+
+mod aggregate;
+mod filter;
+mod project;
+mod scan;
+
+use std::borrow::Cow;
+
+pub use aggregate::*;
+pub use filter::*;
+pub use project::*;
+pub use scan::*;
+use sqlmicro_parser::value::Value;
+
+use crate::{
+    operator::{Operator, Tuple},
+    schema::Schema,
+    ExecutionError,
+};
+
+pub const BATCH_SIZE: usize = 1024;
+
+/// Rows stored column by column, columns may borrow the table storage
+#[derive(Debug, Clone)]
+pub struct Batch<'a> {
+    columns: Vec<Cow<'a, [Value]>>,
+    len: usize,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(columns: Vec<Cow<'a, [Value]>>, len: usize) -> Self {
+        Self { columns, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn column(&self, index: usize) -> &[Value] {
+        &self.columns[index]
+    }
+
+    pub fn row(&self, index: usize) -> Tuple {
+        self.columns
+            .iter()
+            .map(|column| column[index].clone())
+            .collect()
+    }
+
+    /// Copy of the rows at `indices`
+    pub fn gather(&self, indices: &[usize]) -> Batch<'static> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| Cow::Owned(indices.iter().map(|&i| column[i].clone()).collect()))
+            .collect();
+        Batch::new(columns, indices.len())
+    }
+}
+
+/// Pull based operator yielding batches, see [`Operator`]
+pub trait BatchOperator<'a> {
+    fn schema(&self) -> &Schema;
+
+    fn open(&mut self) -> Result<(), ExecutionError>;
+
+    /// Produce the next non empty batch, `None` once the operator is exhausted
+    fn next_batch(&mut self) -> Result<Option<Batch<'a>>, ExecutionError>;
+
+    fn close(&mut self) -> Result<(), ExecutionError>;
+}
+
+pub type BoxedBatchOperator<'a> = Box<dyn BatchOperator<'a> + 'a>;
+
+/// Yields the rows of a batch pipeline one at a time
+pub struct Unbatch<'a> {
+    input: BoxedBatchOperator<'a>,
+    current: Option<Batch<'a>>,
+    position: usize,
+}
+
+impl<'a> Unbatch<'a> {
+    pub fn new(input: BoxedBatchOperator<'a>) -> Self {
+        Self {
+            input,
+            current: None,
+            position: 0,
+        }
+    }
+}
+
+impl<'a> Operator for Unbatch<'a> {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.current = None;
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, ExecutionError> {
+        loop {
+            if let Some(batch) = &self.current {
+                if self.position < batch.len() {
+                    self.position += 1;
+                    return Ok(Some(batch.row(self.position - 1)));
+                }
+            }
+
+            match self.input.next_batch()? {
+                Some(batch) => {
+                    self.current = Some(batch);
+                    self.position = 0;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.current = None;
+        self.input.close()
+    }
+}
@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+
+use crate::{expression::PhysicalExpr, schema::Schema, ExecutionError};
+
+use super::{Batch, BatchOperator, BoxedBatchOperator};
+
+/// Evaluates one expression per output column for every batch
+pub struct BatchProject<'a> {
+    input: BoxedBatchOperator<'a>,
+    exprs: Vec<PhysicalExpr>,
+    schema: Schema,
+}
+
+impl<'a> BatchProject<'a> {
+    pub fn new(input: BoxedBatchOperator<'a>, exprs: Vec<PhysicalExpr>, schema: Schema) -> Self {
+        Self {
+            input,
+            exprs,
+            schema,
+        }
+    }
+}
+
+impl<'a> BatchOperator<'a> for BatchProject<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<Batch<'a>>, ExecutionError> {
+        let Some(batch) = self.input.next_batch()? else {
+            return Ok(None);
+        };
+
+        let columns = self
+            .exprs
+            .iter()
+            .map(|expr| match expr {
+                // keep borrowing the input column instead of copying it
+                PhysicalExpr::Column(index) => Ok(batch.columns[*index].clone()),
+                expr => Ok(Cow::Owned(expr.evaluate_batch(&batch)?.into_owned())),
+            })
+            .collect::<Result<_, ExecutionError>>()?;
+
+        Ok(Some(Batch::new(columns, batch.len())))
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        self.input.close()
+    }
+}
@@ -0,0 +1,63 @@
+use std::{borrow::Cow, ops::Range};
+
+use crate::{operator::table_schema, schema::Schema, table::Table, ExecutionError};
+
+use super::{Batch, BatchOperator, BATCH_SIZE};
+
+/// Scan of a columnar table, batches borrow the table column vectors
+pub struct ColumnarScan<'a> {
+    columns: &'a [Vec<sqlmicro_parser::value::Value>],
+    range: Range<usize>,
+    schema: Schema,
+    position: usize,
+}
+
+impl<'a> ColumnarScan<'a> {
+    /// `None` when the table does not use the columnar layout
+    pub(crate) fn new(name: &str, table: &'a Table) -> Option<Self> {
+        Self::partition(name, table, 0..table.len())
+    }
+
+    /// Scan only the rows of one partition, see [`Table::partitions`]
+    pub(crate) fn partition(name: &str, table: &'a Table, range: Range<usize>) -> Option<Self> {
+        Some(Self {
+            columns: table.column_data()?,
+            position: range.start,
+            range,
+            schema: table_schema(name, table),
+        })
+    }
+}
+
+impl<'a> BatchOperator<'a> for ColumnarScan<'a> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.position = self.range.start;
+        Ok(())
+    }
+
+    fn next_batch(&mut self) -> Result<Option<Batch<'a>>, ExecutionError> {
+        if self.position >= self.range.end {
+            return Ok(None);
+        }
+
+        let start = self.position;
+        let end = (start + BATCH_SIZE).min(self.range.end);
+        self.position = end;
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| Cow::Borrowed(&column[start..end]))
+            .collect();
+
+        Ok(Some(Batch::new(columns, end - start)))
+    }
+
+    fn close(&mut self) -> Result<(), ExecutionError> {
+        Ok(())
+    }
+}
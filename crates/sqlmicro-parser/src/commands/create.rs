@@ -1,51 +1,59 @@
+use std::fmt;
+
 use derive_more::Display;
 use nom::{
     branch::alt,
-    bytes::complete::tag_no_case,
-    character::complete::char,
-    character::complete::multispace1,
-    combinator::map,
+    combinator::{cut, map, opt},
     error::context,
-    sequence::{preceded, separated_pair, tuple},
+    sequence::{delimited, pair, preceded, tuple},
 };
 use nom_supreme::ParserExt;
 use serde::{Deserialize, Serialize};
 
-use crate::parse::{comma_sep, identifier, Parse, ParseResult, RawSpan};
+use crate::{
+    parse::{
+        comma_sep, identifier, keyword, located, symbol, CommaSeparated, Parse, ParseResult,
+        Spanned,
+    },
+    token::{Ident, Symbol, Tokens},
+    SelectStatement,
+};
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize, Display, Copy)]
 pub enum SqlTypeInfo {
+    #[display(fmt = "STRING")]
     String,
+    #[display(fmt = "INT")]
     Int,
 }
 
 impl<'a> Parse<'a> for SqlTypeInfo {
-    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
         context(
             "Column Type",
             alt((
-                map(tag_no_case("string"), |_| Self::String),
-                map(tag_no_case("int"), |_| Self::Int),
+                map(keyword("string"), |_| Self::String),
+                map(keyword("int"), |_| Self::Int),
             )),
         )(input)
     }
 }
 
 /// Column's name + type
-#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+#[display(fmt = "{} {type_info}", "Ident(name)")]
 pub struct Column {
-    pub name: String,
+    pub name: Spanned<String>,
     pub type_info: SqlTypeInfo,
 }
 
 impl<'a> Parse<'a> for Column {
-    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
         context(
             "Create Column",
             map(
-                separated_pair(
-                    identifier.context("Column Name"),
-                    multispace1,
+                pair(
+                    located(identifier).context("Column Name"),
                     SqlTypeInfo::parse,
                 ),
                 |(name, type_info)| Self { name, type_info },
@@ -54,48 +62,220 @@ impl<'a> Parse<'a> for Column {
     }
 }
 
+/// Kind of key of a table, both forbid two rows from having the same
+/// values in its columns
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum KeyKind {
+    /// At most one per table, its columns can not be `NULL`
+    #[display(fmt = "PRIMARY KEY")]
+    Primary,
+    /// Rows with a `NULL` in its columns never conflict
+    #[display(fmt = "UNIQUE")]
+    Unique,
+}
+
+impl<'a> Parse<'a> for KeyKind {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Key",
+            alt((
+                map(pair(keyword("primary"), keyword("key")), |_| Self::Primary),
+                map(keyword("unique"), |_| Self::Unique),
+            )),
+        )(input)
+    }
+}
+
+/// A `PRIMARY KEY` or `UNIQUE` constraint, written after a column or on
+/// its own for keys over several columns
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    pub kind: KeyKind,
+    pub columns: Vec<Spanned<String>>,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns: Vec<Ident> = self.columns.iter().map(|c| Ident(c)).collect();
+        write!(f, "{} ({})", self.kind, CommaSeparated(&columns))
+    }
+}
+
+impl<'a> Parse<'a> for Key {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            pair(
+                KeyKind::parse,
+                delimited(
+                    symbol(Symbol::LeftParen),
+                    comma_sep(located(identifier)).context("Key Columns"),
+                    symbol(Symbol::RightParen),
+                ),
+            ),
+            |(kind, columns)| Self { kind, columns },
+        )(input)
+    }
+}
+
+/// How a table stores its rows, chosen with `USING row | columnar`
+#[derive(Debug, Clone, Copy, Default, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum TableLayout {
+    #[default]
+    #[display(fmt = "ROW")]
+    Row,
+    #[display(fmt = "COLUMNAR")]
+    Columnar,
+}
+
+impl<'a> Parse<'a> for TableLayout {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Table Layout",
+            alt((
+                map(keyword("row"), |_| Self::Row),
+                map(keyword("columnar"), |_| Self::Columnar),
+            )),
+        )(input)
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct CreateStatement {
-    pub table: String,
+    pub table: Spanned<String>,
+    /// Empty when the table is created from a query
     pub columns: Vec<Column>,
+    /// Keys of the table, printed after the columns whether they were
+    /// written after a column or not
+    pub keys: Vec<Key>,
+    pub layout: TableLayout,
+    /// `AS SELECT ...`, whose output gives the columns and the rows of the
+    /// table
+    pub query: Option<Box<SelectStatement>>,
 }
 
-fn column_definitions(input: RawSpan<'_>) -> ParseResult<'_, Vec<Column>> {
-    context(
-        "Column Definitions",
+/// Prints `CREATE TABLE`, the layout only when it is not the default one
+impl fmt::Display for CreateStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CREATE TABLE {}", Ident(&self.table))?;
+        if self.query.is_none() {
+            write!(f, " ({}", CommaSeparated(&self.columns))?;
+            for key in &self.keys {
+                write!(f, ", {key}")?;
+            }
+            write!(f, ")")?;
+        }
+        if self.layout != TableLayout::default() {
+            write!(f, " USING {}", self.layout)?;
+        }
+        if let Some(query) = &self.query {
+            write!(f, " AS {query}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An element of the column definitions, a column is followed by the kind
+/// of key it makes on its own if any
+enum Definition {
+    Column(Column, Option<Key>),
+    Key(Key),
+}
+
+fn definition(input: Tokens<'_>) -> ParseResult<'_, Definition> {
+    alt((
+        map(Key::parse, Definition::Key),
         map(
-            tuple((char('('), comma_sep(Column::parse), char(')'))),
-            |(_, cols, _)| cols,
+            tuple((
+                located(identifier).context("Column Name"),
+                SqlTypeInfo::parse,
+                opt(KeyKind::parse),
+            )),
+            |(name, type_info, kind)| {
+                let column = Column {
+                    name: name.clone(),
+                    type_info,
+                };
+                let key = kind.map(|kind| Key {
+                    kind,
+                    columns: vec![name],
+                });
+                Definition::Column(column, key)
+            },
+        )
+        .context("Create Column"),
+    ))(input)
+}
+
+fn column_definitions(input: Tokens<'_>) -> ParseResult<'_, (Vec<Column>, Vec<Key>)> {
+    map(
+        context(
+            "Column Definitions",
+            delimited(
+                symbol(Symbol::LeftParen),
+                comma_sep(definition),
+                symbol(Symbol::RightParen),
+            ),
         ),
+        |definitions| {
+            let mut columns = Vec::new();
+            let mut keys = Vec::new();
+            for definition in definitions {
+                match definition {
+                    Definition::Column(column, key) => {
+                        columns.push(column);
+                        keys.extend(key);
+                    }
+                    Definition::Key(key) => keys.push(key),
+                }
+            }
+            (columns, keys)
+        },
     )(input)
 }
 
-/// parses "CREATE TABLE <table name> <column defs>
+fn layout(input: Tokens<'_>) -> ParseResult<'_, Option<TableLayout>> {
+    opt(preceded(keyword("using"), cut(TableLayout::parse)))(input)
+}
+
+/// parses "CREATE TABLE <table name> <column defs> [USING <layout>]" or
+/// "CREATE TABLE <table name> [USING <layout>] AS <select>"
 impl<'a> Parse<'a> for CreateStatement {
-    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
         map(
-            separated_pair(
+            pair(
                 preceded(
-                    tuple((
-                        tag_no_case("create"),
-                        multispace1,
-                        tag_no_case("table"),
-                        multispace1,
-                    )),
-                    identifier.context("Table Name"),
+                    pair(keyword("create"), keyword("table")),
+                    located(identifier).context("Table Name"),
                 ),
-                multispace1,
-                column_definitions,
+                alt((
+                    map(
+                        pair(column_definitions, layout),
+                        |((columns, keys), layout)| (columns, keys, layout, None),
+                    ),
+                    map(
+                        pair(
+                            layout,
+                            preceded(keyword("as"), cut(SelectStatement::parse.context("Query"))),
+                        ),
+                        |(layout, query)| (Vec::new(), Vec::new(), layout, Some(Box::new(query))),
+                    ),
+                )),
             )
             .context("Create Table"),
-            |(table, columns)| Self { table, columns },
+            |(table, (columns, keys, layout, query))| Self {
+                table,
+                columns,
+                keys,
+                layout: layout.unwrap_or_default(),
+                query,
+            },
         )(input)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse::Parse, Column, CreateStatement, SqlTypeInfo};
+    use crate::{parse::Parse, Column, CreateStatement, Key, KeyKind, SqlTypeInfo, TableLayout};
 
     #[test]
     fn test_create() {
@@ -115,6 +295,9 @@ mod tests {
                     type_info: SqlTypeInfo::String,
                 },
             ],
+            keys: vec![],
+            layout: TableLayout::Row,
+            query: None,
         };
 
         let result = CreateStatement::parse_from_raw(
@@ -125,4 +308,57 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_create_columnar() {
+        let result = CreateStatement::parse_from_raw("CREATE TABLE foo (col1 int) USING columnar")
+            .unwrap()
+            .1;
+
+        assert_eq!(result.layout, TableLayout::Columnar);
+    }
+
+    #[test]
+    fn test_create_keys() {
+        let result = CreateStatement::parse_from_raw(
+            "CREATE TABLE foo (id int primary key, a int, b string, unique (a, b))",
+        )
+        .unwrap()
+        .1;
+
+        assert_eq!(result.columns.len(), 3);
+        assert_eq!(
+            result.keys,
+            [
+                Key {
+                    kind: KeyKind::Primary,
+                    columns: vec!["id".into()],
+                },
+                Key {
+                    kind: KeyKind::Unique,
+                    columns: vec!["a".into(), "b".into()],
+                },
+            ]
+        );
+        assert_eq!(
+            result.to_string(),
+            "CREATE TABLE foo (id INT, a INT, b STRING, PRIMARY KEY (id), UNIQUE (a, b))"
+        );
+    }
+
+    #[test]
+    fn test_create_as() {
+        let result = CreateStatement::parse_from_raw(
+            "CREATE TABLE adults USING columnar AS SELECT name FROM users WHERE age >= 18",
+        )
+        .unwrap()
+        .1;
+
+        assert!(result.columns.is_empty());
+        assert_eq!(result.layout, TableLayout::Columnar);
+        assert_eq!(
+            result.to_string(),
+            "CREATE TABLE adults USING COLUMNAR AS SELECT name FROM users WHERE age >= 18"
+        );
+    }
 }
@@ -1,36 +1,303 @@
+use std::fmt;
+
+use derive_more::Display;
 use nom::{
-    bytes::complete::tag_no_case,
-    character::complete::multispace1,
+    branch::alt,
+    combinator::{cut, map, opt},
     error::context,
-    sequence::{preceded, tuple},
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
 };
 use nom_supreme::ParserExt;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    parse::{comma_sep, identifier, Parse},
-    value::Value,
+    expression::Expression,
+    parse::{
+        comma_sep, identifier, keyword, located, symbol, CommaSeparated, Parse, ParseResult,
+        Spanned,
+    },
+    token::{Ident, Symbol, Tokens},
+    SelectItem, SelectStatement,
 };
 
+/// Where the inserted rows come from
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum InsertSource {
+    /// A single row, each expression evaluated without any row in scope
+    #[display(fmt = "VALUES {}", "CommaSeparated(_0)")]
+    Values(Vec<Expression>),
+    /// Every row the query outputs
+    #[display(fmt = "{_0}")]
+    Select(Box<SelectStatement>),
+}
+
+impl Default for InsertSource {
+    fn default() -> Self {
+        Self::Values(Vec::new())
+    }
+}
+
+/// `column = value`, as in `DO UPDATE SET`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+#[display(fmt = "{} = {value}", "Ident(column)")]
+pub struct Assignment {
+    pub column: Spanned<String>,
+    pub value: Expression,
+}
+
+impl<'a> Parse<'a> for Assignment {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            separated_pair(
+                located(identifier).context("Column Name"),
+                symbol(Symbol::Eq),
+                Expression::parse,
+            ),
+            |(column, value)| Self { column, value },
+        )(input)
+    }
+}
+
+/// What happens to a row whose key is already in the table
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum ConflictAction {
+    #[display(fmt = "DO NOTHING")]
+    Nothing,
+    /// The values are computed from the row in the table, and from the
+    /// rejected one as `excluded`
+    #[display(fmt = "DO UPDATE SET {}", "CommaSeparated(_0)")]
+    Update(Vec<Assignment>),
+}
+
+/// `ON CONFLICT`, the columns of the key to look for conflicts in are only
+/// optional with `DO NOTHING`, which then skips rows conflicting on any key
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct OnConflict {
+    pub target: Vec<Spanned<String>>,
+    pub action: ConflictAction,
+}
+
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ON CONFLICT ")?;
+        if !self.target.is_empty() {
+            let target: Vec<Ident> = self.target.iter().map(|c| Ident(c)).collect();
+            write!(f, "({}) ", CommaSeparated(&target))?;
+        }
+        write!(f, "{}", self.action)
+    }
+}
+
+fn conflict_target(input: Tokens<'_>) -> ParseResult<'_, Vec<Spanned<String>>> {
+    delimited(
+        symbol(Symbol::LeftParen),
+        comma_sep(located(identifier)).context("Conflict Columns"),
+        symbol(Symbol::RightParen),
+    )(input)
+}
+
+impl<'a> Parse<'a> for OnConflict {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let nothing = map(
+            pair(
+                opt(conflict_target),
+                pair(keyword("do"), keyword("nothing")),
+            ),
+            |(target, _)| Self {
+                target: target.unwrap_or_default(),
+                action: ConflictAction::Nothing,
+            },
+        );
+        let update = map(
+            pair(
+                conflict_target,
+                preceded(
+                    tuple((keyword("do"), keyword("update"), keyword("set"))),
+                    cut(comma_sep(Assignment::parse).context("Assignments")),
+                ),
+            ),
+            |(target, assignments)| Self {
+                target,
+                action: ConflictAction::Update(assignments),
+            },
+        );
+        context(
+            "On Conflict",
+            preceded(
+                pair(keyword("on"), keyword("conflict")),
+                cut(alt((nothing, update))),
+            ),
+        )(input)
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct InsertStatement {
-    pub table: String,
-    pub values: Vec<Value>,
+    pub table: Spanned<String>,
+    /// Columns the values go to in order, all the columns of the table when
+    /// empty, the others are left `NULL`
+    pub columns: Vec<Spanned<String>>,
+    pub source: InsertSource,
+    pub on_conflict: Option<OnConflict>,
+    /// Computed from each row inserted or updated, making the statement
+    /// output those values like a select
+    pub returning: Vec<SelectItem>,
+}
+
+/// Prints the column list only when it has one
+impl fmt::Display for InsertStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "INSERT INTO {}", Ident(&self.table))?;
+        if !self.columns.is_empty() {
+            let columns: Vec<Ident> = self.columns.iter().map(|c| Ident(c)).collect();
+            write!(f, " ({})", CommaSeparated(&columns))?;
+        }
+        write!(f, " {}", self.source)?;
+        if let Some(on_conflict) = &self.on_conflict {
+            write!(f, " {on_conflict}")?;
+        }
+        if !self.returning.is_empty() {
+            write!(f, " RETURNING {}", CommaSeparated(&self.returning))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Parse<'a> for InsertSource {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Insert Source",
+            alt((
+                map(
+                    preceded(
+                        keyword("values"),
+                        comma_sep(Expression::parse).context("Values"),
+                    ),
+                    Self::Values,
+                ),
+                map(SelectStatement::parse, |select| {
+                    Self::Select(Box::new(select))
+                }),
+            )),
+        )(input)
+    }
 }
 
 impl<'a> Parse<'a> for InsertStatement {
-    fn parse(input: crate::parse::RawSpan<'a>) -> crate::parse::ParseResult<'a, Self> {
-        let (rem, (_, _, table, _, values)) = context(
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, (_, _, table, columns, source, on_conflict, returning)) = context(
             "Insert Statement",
             tuple((
-                tag_no_case("insert"),
-                preceded(multispace1, tag_no_case("into")),
-                preceded(multispace1, identifier.context("Table Name")),
-                preceded(multispace1, tag_no_case("values")),
-                preceded(multispace1, comma_sep(Value::parse).context("Values")),
+                keyword("insert"),
+                keyword("into"),
+                located(identifier).context("Table Name"),
+                opt(delimited(
+                    symbol(Symbol::LeftParen),
+                    comma_sep(located(identifier)).context("Column Names"),
+                    symbol(Symbol::RightParen),
+                )),
+                InsertSource::parse,
+                opt(OnConflict::parse),
+                opt(preceded(
+                    keyword("returning"),
+                    cut(comma_sep(SelectItem::parse).context("Returning")),
+                )),
             )),
         )(input)?;
 
-        Ok((rem, InsertStatement { table, values }))
+        Ok((
+            rem,
+            InsertStatement {
+                table,
+                columns: columns.unwrap_or_default(),
+                source,
+                on_conflict,
+                returning: returning.unwrap_or_default(),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expression::Parameter;
+
+    use super::*;
+
+    #[test]
+    fn test_insert_parameters() {
+        let expected = InsertStatement {
+            table: "users".into(),
+            columns: vec![],
+            source: InsertSource::Values(vec![
+                Expression::Parameter(Parameter::Positional(1).into()),
+                Expression::Literal(crate::value::Value::String("ana".into()).into()),
+                Expression::Parameter(Parameter::Anonymous.into()),
+            ]),
+            on_conflict: None,
+            returning: vec![],
+        };
+
+        let insert = InsertStatement::parse_from_raw("insert into users values $1, 'ana',?")
+            .unwrap()
+            .1;
+
+        assert_eq!(insert, expected);
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let insert = InsertStatement::parse_from_raw(
+            "insert into users (name, id) select title, 1 from posts",
+        )
+        .unwrap()
+        .1;
+
+        assert_eq!(insert.columns, vec!["name".into(), "id".into()]);
+        assert!(matches!(insert.source, InsertSource::Select(_)));
+        assert_eq!(
+            insert.to_string(),
+            "INSERT INTO users (name, id) SELECT title, 1 FROM posts"
+        );
+    }
+
+    #[test]
+    fn test_on_conflict() {
+        for (sql, expected) in [
+            (
+                "insert into users values 1, 'ana' on conflict do nothing",
+                "INSERT INTO users VALUES 1, 'ana' ON CONFLICT DO NOTHING",
+            ),
+            (
+                "insert into users select * from staged on conflict (id) do nothing",
+                "INSERT INTO users SELECT * FROM staged ON CONFLICT (id) DO NOTHING",
+            ),
+            (
+                "insert into users (id, name) values 1, 'ana' on conflict (id) \
+                 do update set name = excluded.name, age = age + 1",
+                "INSERT INTO users (id, name) VALUES 1, 'ana' ON CONFLICT (id) \
+                 DO UPDATE SET name = excluded.name, age = age + 1",
+            ),
+        ] {
+            let insert = InsertStatement::parse_from_raw(sql).unwrap().1;
+            assert_eq!(insert.to_string(), expected);
+        }
+
+        let insert = InsertStatement::parse_from_raw(
+            "insert into users select * from staged returning id, name as who, *",
+        )
+        .unwrap()
+        .1;
+        assert_eq!(insert.returning.len(), 3);
+        assert_eq!(
+            insert.to_string(),
+            "INSERT INTO users SELECT * FROM staged RETURNING id, name AS who, *"
+        );
+
+        // the key to update is required
+        assert!(InsertStatement::parse_from_raw(
+            "insert into users values 1 on conflict do update set id = 2"
+        )
+        .is_err());
     }
 }
@@ -1,7 +1,9 @@
 mod create;
 mod insert;
 mod select;
+mod set;
 
 pub use create::*;
 pub use insert::*;
 pub use select::*;
+pub use set::*;
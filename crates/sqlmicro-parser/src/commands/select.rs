@@ -1,45 +1,551 @@
+use std::fmt;
+
+use derive_more::Display;
 use nom::{
-    bytes::complete::tag_no_case, character::complete::multispace1, error::context, sequence::tuple,
+    branch::alt,
+    combinator::{cut, map, opt, value},
+    error::context,
+    multi::many0,
+    sequence::{delimited, pair, preceded, tuple},
+    Parser,
 };
 use nom_supreme::ParserExt;
 use serde::{Deserialize, Serialize};
 
-use crate::parse::{comma_sep, identifier, Parse, ParseResult};
+use crate::{
+    error::ParseError,
+    expression::Expression,
+    parse::{
+        comma_sep, identifier, keyword, located, spanned, symbol, token, CommaSeparated, Parse,
+        ParseResult, Spanned,
+    },
+    token::{Ident, Symbol, Token, Tokens},
+};
+
+/// A single entry of the select list
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum SelectItem {
+    /// `*`, with where it is in the source
+    Wildcard(Spanned<()>),
+    Expression {
+        expr: Expression,
+        alias: Option<String>,
+    },
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard(_) => f.write_str("*"),
+            SelectItem::Expression { expr, alias: None } => write!(f, "{expr}"),
+            SelectItem::Expression {
+                expr,
+                alias: Some(alias),
+            } => write!(f, "{expr} AS {}", Ident(alias)),
+        }
+    }
+}
+
+impl<'a> Parse<'a> for SelectItem {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Select Item",
+            alt((
+                map(spanned(symbol(Symbol::Star)), |(_, span)| {
+                    SelectItem::Wildcard(Spanned::new((), span))
+                }),
+                map(
+                    pair(
+                        Expression::parse,
+                        opt(preceded(keyword("as"), identifier.context("Alias"))),
+                    ),
+                    |(expr, alias)| SelectItem::Expression { expr, alias },
+                ),
+            )),
+        )(input)
+    }
+}
+
+/// What a select reads from, `table [[AS] alias]` or `(SELECT ...) [AS] alias`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum TableRef {
+    /// A stored table or a common table expression
+    Table {
+        name: Spanned<String>,
+        alias: Option<Spanned<String>>,
+    },
+    Subquery {
+        query: Box<SelectStatement>,
+        alias: Spanned<String>,
+    },
+}
+
+impl TableRef {
+    /// The name that qualifies its columns
+    pub fn name(&self) -> &Spanned<String> {
+        match self {
+            TableRef::Table { name, alias } => alias.as_ref().unwrap_or(name),
+            TableRef::Subquery { alias, .. } => alias,
+        }
+    }
+}
+
+impl Default for TableRef {
+    fn default() -> Self {
+        TableRef::Table {
+            name: Spanned::default(),
+            alias: None,
+        }
+    }
+}
+
+impl From<&str> for TableRef {
+    fn from(name: &str) -> Self {
+        TableRef::Table {
+            name: name.into(),
+            alias: None,
+        }
+    }
+}
+
+impl fmt::Display for TableRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableRef::Table { name, alias: None } => write!(f, "{}", Ident(name)),
+            TableRef::Table {
+                name,
+                alias: Some(alias),
+            } => write!(f, "{} AS {}", Ident(name), Ident(alias)),
+            TableRef::Subquery { query, alias } => write!(f, "({query}) AS {}", Ident(alias)),
+        }
+    }
+}
+
+impl<'a> Parse<'a> for TableRef {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        alt((
+            map(
+                pair(
+                    delimited(
+                        symbol(Symbol::LeftParen),
+                        SelectStatement::parse,
+                        cut(symbol(Symbol::RightParen)),
+                    ),
+                    cut(preceded(opt(keyword("as")), located(identifier)).context("Alias")),
+                ),
+                |(query, alias)| TableRef::Subquery {
+                    query: Box::new(query),
+                    alias,
+                },
+            ),
+            map(
+                pair(
+                    located(identifier),
+                    opt(preceded(opt(keyword("as")), located(identifier)).context("Alias")),
+                ),
+                |(name, alias)| TableRef::Table { name, alias },
+            ),
+        ))(input)
+    }
+}
+
+/// `[INNER] JOIN <table> ON <expr>`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+#[display(fmt = "JOIN {table} ON {on}")]
+pub struct Join {
+    pub table: TableRef,
+    pub on: Expression,
+}
+
+impl<'a> Parse<'a> for Join {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, (_, _, table, _, on)) = context(
+            "Join",
+            tuple((
+                opt(keyword("inner")),
+                keyword("join"),
+                cut(TableRef::parse.context("Join Table")),
+                cut(keyword("on")),
+                cut(Expression::parse),
+            )),
+        )(input)?;
+
+        Ok((rem, Join { table, on }))
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct OrderBy {
+    pub expr: Expression,
+    pub asc: bool,
+}
+
+/// Ascending is the default and is left out
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if !self.asc {
+            f.write_str(" DESC")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Parse<'a> for OrderBy {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            pair(
+                Expression::parse,
+                opt(alt((
+                    value(true, keyword("asc")),
+                    value(false, keyword("desc")),
+                ))),
+            ),
+            |(expr, asc)| OrderBy {
+                expr,
+                asc: asc.unwrap_or(true),
+            },
+        )(input)
+    }
+}
+
+/// `DISTINCT` keeps one of the rows with the same output, `DISTINCT ON
+/// (<expr>, ...)` the first of the rows with the same values of the
+/// expressions, in `ORDER BY` order
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Distinct {
+    All,
+    On(Vec<Expression>),
+}
+
+impl fmt::Display for Distinct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distinct::All => f.write_str("DISTINCT"),
+            Distinct::On(exprs) => write!(f, "DISTINCT ON ({})", CommaSeparated(exprs)),
+        }
+    }
+}
+
+impl<'a> Parse<'a> for Distinct {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            preceded(
+                keyword("distinct"),
+                opt(clause(
+                    "on",
+                    delimited(
+                        symbol(Symbol::LeftParen),
+                        comma_sep(Expression::parse),
+                        symbol(Symbol::RightParen),
+                    ),
+                )),
+            ),
+            |on| on.map_or(Distinct::All, Distinct::On),
+        )(input)
+    }
+}
+
+/// How a set operation combines the rows of two queries
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum SetOperator {
+    #[display(fmt = "UNION")]
+    Union,
+    #[display(fmt = "INTERSECT")]
+    Intersect,
+    #[display(fmt = "EXCEPT")]
+    Except,
+}
+
+impl<'a> Parse<'a> for SetOperator {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        alt((
+            value(SetOperator::Union, keyword("union")),
+            value(SetOperator::Intersect, keyword("intersect")),
+            value(SetOperator::Except, keyword("except")),
+        ))(input)
+    }
+}
+
+/// `UNION | INTERSECT | EXCEPT [ALL] <select>`, the select has no `WITH`,
+/// `ORDER BY` or `LIMIT` of its own
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct SetOperation {
+    pub op: SetOperator,
+    pub all: bool,
+    pub query: SelectStatement,
+}
+
+impl fmt::Display for SetOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.op)?;
+        if self.all {
+            f.write_str("ALL ")?;
+        }
+        write!(f, "{}", self.query)
+    }
+}
+
+impl<'a> Parse<'a> for SetOperation {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            tuple((
+                SetOperator::parse,
+                map(opt(keyword("all")), |all| all.is_some()),
+                cut(select_core),
+            )),
+            |(op, all, query)| SetOperation { op, all, query },
+        )(input)
+    }
+}
+
+/// `name [(columns)] AS (<select>)`, in a recursive `WITH` the select after
+/// the last `UNION` of the query can read the rows found so far by `name`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Cte {
+    pub name: Spanned<String>,
+    pub columns: Vec<Spanned<String>>,
+    pub query: Box<SelectStatement>,
+}
+
+impl fmt::Display for Cte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Ident(&self.name))?;
+        if !self.columns.is_empty() {
+            let columns: Vec<Ident> = self.columns.iter().map(|c| Ident(c)).collect();
+            write!(f, " ({})", CommaSeparated(&columns))?;
+        }
+        write!(f, " AS ({})", self.query)
+    }
+}
+
+impl<'a> Parse<'a> for Cte {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, (name, columns, _, query)) = context(
+            "Common Table Expression",
+            tuple((
+                located(identifier),
+                opt(delimited(
+                    symbol(Symbol::LeftParen),
+                    cut(comma_sep(located(identifier))),
+                    cut(symbol(Symbol::RightParen)),
+                )),
+                cut(keyword("as")),
+                cut(delimited(
+                    symbol(Symbol::LeftParen),
+                    SelectStatement::parse,
+                    symbol(Symbol::RightParen),
+                )),
+            )),
+        )(input)?;
+
+        Ok((
+            rem,
+            Cte {
+                name,
+                columns: columns.unwrap_or_default(),
+                query: Box::new(query),
+            },
+        ))
+    }
+}
+
+/// `WITH [RECURSIVE] <cte>, ...`, each one can read those before it
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct With {
+    pub recursive: bool,
+    pub ctes: Vec<Cte>,
+}
 
+impl fmt::Display for With {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WITH ")?;
+        if self.recursive {
+            f.write_str("RECURSIVE ")?;
+        }
+        write!(f, "{}", CommaSeparated(&self.ctes))
+    }
+}
+
+impl<'a> Parse<'a> for With {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            clause(
+                "with",
+                pair(
+                    map(opt(keyword("recursive")), |recursive| recursive.is_some()),
+                    comma_sep(Cte::parse),
+                ),
+            ),
+            |(recursive, ctes)| With { recursive, ctes },
+        )(input)
+    }
+}
+
+/// A select, combined with the ones in `compound` when it has set
+/// operations, in which case `ORDER BY` and `LIMIT` apply to the combined
+/// rows
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SelectStatement {
-    pub table: String,
-    pub fields: Vec<String>,
+    pub with: Option<Box<With>>,
+    pub distinct: Option<Distinct>,
+    pub table: TableRef,
+    pub fields: Vec<SelectItem>,
+    pub joins: Vec<Join>,
+    pub filter: Option<Expression>,
+    pub group_by: Vec<Expression>,
+    pub compound: Vec<SetOperation>,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(with) = &self.with {
+            write!(f, "{with} ")?;
+        }
+        f.write_str("SELECT ")?;
+        if let Some(distinct) = &self.distinct {
+            write!(f, "{distinct} ")?;
+        }
+        write!(f, "{} FROM {}", CommaSeparated(&self.fields), self.table)?;
+        for join in &self.joins {
+            write!(f, " {join}")?;
+        }
+        if let Some(filter) = &self.filter {
+            write!(f, " WHERE {filter}")?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY {}", CommaSeparated(&self.group_by))?;
+        }
+        for operation in &self.compound {
+            write!(f, " {operation}")?;
+        }
+        if !self.order_by.is_empty() {
+            write!(f, " ORDER BY {}", CommaSeparated(&self.order_by))?;
+        }
+        // the grammar only has an offset after a limit
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+            if let Some(offset) = self.offset {
+                write!(f, " OFFSET {offset}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A count of rows, as in `LIMIT` or a window frame
+pub(crate) fn count(input: Tokens<'_>) -> ParseResult<'_, usize> {
+    token("a row count", |token| match token {
+        Token::Number(n) => n.to_string().parse::<usize>().ok(),
+        _ => None,
+    })(input)
+}
+
+/// `<keyword> <parser>`
+fn clause<'a, O>(
+    name: &'static str,
+    parser: impl Parser<Tokens<'a>, O, ParseError<'a>>,
+) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, O> {
+    preceded(keyword(name), cut(parser))
+}
+
+/// `SELECT` up to `GROUP BY`, what a set operation combines
+fn select_core(input: Tokens<'_>) -> ParseResult<'_, SelectStatement> {
+    let (rem, (_, distinct, fields, _, table, joins, filter, group_by)) = context(
+        "Select Statement",
+        tuple((
+            keyword("select"),
+            opt(Distinct::parse),
+            comma_sep(SelectItem::parse).context("Select Columns"),
+            keyword("from"),
+            TableRef::parse.context("From Table"),
+            many0(Join::parse),
+            opt(clause("where", Expression::parse.context("Where"))),
+            opt(preceded(
+                keyword("group"),
+                clause("by", comma_sep(Expression::parse).context("Group By")),
+            )),
+        )),
+    )(input)?;
+
+    Ok((
+        rem,
+        SelectStatement {
+            distinct,
+            table,
+            fields,
+            joins,
+            filter,
+            group_by: group_by.unwrap_or_default(),
+            ..Default::default()
+        },
+    ))
 }
 
 impl<'a> Parse<'a> for SelectStatement {
-    fn parse(input: crate::parse::RawSpan<'a>) -> ParseResult<'a, Self> {
-        let (rem, (_, _, fields, _, _, _, table)) = context(
-            "Select Statement",
-            tuple((
-                tag_no_case("select"),
-                multispace1,
-                comma_sep(identifier).context("Select Columns"),
-                multispace1,
-                tag_no_case("from"),
-                multispace1,
-                identifier.context("From Table"),
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, (with, core, compound, order_by, limit)) = tuple((
+            opt(map(With::parse, Box::new)),
+            select_core,
+            many0(SetOperation::parse),
+            opt(preceded(
+                keyword("order"),
+                clause("by", comma_sep(OrderBy::parse).context("Order By")),
             )),
-        )(input)?;
+            opt(pair(
+                clause("limit", count.context("Limit")),
+                opt(clause("offset", count.context("Offset"))),
+            )),
+        ))(input)?;
 
-        Ok((rem, SelectStatement { fields, table }))
+        let (limit, offset) = limit.map_or((None, None), |(limit, offset)| (Some(limit), offset));
+
+        Ok((
+            rem,
+            SelectStatement {
+                with,
+                compound,
+                order_by: order_by.unwrap_or_default(),
+                limit,
+                offset,
+                ..core
+            },
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
     use super::*;
+    use crate::{expression::BinaryOperator, value::Value};
+
+    fn column(name: &str) -> Expression {
+        Expression::Column {
+            table: None,
+            name: name.into(),
+        }
+    }
+
+    fn field(name: &str) -> SelectItem {
+        SelectItem::Expression {
+            expr: column(name),
+            alias: None,
+        }
+    }
 
     #[test]
     fn test_select() {
         let expected = SelectStatement {
             table: "t1".into(),
-            fields: vec!["foo".into(), "bar".into()],
+            fields: vec![field("foo"), field("bar")],
+            ..Default::default()
         };
 
         let query = SelectStatement::parse_from_raw("select foo, bar from t1;")
@@ -47,5 +553,109 @@ mod tests {
             .1;
 
         assert_eq!(expected, query);
+
+        for spacing in [
+            "select foo,bar from t1",
+            "SELECT foo ,bar\n\tFROM t1",
+            "select/* cols */foo , bar -- all of them\nfrom t1",
+        ] {
+            let query = SelectStatement::parse_format_error(spacing).unwrap();
+            assert_eq!(expected, query, "{spacing}");
+        }
+    }
+
+    #[test]
+    fn test_select_clauses() {
+        let expected = SelectStatement {
+            with: None,
+            distinct: None,
+            table: "t1".into(),
+            fields: vec![
+                field("foo"),
+                SelectItem::Expression {
+                    expr: Expression::Function {
+                        name: "count".into(),
+                        args: vec![Expression::Wildcard],
+                    },
+                    alias: Some("total".into()),
+                },
+            ],
+            joins: vec![Join {
+                table: "t2".into(),
+                on: Expression::binary(
+                    Expression::Column {
+                        table: Some("t1".into()),
+                        name: "id".into(),
+                    },
+                    BinaryOperator::Eq,
+                    Expression::Column {
+                        table: Some("t2".into()),
+                        name: "id".into(),
+                    },
+                ),
+            }],
+            filter: Some(Expression::binary(
+                column("bar"),
+                BinaryOperator::Gt,
+                Expression::Literal(Value::Number(BigDecimal::from_str("1").unwrap()).into()),
+            )),
+            group_by: vec![column("foo")],
+            compound: vec![],
+            order_by: vec![OrderBy {
+                expr: column("total"),
+                asc: false,
+            }],
+            limit: Some(10),
+            offset: Some(5),
+        };
+
+        let query = SelectStatement::parse_from_raw(
+            "select foo, count(*) as total from t1 join t2 on t1.id = t2.id \
+             where bar > 1 group by foo order by total desc limit 10 offset 5",
+        )
+        .unwrap()
+        .1;
+
+        assert_eq!(expected, query);
+    }
+
+    #[test]
+    fn test_table_alias() {
+        let table = |name: &str, alias: &str| TableRef::Table {
+            name: name.into(),
+            alias: Some(alias.into()),
+        };
+
+        for sql in [
+            "select a from t as t2 join u v on t2.a = v.a where v.b = 1",
+            "select a from t t2 inner join u as v on t2.a = v.a where v.b = 1",
+        ] {
+            let select = SelectStatement::parse_format_error(sql).unwrap();
+            assert_eq!(select.table, table("t", "t2"), "{sql}");
+            assert_eq!(select.table.name().node, "t2", "{sql}");
+            assert_eq!(select.joins[0].table, table("u", "v"), "{sql}");
+            assert!(select.filter.is_some(), "{sql}");
+        }
+        assert_eq!(
+            table("t", "select").to_string(),
+            "t AS \"select\"",
+            "a keyword alias is quoted"
+        );
+    }
+
+    #[test]
+    fn test_reserved_keyword() {
+        let error = SelectStatement::parse_format_error("select from from t").unwrap_err();
+
+        assert!(format!("{error:?}").contains("ReservedKeyword(\"from\")"));
+
+        let select = SelectStatement::parse_format_error("select \"from\" from t").unwrap();
+        assert_eq!(
+            select.fields,
+            vec![SelectItem::Expression {
+                expr: column("from"),
+                alias: None,
+            }]
+        );
     }
 }
@@ -0,0 +1,55 @@
+use derive_more::Display;
+use nom::{branch::alt, error::context, sequence::tuple};
+use nom_supreme::ParserExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parse::{identifier, keyword, symbol, Parse, ParseResult},
+    token::{Ident, Symbol, Tokens},
+    value::Value,
+};
+
+/// `SET <name> = <value>` changes a setting of the current connection
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+#[display(fmt = "SET {} = {}", "Ident(name)", "value.to_sql()")]
+pub struct SetStatement {
+    pub name: String,
+    pub value: Value,
+}
+
+impl<'a> Parse<'a> for SetStatement {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, (_, name, _, value)) = context(
+            "Set Statement",
+            tuple((
+                keyword("set"),
+                identifier.context("Setting Name"),
+                alt((symbol(Symbol::Eq), keyword("to"))),
+                Value::parse.context("Setting Value"),
+            )),
+        )(input)?;
+
+        Ok((rem, SetStatement { name, value }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let expected = SetStatement {
+            name: "max_parallelism".into(),
+            value: Value::Number(BigDecimal::from(4)),
+        };
+
+        let query = SetStatement::parse_from_raw("SET max_parallelism = 4")
+            .unwrap()
+            .1;
+
+        assert_eq!(query, expected);
+    }
+}
@@ -1,11 +1,31 @@
 use miette::Diagnostic;
-use nom_supreme::error::{BaseErrorKind, ErrorTree, GenericErrorTree, StackContext};
+use nom_supreme::error::{BaseErrorKind, ErrorTree, Expectation, GenericErrorTree, StackContext};
 use thiserror::Error;
 
-use crate::parse::RawSpan;
+use crate::token::{Token, Tokens};
 
 // Define custom error
-pub type ParseError<'a> = ErrorTree<RawSpan<'a>>;
+pub type ParseError<'a> = ErrorTree<Tokens<'a>>;
+
+/// The next token is not one the grammar accepts here
+#[derive(Error, Debug)]
+#[error("expected {0}")]
+pub struct Expected(pub &'static str);
+
+/// Text the lexer could not read as a token
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct InvalidToken(pub &'static str);
+
+/// An unquoted name that is a reserved keyword
+#[derive(Error, Debug)]
+#[error("{0} is a reserved keyword, quote it to use it as a name")]
+pub struct ReservedKeyword(pub String);
+
+/// A quoted name with nothing between the quotes
+#[derive(Error, Debug)]
+#[error("quoted names can not be empty")]
+pub struct EmptyIdentifier;
 
 #[derive(Error, Debug, Diagnostic)]
 #[error("Parse Error")]
@@ -18,10 +38,21 @@ pub struct FormattedError<'b> {
 
     kind: BaseErrorKind<&'b str, Box<dyn std::error::Error + Send + Sync + 'static>>,
 
+    #[help]
+    help: Option<String>,
+
     #[related]
     others: Vec<FormattedErrorContext<'b>>,
 }
 
+/// Every statement of a script that failed to parse, in order
+#[derive(Error, Debug, Diagnostic)]
+#[error("The script has {} parse errors", .errors.len())]
+pub struct ParseErrors<'b> {
+    #[related]
+    pub errors: Vec<FormattedError<'b>>,
+}
+
 #[derive(Error, Debug, Diagnostic)]
 #[error("Parse Error Context")]
 pub struct FormattedErrorContext<'b> {
@@ -34,15 +65,63 @@ pub struct FormattedErrorContext<'b> {
     context: StackContext<&'b str>,
 }
 
+/// Whether the error says what is wrong with the token found rather than
+/// only what was expected instead, those are preferred among alternatives
+fn is_specific(kind: &BaseErrorKind<&str, Box<dyn std::error::Error + Send + Sync>>) -> bool {
+    match kind {
+        BaseErrorKind::External(e) => !e.is::<Expected>(),
+        _ => false,
+    }
+}
+
+/// Edits to turn `a` into `b`, inserting, removing or replacing a character
+/// or swapping two adjacent ones
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // distances between prefixes of `a` and `b`, one row per prefix of `a`
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i];
+        for j in 1..=b.len() {
+            let mut distance = (rows[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                .min(rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            row.push(distance);
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// A keyword was expected and the name found looks like a misspelling of it
+fn suggestion(
+    location: &Tokens<'_>,
+    kind: &BaseErrorKind<&str, Box<dyn std::error::Error + Send + Sync>>,
+) -> Option<String> {
+    let (BaseErrorKind::Expected(Expectation::Tag(keyword)), Some(Token::Identifier(word))) =
+        (kind, location.first().map(|next| &next.token))
+    else {
+        return None;
+    };
+    let allowed = if keyword.len() <= 4 { 1 } else { 2 };
+    (edit_distance(word, keyword) <= allowed)
+        .then(|| format!("did you mean {}?", keyword.to_uppercase()))
+}
+
 pub fn format_parse_error<'a>(input: &'a str, e: ParseError<'a>) -> FormattedError<'a> {
     match e {
         // normal error like unexpected character
         GenericErrorTree::Base { location, kind } => {
-            let offset = location.location_offset().into();
+            let offset = location.offset().into();
+            let len = location.first().map_or(0, |token| token.span.len).into();
 
             FormattedError {
                 src: input,
-                span: miette::SourceSpan::new(offset, 0.into()),
+                span: miette::SourceSpan::new(offset, len),
+                help: suggestion(&location, &kind),
                 kind,
                 others: Vec::new(),
             }
@@ -52,7 +131,7 @@ pub fn format_parse_error<'a>(input: &'a str, e: ParseError<'a>) -> FormattedErr
             let mut contexts: Vec<FormattedErrorContext> = contexts
                 .into_iter()
                 .map(|(location, context)| {
-                    let offset = location.location_offset().into();
+                    let offset = location.offset().into();
                     FormattedErrorContext {
                         src: input,
                         span: miette::SourceSpan::new(offset, 0.into()),
@@ -66,7 +145,28 @@ pub fn format_parse_error<'a>(input: &'a str, e: ParseError<'a>) -> FormattedErr
         GenericErrorTree::Alt(alt_errors) => alt_errors
             .into_iter()
             .map(|e| format_parse_error(input, e))
-            .max_by_key(|formated| formated.others.len())
+            .max_by_key(|formated| {
+                (
+                    formated.others.len(),
+                    is_specific(&formated.kind),
+                    formated.help.is_some(),
+                )
+            })
             .unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("selct", "select"), 1);
+        assert_eq!(edit_distance("form", "from"), 1);
+        assert_eq!(edit_distance("gruop", "group"), 1);
+        assert_eq!(edit_distance("insret", "insert"), 1);
+        assert_eq!(edit_distance("", "set"), 3);
+        assert_eq!(edit_distance("limit", "offset"), 5);
+    }
+}
@@ -0,0 +1,1007 @@
+use std::fmt;
+
+use derive_more::Display;
+use nom::{
+    branch::alt,
+    combinator::{cut, map, opt, peek, success, value},
+    error::context,
+    multi::many1,
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::count,
+    parse::{
+        comma_sep, identifier, keyword, located, spanned, symbol, token, CommaSeparated, Parse,
+        ParseResult, Spanned,
+    },
+    token::{Ident, Symbol, Token, Tokens},
+    value::{parse_literal, Value},
+    OrderBy, SelectStatement, SqlTypeInfo,
+};
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum BinaryOperator {
+    #[display(fmt = "OR")]
+    Or,
+    #[display(fmt = "AND")]
+    And,
+    #[display(fmt = "=")]
+    Eq,
+    #[display(fmt = "<>")]
+    NotEq,
+    #[display(fmt = "<")]
+    Lt,
+    #[display(fmt = "<=")]
+    LtEq,
+    #[display(fmt = ">")]
+    Gt,
+    #[display(fmt = ">=")]
+    GtEq,
+    #[display(fmt = "+")]
+    Plus,
+    #[display(fmt = "-")]
+    Minus,
+    #[display(fmt = "*")]
+    Multiply,
+    #[display(fmt = "/")]
+    Divide,
+    #[display(fmt = "%")]
+    Modulo,
+    #[display(fmt = "||")]
+    Concat,
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum UnaryOperator {
+    #[display(fmt = "NOT")]
+    Not,
+    #[display(fmt = "-")]
+    Minus,
+}
+
+/// Placeholder for a value supplied when a prepared statement is executed
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum Parameter {
+    /// `?`, numbered by order of appearance in the statement
+    #[display(fmt = "?")]
+    Anonymous,
+    /// `$n`, numbered from 1
+    #[display(fmt = "${_0}")]
+    Positional(usize),
+}
+
+impl<'a> Parse<'a> for Parameter {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        token("a parameter", |token| match token {
+            Token::Parameter(parameter) => Some(*parameter),
+            _ => None,
+        })(input)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Expression {
+    Literal(Spanned<Value>),
+    Parameter(Spanned<Parameter>),
+    Column {
+        table: Option<Spanned<String>>,
+        name: Spanned<String>,
+    },
+    Binary {
+        left: Box<Expression>,
+        op: BinaryOperator,
+        right: Box<Expression>,
+    },
+    Unary {
+        op: UnaryOperator,
+        operand: Box<Expression>,
+    },
+    /// Function call, the name is stored lowercased
+    Function {
+        name: Spanned<String>,
+        args: Vec<Expression>,
+    },
+    /// `*` as a function argument, as in `count(*)`
+    Wildcard,
+    /// `(SELECT ...)` used as a value, it has to return a single column and
+    /// at most one row
+    Subquery(Box<SelectStatement>),
+    /// `EXISTS (SELECT ...)`
+    Exists(Box<SelectStatement>),
+    /// `expr [NOT] IN (SELECT ...)`
+    InSubquery {
+        expr: Box<Expression>,
+        subquery: Box<SelectStatement>,
+        negated: bool,
+    },
+    /// `expr [NOT] IN (value, ...)`
+    InList {
+        expr: Box<Expression>,
+        list: Vec<Expression>,
+        negated: bool,
+    },
+    /// `expr [NOT] BETWEEN low AND high`, both ends included
+    Between {
+        expr: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+        negated: bool,
+    },
+    /// `expr [NOT] LIKE | ILIKE pattern [ESCAPE escape]`, where `%` matches
+    /// any text and `_` a single character
+    Like {
+        expr: Box<Expression>,
+        pattern: Box<Expression>,
+        escape: Option<Box<Expression>>,
+        negated: bool,
+        case_insensitive: bool,
+    },
+    /// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`, the result of the
+    /// first `WHEN` that holds, or that equals the operand when there is one
+    Case {
+        operand: Option<Box<Expression>>,
+        branches: Vec<(Expression, Expression)>,
+        else_result: Option<Box<Expression>>,
+    },
+    /// `CAST(expr AS type)` or `expr::type`
+    Cast {
+        expr: Box<Expression>,
+        type_info: SqlTypeInfo,
+    },
+    /// `name(args) OVER (...)`, a function computed over the rows of the
+    /// window of each row, the name is stored lowercased
+    Window {
+        name: Spanned<String>,
+        args: Vec<Expression>,
+        over: Box<Window>,
+    },
+}
+
+/// `PARTITION BY <expr>, ... ORDER BY <order>, ... [<frame>]`, the rows a
+/// window function reads for each row
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    pub partition_by: Vec<Expression>,
+    pub order_by: Vec<OrderBy>,
+    /// `None` for the default, from the first row of the partition to the
+    /// last peer of the current row
+    pub frame: Option<WindowFrame>,
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut clauses = Vec::new();
+        if !self.partition_by.is_empty() {
+            clauses.push(format!(
+                "PARTITION BY {}",
+                CommaSeparated(&self.partition_by)
+            ));
+        }
+        if !self.order_by.is_empty() {
+            clauses.push(format!("ORDER BY {}", CommaSeparated(&self.order_by)));
+        }
+        if let Some(frame) = &self.frame {
+            clauses.push(frame.to_string());
+        }
+        f.write_str(&clauses.join(" "))
+    }
+}
+
+/// Whether the bounds of a frame count rows or order by values
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum FrameUnits {
+    #[display(fmt = "ROWS")]
+    Rows,
+    /// Rows with the same `ORDER BY` values, its peers, are all in the frame
+    /// of a row or none is
+    #[display(fmt = "RANGE")]
+    Range,
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+pub enum FrameBound {
+    #[display(fmt = "UNBOUNDED PRECEDING")]
+    UnboundedPreceding,
+    #[display(fmt = "{_0} PRECEDING")]
+    Preceding(usize),
+    #[display(fmt = "CURRENT ROW")]
+    CurrentRow,
+    #[display(fmt = "{_0} FOLLOWING")]
+    Following(usize),
+    #[display(fmt = "UNBOUNDED FOLLOWING")]
+    UnboundedFollowing,
+}
+
+/// `ROWS | RANGE BETWEEN <start> AND <end>`, a frame written with only its
+/// start ends at the current row
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
+#[display(fmt = "{units} BETWEEN {start} AND {end}")]
+pub struct WindowFrame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+impl<'a> Parse<'a> for FrameBound {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        alt((
+            preceded(
+                keyword("unbounded"),
+                cut(alt((
+                    value(FrameBound::UnboundedPreceding, keyword("preceding")),
+                    value(FrameBound::UnboundedFollowing, keyword("following")),
+                ))),
+            ),
+            value(
+                FrameBound::CurrentRow,
+                pair(keyword("current"), cut(keyword("row"))),
+            ),
+            map(
+                pair(
+                    count,
+                    cut(alt((
+                        value(true, keyword("preceding")),
+                        value(false, keyword("following")),
+                    ))),
+                ),
+                |(n, preceding)| match preceding {
+                    true => FrameBound::Preceding(n),
+                    false => FrameBound::Following(n),
+                },
+            ),
+        ))(input)
+    }
+}
+
+impl<'a> Parse<'a> for WindowFrame {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, units) = alt((
+            value(FrameUnits::Rows, keyword("rows")),
+            value(FrameUnits::Range, keyword("range")),
+        ))(input)?;
+        cut(alt((
+            map(
+                preceded(
+                    keyword("between"),
+                    separated_pair(FrameBound::parse, keyword("and"), FrameBound::parse),
+                ),
+                move |(start, end)| WindowFrame { units, start, end },
+            ),
+            map(FrameBound::parse, move |start| WindowFrame {
+                units,
+                start,
+                end: FrameBound::CurrentRow,
+            }),
+        )))(rem)
+    }
+}
+
+impl<'a> Parse<'a> for Window {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        map(
+            tuple((
+                opt(preceded(
+                    keyword("partition"),
+                    cut(preceded(keyword("by"), comma_sep(Expression::parse))),
+                )),
+                opt(preceded(
+                    keyword("order"),
+                    cut(preceded(keyword("by"), comma_sep(OrderBy::parse))),
+                )),
+                opt(WindowFrame::parse),
+            )),
+            |(partition_by, order_by, frame)| Window {
+                partition_by: partition_by.unwrap_or_default(),
+                order_by: order_by.unwrap_or_default(),
+                frame,
+            },
+        )(input)
+    }
+}
+
+impl Expression {
+    pub fn binary(left: Expression, op: BinaryOperator, right: Expression) -> Self {
+        Self::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        }
+    }
+}
+
+/// Precedence of `NOT`
+const NOT: u8 = 3;
+/// Precedence of `IN`, `BETWEEN` and `LIKE`, between comparisons and
+/// concatenation
+const IN: u8 = 5;
+/// Precedence of unary minus
+const NEGATE: u8 = 9;
+/// Precedence of an expression that needs no parentheses anywhere
+const ATOM: u8 = 10;
+
+impl BinaryOperator {
+    /// How tightly the operator binds, higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Lt
+            | BinaryOperator::LtEq
+            | BinaryOperator::Gt
+            | BinaryOperator::GtEq => 4,
+            BinaryOperator::Concat => 6,
+            BinaryOperator::Plus | BinaryOperator::Minus => 7,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 8,
+        }
+    }
+}
+
+impl Expression {
+    /// Precedence of the expression's outermost operator, following the
+    /// levels of the grammar below
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary { op, .. } => op.precedence(),
+            Expression::Unary {
+                op: UnaryOperator::Not,
+                ..
+            } => NOT,
+            Expression::Unary {
+                op: UnaryOperator::Minus,
+                ..
+            } => NEGATE,
+            Expression::InSubquery { .. }
+            | Expression::InList { .. }
+            | Expression::Between { .. }
+            | Expression::Like { .. } => IN,
+            _ => ATOM,
+        }
+    }
+}
+
+/// `expr`, between parentheses when it binds looser than `min`
+struct Operand<'a>(&'a Expression, u8);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Operand(expr, min) = self;
+        if expr.precedence() < *min {
+            write!(f, "({expr})")
+        } else {
+            write!(f, "{expr}")
+        }
+    }
+}
+
+/// Prints sql that parses back into the same expression, with only the
+/// parentheses precedence needs
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Literal(value) => f.write_str(&value.to_sql()),
+            Expression::Parameter(parameter) => write!(f, "{parameter}"),
+            Expression::Column { table: None, name } => write!(f, "{}", Ident(name)),
+            Expression::Column {
+                table: Some(table),
+                name,
+            } => write!(f, "{}.{}", Ident(table), Ident(name)),
+            // operators are left associative, a right operand of the same
+            // level needs parentheses
+            Expression::Binary { left, op, right } => write!(
+                f,
+                "{} {op} {}",
+                Operand(left, op.precedence()),
+                Operand(right, op.precedence() + 1)
+            ),
+            Expression::Unary {
+                op: UnaryOperator::Not,
+                operand,
+            } => write!(f, "NOT {}", Operand(operand, NOT)),
+            Expression::Unary {
+                op: UnaryOperator::Minus,
+                operand,
+            } => {
+                let operand = Operand(operand, NEGATE).to_string();
+                // `--` would start a comment
+                let space = if operand.starts_with('-') { " " } else { "" };
+                write!(f, "-{space}{operand}")
+            }
+            Expression::Function { name, args } => {
+                write!(f, "{}({})", Ident(name), CommaSeparated(args))
+            }
+            Expression::Wildcard => f.write_str("*"),
+            Expression::Subquery(subquery) => write!(f, "({subquery})"),
+            Expression::Exists(subquery) => write!(f, "EXISTS ({subquery})"),
+            // `IN` does not chain, its operand has to bind tighter
+            Expression::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{} {not}IN ({subquery})", Operand(expr, IN + 1))
+            }
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(
+                    f,
+                    "{} {not}IN ({})",
+                    Operand(expr, IN + 1),
+                    CommaSeparated(list)
+                )
+            }
+            Expression::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(
+                    f,
+                    "{} {not}BETWEEN {} AND {}",
+                    Operand(expr, IN + 1),
+                    Operand(low, IN + 1),
+                    Operand(high, IN + 1)
+                )
+            }
+            Expression::Like {
+                expr,
+                pattern,
+                escape,
+                negated,
+                case_insensitive,
+            } => {
+                let not = if *negated { "NOT " } else { "" };
+                let like = if *case_insensitive { "ILIKE" } else { "LIKE" };
+                write!(
+                    f,
+                    "{} {not}{like} {}",
+                    Operand(expr, IN + 1),
+                    Operand(pattern, IN + 1)
+                )?;
+                if let Some(escape) = escape {
+                    write!(f, " ESCAPE {}", Operand(escape, IN + 1))?;
+                }
+                Ok(())
+            }
+            Expression::Case {
+                operand,
+                branches,
+                else_result,
+            } => {
+                f.write_str("CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {operand}")?;
+                }
+                for (when, then) in branches {
+                    write!(f, " WHEN {when} THEN {then}")?;
+                }
+                if let Some(else_result) = else_result {
+                    write!(f, " ELSE {else_result}")?;
+                }
+                f.write_str(" END")
+            }
+            Expression::Cast { expr, type_info } => write!(f, "CAST({expr} AS {type_info})"),
+            Expression::Window { name, args, over } => {
+                write!(f, "{}({}) OVER ({over})", Ident(name), CommaSeparated(args))
+            }
+        }
+    }
+}
+
+/// Parse a left associative chain of `operand (operator operand)*`
+fn binary_level<'a>(
+    input: Tokens<'a>,
+    operand: fn(Tokens<'a>) -> ParseResult<'a, Expression>,
+    operator: fn(Tokens<'a>) -> ParseResult<'a, BinaryOperator>,
+) -> ParseResult<'a, Expression> {
+    let (mut rem, mut left) = operand(input)?;
+    loop {
+        match pair(operator, operand)(rem.clone()) {
+            Ok((next, (op, right))) => {
+                rem = next;
+                left = Expression::binary(left, op, right);
+            }
+            Err(nom::Err::Error(_)) => return Ok((rem, left)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn or_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    binary_level(input, and_expression, |i| {
+        value(BinaryOperator::Or, keyword("or"))(i)
+    })
+}
+
+fn and_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    binary_level(input, not_expression, |i| {
+        value(BinaryOperator::And, keyword("and"))(i)
+    })
+}
+
+fn not_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    alt((
+        map(preceded(keyword("not"), not_expression), |operand| {
+            Expression::Unary {
+                op: UnaryOperator::Not,
+                operand: Box::new(operand),
+            }
+        }),
+        comparison_expression,
+    ))(input)
+}
+
+fn comparison_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    binary_level(input, in_expression, |i| {
+        token("a comparison operator", |token| match token {
+            Token::Symbol(Symbol::Eq) => Some(BinaryOperator::Eq),
+            Token::Symbol(Symbol::NotEq) => Some(BinaryOperator::NotEq),
+            Token::Symbol(Symbol::Lt) => Some(BinaryOperator::Lt),
+            Token::Symbol(Symbol::LtEq) => Some(BinaryOperator::LtEq),
+            Token::Symbol(Symbol::Gt) => Some(BinaryOperator::Gt),
+            Token::Symbol(Symbol::GtEq) => Some(BinaryOperator::GtEq),
+            _ => None,
+        })(i)
+    })
+}
+
+/// The predicates that follow their operand
+#[derive(Clone, Copy)]
+enum Predicate {
+    In,
+    Between,
+    Like { case_insensitive: bool },
+}
+
+/// `expr [NOT] IN (...)`, `expr [NOT] BETWEEN low AND high`,
+/// `expr [NOT] LIKE | ILIKE pattern [ESCAPE escape]`, or just `expr`
+fn in_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    let (rem, expr) = concat_expression(input)?;
+    predicate(rem, expr)
+}
+
+/// The predicate after `expr`, if any. Kept out of [`in_expression`] so the
+/// parsers it builds do not take stack space in every level of nesting
+fn predicate(input: Tokens<'_>, expr: Expression) -> ParseResult<'_, Expression> {
+    let (rem, predicate) = opt(pair(
+        map(opt(keyword("not")), |not| not.is_some()),
+        alt((
+            value(Predicate::In, keyword("in")),
+            value(Predicate::Between, keyword("between")),
+            value(
+                Predicate::Like {
+                    case_insensitive: false,
+                },
+                keyword("like"),
+            ),
+            value(
+                Predicate::Like {
+                    case_insensitive: true,
+                },
+                keyword("ilike"),
+            ),
+        )),
+    ))(input)?;
+    let Some((negated, predicate)) = predicate else {
+        return Ok((rem, expr));
+    };
+
+    let expr = Box::new(expr);
+    match predicate {
+        Predicate::In => cut(alt((
+            map(subquery, |subquery| Expression::InSubquery {
+                expr: expr.clone(),
+                subquery: Box::new(subquery),
+                negated,
+            }),
+            map(
+                delimited(
+                    symbol(Symbol::LeftParen),
+                    comma_sep(Expression::parse),
+                    symbol(Symbol::RightParen),
+                ),
+                |list| Expression::InList {
+                    expr: expr.clone(),
+                    list,
+                    negated,
+                },
+            ),
+        )))(rem),
+        Predicate::Between => map(
+            cut(separated_pair(
+                concat_expression,
+                keyword("and"),
+                concat_expression,
+            )),
+            |(low, high)| Expression::Between {
+                expr: expr.clone(),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            },
+        )(rem),
+        Predicate::Like { case_insensitive } => map(
+            cut(pair(
+                concat_expression,
+                opt(preceded(keyword("escape"), cut(concat_expression))),
+            )),
+            |(pattern, escape)| Expression::Like {
+                expr: expr.clone(),
+                pattern: Box::new(pattern),
+                escape: escape.map(Box::new),
+                negated,
+                case_insensitive,
+            },
+        )(rem),
+    }
+}
+
+/// `(SELECT ...)`
+fn subquery(input: Tokens<'_>) -> ParseResult<'_, SelectStatement> {
+    delimited(
+        symbol(Symbol::LeftParen),
+        preceded(
+            peek(alt((keyword("select"), keyword("with")))),
+            cut(SelectStatement::parse),
+        ),
+        cut(symbol(Symbol::RightParen)),
+    )(input)
+}
+
+fn concat_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    binary_level(input, additive_expression, |i| {
+        value(BinaryOperator::Concat, symbol(Symbol::Concat))(i)
+    })
+}
+
+fn additive_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    binary_level(input, multiplicative_expression, |i| {
+        token("an operator", |token| match token {
+            Token::Symbol(Symbol::Plus) => Some(BinaryOperator::Plus),
+            Token::Symbol(Symbol::Minus) => Some(BinaryOperator::Minus),
+            _ => None,
+        })(i)
+    })
+}
+
+fn multiplicative_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    binary_level(input, unary_expression, |i| {
+        token("an operator", |token| match token {
+            Token::Symbol(Symbol::Star) => Some(BinaryOperator::Multiply),
+            Token::Symbol(Symbol::Slash) => Some(BinaryOperator::Divide),
+            Token::Symbol(Symbol::Percent) => Some(BinaryOperator::Modulo),
+            _ => None,
+        })(i)
+    })
+}
+
+fn unary_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    alt((
+        map(
+            spanned(preceded(symbol(Symbol::Minus), unary_expression)),
+            |(operand, span)| match operand {
+                // a negative number is a literal of its own, minus included
+                Expression::Literal(Spanned {
+                    node: Value::Number(n),
+                    ..
+                }) => Expression::Literal(Spanned::new(Value::Number(-n), span)),
+                operand => Expression::Unary {
+                    op: UnaryOperator::Minus,
+                    operand: Box::new(operand),
+                },
+            },
+        ),
+        cast_operator,
+    ))(input)
+}
+
+/// `expr::type`, which binds tighter than any other operator
+fn cast_operator(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    let (rem, expr) = primary_expression(input)?;
+    cast_types(rem, expr)
+}
+
+fn cast_types(mut rem: Tokens<'_>, mut expr: Expression) -> ParseResult<'_, Expression> {
+    while let (next, Some(type_info)) = opt(preceded(
+        symbol(Symbol::DoubleColon),
+        cut(SqlTypeInfo::parse),
+    ))(rem.clone())?
+    {
+        rem = next;
+        expr = Expression::Cast {
+            expr: Box::new(expr),
+            type_info,
+        };
+    }
+    Ok((rem, expr))
+}
+
+/// `CAST(expr AS type)`
+fn cast(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    map(
+        preceded(
+            keyword("cast"),
+            cut(delimited(
+                symbol(Symbol::LeftParen),
+                separated_pair(Expression::parse, keyword("as"), SqlTypeInfo::parse),
+                symbol(Symbol::RightParen),
+            )),
+        ),
+        |(expr, type_info)| Expression::Cast {
+            expr: Box::new(expr),
+            type_info,
+        },
+    )(input)
+}
+
+/// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`
+fn case(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    map(
+        preceded(
+            keyword("case"),
+            cut(tuple((
+                opt(Expression::parse),
+                many1(pair(
+                    preceded(keyword("when"), Expression::parse),
+                    preceded(keyword("then"), Expression::parse),
+                )),
+                opt(preceded(keyword("else"), Expression::parse)),
+                keyword("end"),
+            ))),
+        ),
+        |(operand, branches, else_result, _)| Expression::Case {
+            operand: operand.map(Box::new),
+            branches,
+            else_result: else_result.map(Box::new),
+        },
+    )(input)
+}
+
+/// `EXTRACT(field FROM expr)`, a call of `extract` with the name of the
+/// field as its first argument
+fn extract(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    map(
+        pair(
+            located(keyword("extract")),
+            delimited(
+                symbol(Symbol::LeftParen),
+                separated_pair(located(identifier), keyword("from"), cut(Expression::parse)),
+                cut(symbol(Symbol::RightParen)),
+            ),
+        ),
+        |(name, (field, expr))| Expression::Function {
+            name: Spanned::new("extract".to_string(), name.span),
+            args: vec![
+                Expression::Literal(Spanned::new(
+                    Value::String(field.to_lowercase()),
+                    field.span,
+                )),
+                expr,
+            ],
+        },
+    )(input)
+}
+
+fn function_args(input: Tokens<'_>) -> ParseResult<'_, Vec<Expression>> {
+    delimited(
+        symbol(Symbol::LeftParen),
+        alt((
+            map(symbol(Symbol::Star), |_| vec![Expression::Wildcard]),
+            comma_sep(Expression::parse),
+            success(Vec::new()),
+        )),
+        symbol(Symbol::RightParen),
+    )(input)
+}
+
+/// `OVER (<window>)`
+fn over(input: Tokens<'_>) -> ParseResult<'_, Window> {
+    preceded(
+        keyword("over"),
+        cut(delimited(
+            symbol(Symbol::LeftParen),
+            Window::parse,
+            symbol(Symbol::RightParen),
+        )),
+    )(input)
+}
+
+/// Parses `name`, `table.name`, `name(args)` or `name(args) OVER (...)`
+fn column_or_function(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    let (rem, name) = located(identifier)(input)?;
+
+    let (rem, args) = opt(function_args)(rem)?;
+    if let Some(args) = args {
+        let name = Spanned::new(name.to_lowercase(), name.span);
+        let (rem, over) = opt(over)(rem)?;
+        return Ok((
+            rem,
+            match over {
+                Some(over) => Expression::Window {
+                    name,
+                    args,
+                    over: Box::new(over),
+                },
+                None => Expression::Function { name, args },
+            },
+        ));
+    }
+
+    let (rem, column) = opt(preceded(symbol(Symbol::Dot), located(identifier)))(rem)?;
+    let expression = match column {
+        Some(column) => Expression::Column {
+            table: Some(name),
+            name: column,
+        },
+        None => Expression::Column { table: None, name },
+    };
+
+    Ok((rem, expression))
+}
+
+fn primary_expression(input: Tokens<'_>) -> ParseResult<'_, Expression> {
+    alt((
+        map(located(parse_literal), Expression::Literal),
+        map(located(Parameter::parse), Expression::Parameter),
+        map(subquery, |subquery| {
+            Expression::Subquery(Box::new(subquery))
+        }),
+        map(preceded(keyword("exists"), cut(subquery)), |subquery| {
+            Expression::Exists(Box::new(subquery))
+        }),
+        case,
+        cast,
+        extract,
+        delimited(
+            symbol(Symbol::LeftParen),
+            Expression::parse,
+            symbol(Symbol::RightParen),
+        ),
+        column_or_function,
+    ))(input)
+}
+
+impl<'a> Parse<'a> for Expression {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        context("Expression", or_expression)(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::parse::Span;
+
+    fn column(name: &str) -> Expression {
+        Expression::Column {
+            table: None,
+            name: name.into(),
+        }
+    }
+
+    fn number(n: &str) -> Expression {
+        Expression::Literal(Value::Number(BigDecimal::from_str(n).unwrap()).into())
+    }
+
+    #[test]
+    fn test_precedence() {
+        let expected = Expression::binary(
+            Expression::binary(
+                column("a"),
+                BinaryOperator::Eq,
+                Expression::binary(
+                    number("1"),
+                    BinaryOperator::Plus,
+                    Expression::binary(number("2"), BinaryOperator::Multiply, column("b")),
+                ),
+            ),
+            BinaryOperator::Or,
+            Expression::Unary {
+                op: UnaryOperator::Not,
+                operand: Box::new(Expression::Column {
+                    table: Some("t".into()),
+                    name: "c".into(),
+                }),
+            },
+        );
+
+        let (rem, expr) = Expression::parse_from_raw("a = 1 + 2 * b or not t.c order").unwrap();
+
+        assert_eq!(expr, expected);
+        assert_eq!(
+            rem.first().map(|next| &next.token),
+            Some(&Token::Keyword("order"))
+        );
+    }
+
+    #[test]
+    fn test_function() {
+        let expected = Expression::binary(
+            Expression::Function {
+                name: "count".into(),
+                args: vec![Expression::Wildcard],
+            },
+            BinaryOperator::Gt,
+            Expression::Function {
+                name: "max".into(),
+                args: vec![column("a")],
+            },
+        );
+
+        let expr = Expression::parse_from_raw("COUNT(*) > max( a )").unwrap().1;
+
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn test_parameters() {
+        let expected = Expression::binary(
+            Expression::binary(
+                column("a"),
+                BinaryOperator::Eq,
+                Expression::Parameter(Parameter::Anonymous.into()),
+            ),
+            BinaryOperator::And,
+            Expression::binary(
+                column("b"),
+                BinaryOperator::Gt,
+                Expression::Parameter(Parameter::Positional(12).into()),
+            ),
+        );
+
+        let expr = Expression::parse_from_raw("a = ? and b > $12").unwrap().1;
+
+        assert_eq!(expr, expected);
+        assert!(Expression::parse_format_error("$0").is_err());
+    }
+
+    #[test]
+    fn test_spans() {
+        let expr = Expression::parse_format_error("t.\"Name\" = max( b )").unwrap();
+
+        let Expression::Binary { left, right, .. } = expr else {
+            panic!("expected a comparison");
+        };
+        let Expression::Column {
+            table: Some(table),
+            name,
+        } = *left
+        else {
+            panic!("expected a qualified column");
+        };
+        assert_eq!(table.span, Span { offset: 0, len: 1 });
+        assert_eq!(name.span, Span { offset: 2, len: 6 });
+
+        let Expression::Function { name, args } = *right else {
+            panic!("expected a function call");
+        };
+        assert_eq!(name.span, Span { offset: 11, len: 3 });
+        assert!(matches!(&args[0], Expression::Column { name, .. } if name.span.offset == 16));
+
+        // the minus of a negative number is part of the literal
+        let expr = Expression::parse_format_error("'a' || - 12").unwrap();
+        let Expression::Binary { left, right, .. } = expr else {
+            panic!("expected a concatenation");
+        };
+        assert!(
+            matches!(*left, Expression::Literal(value) if value.span == Span { offset: 0, len: 3 })
+        );
+        assert!(
+            matches!(*right, Expression::Literal(value) if value.span == Span { offset: 7, len: 4 })
+        );
+    }
+}
@@ -1,7 +1,11 @@
 pub mod commands;
 pub mod error;
+pub mod expression;
 pub mod parse;
 pub mod query;
+pub mod script;
+pub mod token;
 pub mod value;
+pub mod visit;
 
 pub use commands::*;
@@ -1,73 +1,291 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+};
+
 use nom::{
-    bytes::complete::{tag_no_case, take_while1},
-    character::complete::char,
-    character::complete::multispace0,
     combinator::{all_consuming, map, peek},
+    error::{ErrorKind, FromExternalError},
     multi::separated_list1,
-    sequence::{pair, tuple},
+    sequence::pair,
     Finish, IResult,
 };
 use nom_locate::LocatedSpan;
+use nom_supreme::error::{BaseErrorKind, Expectation};
+use serde::{Deserialize, Serialize};
 
-use crate::error::{format_parse_error, FormattedError, ParseError};
+use crate::{
+    error::{
+        format_parse_error, EmptyIdentifier, Expected, FormattedError, InvalidToken, ParseError,
+        ReservedKeyword,
+    },
+    token::{Symbol, Token, Tokens},
+};
 
-// Use nom_locate's LocatedSpan as a wrapper around a string input
+// Use nom_locate's LocatedSpan as a wrapper around a string input, the lexer
+// reads from it
 pub type RawSpan<'a> = LocatedSpan<&'a str>;
 
-// the result for all of our parsers, they will have our span type as input and can have any output
-// this will use a default error type but we will change that latter
-pub type ParseResult<'a, T> = IResult<RawSpan<'a>, T, ParseError<'a>>;
+// the result for all of our parsers, they take the tokens of the source as
+// input and can have any output
+pub type ParseResult<'a, T> = IResult<Tokens<'a>, T, ParseError<'a>>;
 
 /// Implement the parse function to more easily convert a span into a sql
 /// command
 pub trait Parse<'a>: Sized {
-    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self>;
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self>;
 
     fn parse_from_raw(input: &'a str) -> ParseResult<'a, Self> {
-        let i = LocatedSpan::new(input);
-        Self::parse(i)
+        Self::parse(Tokens::new(input))
     }
 
     fn parse_format_error(input: &'a str) -> Result<Self, FormattedError<'a>> {
-        let span_input = LocatedSpan::new(input);
-        match all_consuming(Self::parse)(span_input).finish() {
+        match all_consuming(Self::parse)(Tokens::new(input)).finish() {
             Ok((_, query)) => Ok(query),
             Err(e) => Err(format_parse_error(input, e)),
         }
     }
 }
 
-/// Parse a unquoted sql identifier
-pub(crate) fn identifier(input: RawSpan) -> ParseResult<String> {
-    map(take_while1(|c: char| c.is_alphanumeric()), |s: RawSpan| {
-        s.fragment().to_string()
-    })(input)
+/// Position of a piece of the source text
+#[derive(Debug, Clone, Copy, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    /// Byte offset from the start of the source
+    pub offset: usize,
+    /// Length in bytes
+    pub len: usize,
 }
 
-pub(crate) fn comma_sep<'a, O, E, F>(
-    f: F,
-) -> impl FnMut(RawSpan<'a>) -> IResult<RawSpan<'a>, Vec<O>, E>
+impl Span {
+    pub(crate) fn of(span: RawSpan) -> Self {
+        Self {
+            offset: span.location_offset(),
+            len: span.fragment().len(),
+        }
+    }
+}
+
+impl From<Span> for miette::SourceSpan {
+    fn from(span: Span) -> Self {
+        miette::SourceSpan::new(span.offset.into(), span.len.into())
+    }
+}
+
+/// A node of the AST and the source text it was parsed from. The span is
+/// left out of comparisons and hashing, so a node built in code, where the
+/// span is empty, equals the same node parsed from any text
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash(state)
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    fn from(node: T) -> Self {
+        Self::new(node, Span::default())
+    }
+}
+
+impl From<&str> for Spanned<String> {
+    fn from(name: &str) -> Self {
+        name.to_string().into()
+    }
+}
+
+/// Next token, if any, text the lexer could not read is an error wherever
+/// it is found
+fn next_token<'a, 'b>(
+    input: &'b Tokens<'a>,
+) -> Result<Option<&'b Token>, nom::Err<ParseError<'a>>> {
+    match input.first().map(|next| &next.token) {
+        Some(Token::Invalid(reason)) => Err(nom::Err::Failure(ParseError::from_external_error(
+            input.clone(),
+            ErrorKind::Verify,
+            InvalidToken(reason),
+        ))),
+        next => Ok(next),
+    }
+}
+
+/// Take the next token when `f` accepts it, `what` describes the tokens `f`
+/// accepts for the error otherwise
+pub(crate) fn token<'a, O>(
+    what: &'static str,
+    f: impl Fn(&Token) -> Option<O>,
+) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, O> {
+    move |input: Tokens<'a>| match next_token(&input)?.and_then(&f) {
+        Some(output) => Ok((input.advance(1), output)),
+        None => Err(nom::Err::Error(ParseError::from_external_error(
+            input,
+            ErrorKind::Verify,
+            Expected(what),
+        ))),
+    }
+}
+
+/// Match a sql keyword, either a reserved one or a word with a meaning in
+/// some statements, like the `to` of `SET a TO 1`
+pub(crate) fn keyword<'a>(kw: &'static str) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, ()> {
+    move |input: Tokens<'a>| match next_token(&input)? {
+        Some(Token::Keyword(word)) if *word == kw => Ok((input.advance(1), ())),
+        Some(Token::Identifier(word)) if word == kw => Ok((input.advance(1), ())),
+        _ => Err(nom::Err::Error(ParseError::Base {
+            location: input,
+            kind: BaseErrorKind::Expected(Expectation::Tag(kw)),
+        })),
+    }
+}
+
+/// Match a punctuation or operator symbol
+pub(crate) fn symbol<'a>(symbol: Symbol) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, ()> {
+    token("a symbol", move |token| {
+        (*token == Token::Symbol(symbol)).then_some(())
+    })
+}
+
+/// Parse a sql identifier, either unquoted or quoted with `"` or `` ` ``
+pub(crate) fn identifier(input: Tokens<'_>) -> ParseResult<'_, String> {
+    match next_token(&input)? {
+        Some(Token::Keyword(kw)) => Err(nom::Err::Error(ParseError::from_external_error(
+            input.clone(),
+            ErrorKind::Verify,
+            ReservedKeyword(kw.to_string()),
+        ))),
+        Some(Token::QuotedIdentifier(name)) if name.is_empty() => Err(nom::Err::Failure(
+            ParseError::from_external_error(input.clone(), ErrorKind::Verify, EmptyIdentifier),
+        )),
+        _ => token("an identifier", |token| match token {
+            Token::Identifier(name) | Token::QuotedIdentifier(name) => Some(name.clone()),
+            _ => None,
+        })(input.clone()),
+    }
+}
+
+pub(crate) fn comma_sep<'a, O, F>(f: F) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, Vec<O>>
 where
-    F: nom::Parser<RawSpan<'a>, O, E>,
-    E: nom::error::ParseError<RawSpan<'a>>,
+    F: nom::Parser<Tokens<'a>, O, ParseError<'a>>,
 {
-    separated_list1(tuple((multispace0, char(','), multispace0)), f)
+    separated_list1(symbol(Symbol::Comma), f)
 }
 
-/// Check if the input has the passed in tag
+/// Displays the items separated by `, `
+pub(crate) struct CommaSeparated<'a, T>(pub &'a [T]);
+
+impl<T: fmt::Display> fmt::Display for CommaSeparated<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{item}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check if the input starts with the passed in keyword
 /// if so run the parser supplied
-/// (with the peeked tag still expected)
-/// and cut on error
+/// (with the peeked keyword still expected)
 /// This is useful for alts so we stop on errors
-pub(crate) fn peek_then_cut<'a, T, O, E, F>(
-    peek_tag: T,
+pub(crate) fn peek_then_cut<'a, O, F>(
+    kw: &'static str,
     f: F,
-) -> impl FnMut(RawSpan<'a>) -> IResult<RawSpan<'a>, O, E>
+) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, O>
+where
+    F: nom::Parser<Tokens<'a>, O, ParseError<'a>>,
+{
+    map(pair(peek(keyword(kw)), f), |(_, f_res)| f_res)
+}
+
+/// Run `f` and also return the span of the source text it parsed
+pub(crate) fn spanned<'a, O, F>(mut f: F) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, (O, Span)>
+where
+    F: nom::Parser<Tokens<'a>, O, ParseError<'a>>,
+{
+    move |input: Tokens<'a>| {
+        let (rem, output) = f.parse(input.clone())?;
+        let span = input.span_to(&rem);
+        Ok((rem, (output, span)))
+    }
+}
+
+/// Run `f` and keep the span of the source text it parsed with its output
+pub(crate) fn located<'a, O, F>(f: F) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, Spanned<O>>
 where
-    T: nom::InputLength + Clone,
-    F: nom::Parser<RawSpan<'a>, O, E>,
-    E: nom::error::ParseError<RawSpan<'a>> + nom_supreme::tag::TagError<RawSpan<'a>, T>,
-    LocatedSpan<&'a str>: nom::Compare<T>,
+    F: nom::Parser<Tokens<'a>, O, ParseError<'a>>,
 {
-    map(pair(peek(tag_no_case(peek_tag)), f), |(_, f_res)| f_res)
+    map(spanned(f), |(node, span)| Spanned::new(node, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_identifier(input: &str) -> Option<String> {
+        all_consuming(identifier)(Tokens::new(input))
+            .finish()
+            .ok()
+            .map(|(_, name)| name)
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(parse_identifier("User_Id2").as_deref(), Some("user_id2"));
+        assert_eq!(parse_identifier("_tmp").as_deref(), Some("_tmp"));
+        assert_eq!(
+            parse_identifier("\"Created-At\"").as_deref(),
+            Some("Created-At")
+        );
+        assert_eq!(
+            parse_identifier("\"say \"\"hi\"\"\"").as_deref(),
+            Some("say \"hi\"")
+        );
+        assert_eq!(parse_identifier("`a``b`").as_deref(), Some("a`b"));
+        assert_eq!(parse_identifier("\"select\"").as_deref(), Some("select"));
+
+        for invalid in ["select", "FROM", "1abc", "\"\"", "\"open", "a-b"] {
+            assert_eq!(parse_identifier(invalid), None, "{invalid}");
+        }
+    }
 }
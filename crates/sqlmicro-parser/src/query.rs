@@ -1,54 +1,653 @@
-use nom::{
-    branch::alt,
-    character::complete::char,
-    character::complete::multispace0,
-    combinator::map,
-    error::context,
-    sequence::{preceded, tuple},
-};
+use derive_more::Display;
+use nom::{branch::alt, combinator::map, error::context, sequence::terminated};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    parse::{peek_then_cut, Parse, ParseResult, RawSpan},
-    CreateStatement, InsertStatement, SelectStatement,
+    parse::{peek_then_cut, symbol, Parse, ParseResult},
+    token::{Symbol, Tokens},
+    CreateStatement, InsertStatement, SelectStatement, SetStatement,
 };
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+/// Displays as canonical sql, including the closing `;`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Display)]
 pub enum SqlQuery {
+    #[display(fmt = "{_0};")]
     Create(CreateStatement),
+    #[display(fmt = "{_0};")]
     Insert(InsertStatement),
-    Select(SelectStatement),
+    #[display(fmt = "{_0};")]
+    Select(Box<SelectStatement>),
+    #[display(fmt = "{_0};")]
+    Set(SetStatement),
 }
 
-impl<'a> Parse<'a> for SqlQuery {
-    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
-        let (rest, (query, _, _, _)) = context(
-            "Query",
-            preceded(
-                multispace0,
-                tuple((
-                    alt((
-                        peek_then_cut("create", map(CreateStatement::parse, SqlQuery::Create)),
-                        peek_then_cut("select", map(SelectStatement::parse, SqlQuery::Select)),
-                        peek_then_cut("insert", map(InsertStatement::parse, SqlQuery::Insert)),
-                    )),
-                    multispace0,
-                    char(';'),
-                    multispace0,
-                )),
+impl SqlQuery {
+    /// The query as canonical sql, the same as its `Display`
+    pub fn to_sql(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// A statement up to its terminating `;`
+pub(crate) fn statement(input: Tokens<'_>) -> ParseResult<'_, SqlQuery> {
+    terminated(
+        alt((
+            peek_then_cut("create", map(CreateStatement::parse, SqlQuery::Create)),
+            peek_then_cut(
+                "select",
+                map(SelectStatement::parse, |select| {
+                    SqlQuery::Select(Box::new(select))
+                }),
             ),
-        )(input)?;
+            peek_then_cut(
+                "with",
+                map(SelectStatement::parse, |select| {
+                    SqlQuery::Select(Box::new(select))
+                }),
+            ),
+            peek_then_cut("insert", map(InsertStatement::parse, SqlQuery::Insert)),
+            peek_then_cut("set", map(SetStatement::parse, SqlQuery::Set)),
+        )),
+        symbol(Symbol::Semicolon),
+    )(input)
+}
 
-        Ok((rest, query))
+impl<'a> Parse<'a> for SqlQuery {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        context("Query", statement)(input)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse::Parse, Column, CreateStatement, SqlTypeInfo};
+    use bigdecimal::BigDecimal;
+    use proptest::prelude::*;
+
+    use crate::{
+        expression::{
+            BinaryOperator, Expression, FrameBound, FrameUnits, Parameter, UnaryOperator, Window,
+            WindowFrame,
+        },
+        parse::{Parse, Spanned},
+        token::RESERVED_KEYWORDS,
+        value::Value,
+        Assignment, Column, ConflictAction, CreateStatement, Cte, Distinct, InsertSource,
+        InsertStatement, Join, Key, KeyKind, OnConflict, OrderBy, SelectItem, SelectStatement,
+        SetOperation, SetOperator, SetStatement, SqlTypeInfo, TableLayout, TableRef, With,
+    };
 
     use super::SqlQuery;
 
+    /// Names that print plain, quoted, or are keywords
+    fn name() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-z_][a-z0-9_]{0,6}",
+            "[a-zA-Z0-9_ \"`'-]{1,8}",
+            prop::sample::select(RESERVED_KEYWORDS).prop_map(String::from),
+        ]
+    }
+
+    fn value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            (any::<i64>(), -3..6i64)
+                .prop_map(|(n, scale)| Value::Number(BigDecimal::new(n.into(), scale))),
+            "\\PC{0,8}".prop_map(Value::String),
+            any::<bool>().prop_map(Value::Boolean),
+            Just(Value::Null),
+        ]
+    }
+
+    fn expression() -> BoxedStrategy<Expression> {
+        let leaf = prop_oneof![
+            value().prop_map(|value| Expression::Literal(value.into())),
+            Just(Expression::Parameter(Parameter::Anonymous.into())),
+            (1..20usize).prop_map(|n| Expression::Parameter(Parameter::Positional(n).into())),
+            (prop::option::of(name()), name()).prop_map(|(table, name)| Expression::Column {
+                table: table.map(Into::into),
+                name: name.into(),
+            }),
+        ];
+        let binary_operator = prop::sample::select(vec![
+            BinaryOperator::Or,
+            BinaryOperator::And,
+            BinaryOperator::Eq,
+            BinaryOperator::NotEq,
+            BinaryOperator::Lt,
+            BinaryOperator::LtEq,
+            BinaryOperator::Gt,
+            BinaryOperator::GtEq,
+            BinaryOperator::Plus,
+            BinaryOperator::Minus,
+            BinaryOperator::Multiply,
+            BinaryOperator::Divide,
+            BinaryOperator::Modulo,
+            BinaryOperator::Concat,
+        ]);
+        leaf.prop_recursive(4, 24, 3, move |inner| {
+            // the larger arms are boxed, the value tree of the union holds
+            // every arm inline and overflows the stack otherwise
+            prop_oneof![
+                (inner.clone(), binary_operator.clone(), inner.clone())
+                    .prop_map(|(left, op, right)| Expression::binary(left, op, right)),
+                inner.clone().prop_map(|operand| Expression::Unary {
+                    op: UnaryOperator::Not,
+                    operand: Box::new(operand),
+                }),
+                // the parser folds the minus of a number into the literal
+                inner
+                    .clone()
+                    .prop_filter("negated number", |operand| !matches!(
+                        operand,
+                        Expression::Literal(Spanned {
+                            node: Value::Number(_),
+                            ..
+                        })
+                    ))
+                    .prop_map(|operand| Expression::Unary {
+                        op: UnaryOperator::Minus,
+                        operand: Box::new(operand),
+                    }),
+                (
+                    inner.clone(),
+                    prop::collection::vec(inner.clone(), 1..3),
+                    any::<bool>()
+                )
+                    .prop_map(|(expr, list, negated)| Expression::InList {
+                        expr: Box::new(expr),
+                        list,
+                        negated,
+                    })
+                    .boxed(),
+                (inner.clone(), inner.clone(), inner.clone(), any::<bool>())
+                    .prop_map(|(expr, low, high, negated)| Expression::Between {
+                        expr: Box::new(expr),
+                        low: Box::new(low),
+                        high: Box::new(high),
+                        negated,
+                    })
+                    .boxed(),
+                (
+                    inner.clone(),
+                    inner.clone(),
+                    prop::option::of(inner.clone()),
+                    any::<bool>(),
+                    any::<bool>()
+                )
+                    .prop_map(|(expr, pattern, escape, negated, case_insensitive)| {
+                        Expression::Like {
+                            expr: Box::new(expr),
+                            pattern: Box::new(pattern),
+                            escape: escape.map(Box::new),
+                            negated,
+                            case_insensitive,
+                        }
+                    })
+                    .boxed(),
+                (
+                    prop::option::of(inner.clone()),
+                    prop::collection::vec((inner.clone(), inner.clone()), 1..3),
+                    prop::option::of(inner.clone())
+                )
+                    .prop_map(|(operand, branches, else_result)| {
+                        Expression::Case {
+                            operand: operand.map(Box::new),
+                            branches,
+                            else_result: else_result.map(Box::new),
+                        }
+                    })
+                    .boxed(),
+                (
+                    inner.clone(),
+                    prop_oneof![Just(SqlTypeInfo::Int), Just(SqlTypeInfo::String)]
+                )
+                    .prop_map(|(expr, type_info)| Expression::Cast {
+                        expr: Box::new(expr),
+                        type_info,
+                    }),
+                (
+                    "[a-z][a-z_]{0,5}",
+                    prop::collection::vec(inner.clone(), 0..3)
+                )
+                    .prop_map(|(name, args)| Expression::Function {
+                        name: name.into(),
+                        args
+                    }),
+                "[a-z][a-z_]{0,5}".prop_map(|name| Expression::Function {
+                    name: name.into(),
+                    args: vec![Expression::Wildcard],
+                }),
+                (
+                    "[a-z][a-z_]{0,5}",
+                    prop::collection::vec(inner.clone(), 0..2),
+                    prop::collection::vec(inner.clone(), 0..2),
+                    prop::collection::vec((inner, any::<bool>()), 0..2),
+                    prop::option::of(frame()),
+                )
+                    .prop_map(|(name, args, partition_by, order_by, frame)| {
+                        Expression::Window {
+                            name: name.into(),
+                            args,
+                            over: Box::new(Window {
+                                partition_by,
+                                order_by: order_by
+                                    .into_iter()
+                                    .map(|(expr, asc)| OrderBy { expr, asc })
+                                    .collect(),
+                                frame,
+                            }),
+                        }
+                    })
+                    .boxed(),
+            ]
+        })
+        .boxed()
+    }
+
+    fn frame() -> impl Strategy<Value = WindowFrame> {
+        let bound = prop_oneof![
+            Just(FrameBound::UnboundedPreceding),
+            (0..100usize).prop_map(FrameBound::Preceding),
+            Just(FrameBound::CurrentRow),
+            (0..100usize).prop_map(FrameBound::Following),
+            Just(FrameBound::UnboundedFollowing),
+        ];
+        let units = prop_oneof![Just(FrameUnits::Rows), Just(FrameUnits::Range)];
+        (units, bound.clone(), bound).prop_map(|(units, start, end)| WindowFrame {
+            units,
+            start,
+            end,
+        })
+    }
+
+    /// Expressions that can also hold subqueries, one level deep
+    fn nested_expression() -> impl Strategy<Value = Expression> + Clone {
+        let subquery = || select_with(expression(), table()).prop_map(Box::new);
+        prop_oneof![
+            4 => expression(),
+            1 => subquery().prop_map(Expression::Subquery),
+            1 => subquery().prop_map(Expression::Exists),
+            1 => (expression(), subquery(), any::<bool>()).prop_map(|(expr, subquery, negated)| {
+                Expression::InSubquery {
+                    expr: Box::new(expr),
+                    subquery,
+                    negated,
+                }
+            }),
+        ]
+    }
+
+    fn table() -> BoxedStrategy<TableRef> {
+        (name(), prop::option::of(name()))
+            .prop_map(|(name, alias)| TableRef::Table {
+                name: name.into(),
+                alias: alias.map(Into::into),
+            })
+            .boxed()
+    }
+
+    /// A table or a derived table over a plain select
+    fn table_ref() -> impl Strategy<Value = TableRef> + Clone {
+        prop_oneof![
+            3 => table(),
+            1 => (select_with(expression(), table()), name())
+                .prop_map(|(query, alias)| TableRef::Subquery {
+                    query: Box::new(query),
+                    alias: alias.into(),
+                }),
+        ]
+    }
+
+    /// Set operations over plain selects without `ORDER BY` or `LIMIT`
+    fn compound() -> impl Strategy<Value = Vec<SetOperation>> {
+        let op = prop_oneof![
+            Just(SetOperator::Union),
+            Just(SetOperator::Intersect),
+            Just(SetOperator::Except),
+        ];
+        let operation =
+            (op, any::<bool>(), select_with(expression(), table())).prop_map(|(op, all, query)| {
+                SetOperation {
+                    op,
+                    all,
+                    query: SelectStatement {
+                        order_by: vec![],
+                        limit: None,
+                        offset: None,
+                        ..query
+                    },
+                }
+            });
+        prop::collection::vec(operation, 0..3)
+    }
+
+    /// `WITH` over plain selects
+    fn with() -> impl Strategy<Value = With> {
+        let cte = (
+            name(),
+            prop::collection::vec(name(), 0..3),
+            select_with(expression(), table()),
+            compound(),
+        )
+            .prop_map(|(name, columns, query, compound)| Cte {
+                name: name.into(),
+                columns: columns.into_iter().map(Into::into).collect(),
+                query: Box::new(SelectStatement { compound, ..query }),
+            });
+        (any::<bool>(), prop::collection::vec(cte, 1..3))
+            .prop_map(|(recursive, ctes)| With { recursive, ctes })
+    }
+
+    fn select() -> BoxedStrategy<SelectStatement> {
+        (
+            prop::option::weighted(0.2, with().prop_map(Box::new)),
+            select_with(nested_expression(), table_ref()),
+            compound(),
+        )
+            .prop_map(|(with, select, compound)| SelectStatement {
+                with,
+                compound,
+                ..select
+            })
+            .boxed()
+    }
+
+    fn select_with(
+        expression: impl Strategy<Value = Expression> + Clone + 'static,
+        table: impl Strategy<Value = TableRef> + Clone + 'static,
+    ) -> impl Strategy<Value = SelectStatement> {
+        let item = prop_oneof![
+            Just(SelectItem::Wildcard(().into())),
+            (expression.clone(), prop::option::of(name()))
+                .prop_map(|(expr, alias)| SelectItem::Expression { expr, alias }),
+        ];
+        let join = (table.clone(), expression.clone()).prop_map(|(table, on)| Join { table, on });
+        let order_by =
+            (expression.clone(), any::<bool>()).prop_map(|(expr, asc)| OrderBy { expr, asc });
+        let distinct = prop::option::of(prop_oneof![
+            Just(Distinct::All),
+            prop::collection::vec(expression.clone(), 1..3).prop_map(Distinct::On),
+        ]);
+        let limit = prop::option::of((0..1000usize, prop::option::of(0..1000usize)));
+        (
+            distinct,
+            prop::collection::vec(item, 1..3),
+            table,
+            prop::collection::vec(join, 0..2),
+            prop::option::of(expression.clone()),
+            prop::collection::vec(expression, 0..2),
+            prop::collection::vec(order_by, 0..2),
+            limit,
+        )
+            .prop_map(
+                |(distinct, fields, table, joins, filter, group_by, order_by, limit)| {
+                    SelectStatement {
+                        with: None,
+                        distinct,
+                        table,
+                        fields,
+                        joins,
+                        filter,
+                        group_by,
+                        compound: vec![],
+                        order_by,
+                        limit: limit.map(|(limit, _)| limit),
+                        offset: limit.and_then(|(_, offset)| offset),
+                    }
+                },
+            )
+    }
+
+    fn query() -> impl Strategy<Value = SqlQuery> {
+        let type_info = prop_oneof![Just(SqlTypeInfo::Int), Just(SqlTypeInfo::String)];
+        let column = (name(), type_info).prop_map(|(name, type_info)| Column {
+            name: name.into(),
+            type_info,
+        });
+        let layout = prop_oneof![Just(TableLayout::Row), Just(TableLayout::Columnar)];
+        let names = || prop::collection::vec(name().prop_map(Into::into), 1..3);
+        let key = (
+            prop_oneof![Just(KeyKind::Primary), Just(KeyKind::Unique)],
+            names(),
+        )
+            .prop_map(|(kind, columns)| Key { kind, columns });
+        let assignment = (name(), expression()).prop_map(|(column, value)| Assignment {
+            column: column.into(),
+            value,
+        });
+        let returning = prop_oneof![
+            Just(SelectItem::Wildcard(().into())),
+            (expression(), prop::option::of(name()))
+                .prop_map(|(expr, alias)| SelectItem::Expression { expr, alias }),
+        ];
+        // only DO NOTHING can leave the key out
+        let on_conflict = prop_oneof![
+            prop::option::of(names()).prop_map(|target| OnConflict {
+                target: target.unwrap_or_default(),
+                action: ConflictAction::Nothing,
+            }),
+            (names(), prop::collection::vec(assignment, 1..3)).prop_map(|(target, assignments)| {
+                OnConflict {
+                    target,
+                    action: ConflictAction::Update(assignments),
+                }
+            }),
+        ];
+        prop_oneof![
+            (
+                name(),
+                prop::collection::vec(column, 1..4),
+                prop::collection::vec(key, 0..3),
+                layout.clone()
+            )
+                .prop_map(|(table, columns, keys, layout)| {
+                    SqlQuery::Create(CreateStatement {
+                        table: table.into(),
+                        columns,
+                        keys,
+                        layout,
+                        query: None,
+                    })
+                }),
+            (name(), layout, select()).prop_map(|(table, layout, query)| {
+                SqlQuery::Create(CreateStatement {
+                    table: table.into(),
+                    columns: vec![],
+                    keys: vec![],
+                    layout,
+                    query: Some(Box::new(query)),
+                })
+            }),
+            (
+                name(),
+                prop::collection::vec(name(), 0..3),
+                prop_oneof![
+                    prop::collection::vec(expression(), 1..4).prop_map(InsertSource::Values),
+                    select().prop_map(|select| InsertSource::Select(Box::new(select))),
+                ],
+                prop::option::of(on_conflict),
+                prop::collection::vec(returning, 0..3),
+            )
+                .prop_map(|(table, columns, source, on_conflict, returning)| {
+                    SqlQuery::Insert(InsertStatement {
+                        table: table.into(),
+                        columns: columns.into_iter().map(Into::into).collect(),
+                        source,
+                        on_conflict,
+                        returning,
+                    })
+                }),
+            select().prop_map(|select| SqlQuery::Select(Box::new(select))),
+            (name(), value()).prop_map(|(name, value)| SqlQuery::Set(SetStatement { name, value })),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_print_round_trip(query in query()) {
+            let sql = query.to_sql();
+            let parsed = SqlQuery::parse_format_error(&sql)
+                .map_err(|e| TestCaseError::fail(format!("{sql}: {e:?}")))?;
+            prop_assert_eq!(parsed, query, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_print() {
+        let query = SqlQuery::parse_format_error(
+            "select Name, count(*) AS \"Total\" from \"Users\" join posts on id=author \
+             where not (a or b) and -(x + 1) * 2 > - -y order by 2 asc, name desc limit 5;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT name, count(*) AS \"Total\" FROM \"Users\" JOIN posts ON id = author \
+             WHERE NOT (a OR b) AND -(x + 1) * 2 > - -y ORDER BY 2, name DESC LIMIT 5;"
+        );
+    }
+
+    #[test]
+    fn test_print_subqueries() {
+        let query = SqlQuery::parse_format_error(
+            "select (select max(id) from b), x from (select a as x from c) t \
+             where not exists (select * from d where d.x = t.x) and x + 1 not in (select y from e) \
+             and (x in (select y from e)) = true;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT (SELECT max(id) FROM b), x FROM (SELECT a AS x FROM c) AS t \
+             WHERE NOT EXISTS (SELECT * FROM d WHERE d.x = t.x) AND x + 1 NOT IN (SELECT y FROM e) \
+             AND x IN (SELECT y FROM e) = TRUE;"
+        );
+    }
+
+    #[test]
+    fn test_print_predicates() {
+        let query = SqlQuery::parse_format_error(
+            "select * from t where a not in (1, 2 + 3) and b between -1 and x + 1 \
+             and c ilike '%a!_%' escape '!' or not d like e and (a in (1)) = false;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT * FROM t WHERE a NOT IN (1, 2 + 3) AND b BETWEEN -1 AND x + 1 \
+             AND c ILIKE '%a!_%' ESCAPE '!' OR NOT d LIKE e AND a IN (1) = FALSE;"
+        );
+    }
+
+    #[test]
+    fn test_print_conditionals() {
+        let query = SqlQuery::parse_format_error(
+            "select case when a > 1 then 'big' when a < 0 then null else 'small' end, \
+             case a + 1 when 2 then cast(b as string) end, -x::int::string, \
+             coalesce(a, nullif(b, 0)) from t;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT CASE WHEN a > 1 THEN 'big' WHEN a < 0 THEN NULL ELSE 'small' END, \
+             CASE a + 1 WHEN 2 THEN CAST(b AS STRING) END, -CAST(CAST(x AS INT) AS STRING), \
+             coalesce(a, nullif(b, 0)) FROM t;"
+        );
+    }
+
+    #[test]
+    fn test_print_functions() {
+        let query = SqlQuery::parse_format_error(
+            "select upper(a) || '-' || (b || c), a || b = c, extract(YEAR from now()), \
+             extract('day', d) from t where a || b like 'x%';",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT upper(a) || '-' || (b || c), a || b = c, extract('year', now()), \
+             extract('day', d) FROM t WHERE a || b LIKE 'x%';"
+        );
+    }
+
+    #[test]
+    fn test_print_windows() {
+        let query = SqlQuery::parse_format_error(
+            "select row_number() over (), rank() OVER (partition by a, b order by c desc), \
+             sum(x) over (order by c rows 2 preceding) + 1, \
+             first_value(y) over (range between unbounded preceding and 3 following) \
+             from t order by lag(x, 1, 0) over (partition by a);",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT row_number() OVER (), rank() OVER (PARTITION BY a, b ORDER BY c DESC), \
+             sum(x) OVER (ORDER BY c ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) + 1, \
+             first_value(y) OVER (RANGE BETWEEN UNBOUNDED PRECEDING AND 3 FOLLOWING) \
+             FROM t ORDER BY lag(x, 1, 0) OVER (PARTITION BY a);"
+        );
+
+        for sql in [
+            "select sum(x) over (rows between 1 preceding) from t;",
+            "select sum(x) over (order by) from t;",
+            "select sum(x) over partition from t;",
+        ] {
+            assert!(SqlQuery::parse_format_error(sql).is_err(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_print_with() {
+        let query = SqlQuery::parse_format_error(
+            "with recursive tree (id, depth) as (select id, 0 from nodes where parent = 0 \
+             union all select nodes.id, depth + 1 from nodes join tree on parent = tree.id), \
+             top as (select * from tree where depth < 2) select * from top;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "WITH RECURSIVE tree (id, depth) AS (SELECT id, 0 FROM nodes WHERE parent = 0 \
+             UNION ALL SELECT nodes.id, depth + 1 FROM nodes JOIN tree ON parent = tree.id), \
+             top AS (SELECT * FROM tree WHERE depth < 2) SELECT * FROM top;"
+        );
+    }
+
+    #[test]
+    fn test_print_set_operations() {
+        let query = SqlQuery::parse_format_error(
+            "select a from b union select c from d where c > 1 intersect all select e from f \
+             group by e except select g from h order by 1 desc limit 2;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT a FROM b UNION SELECT c FROM d WHERE c > 1 INTERSECT ALL SELECT e FROM f \
+             GROUP BY e EXCEPT SELECT g FROM h ORDER BY 1 DESC LIMIT 2;"
+        );
+    }
+
+    #[test]
+    fn test_print_distinct() {
+        let query = SqlQuery::parse_format_error(
+            "select distinct on (name, age % 2) name, id from users order by name, age % 2, id desc;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT DISTINCT ON (name, age % 2) name, id FROM users ORDER BY name, age % 2, id DESC;"
+        );
+        assert_eq!(
+            SqlQuery::parse_format_error("select distinct name from users;")
+                .unwrap()
+                .to_string(),
+            "SELECT DISTINCT name FROM users;"
+        );
+    }
+
     #[test]
     fn test_create() {
         let expected = CreateStatement {
@@ -67,6 +666,9 @@ mod tests {
                     type_info: SqlTypeInfo::String,
                 },
             ],
+            keys: vec![],
+            layout: TableLayout::Row,
+            query: None,
         };
 
         let query_raw = "CREATE TABLE foo (col1 int, col2 string, col3 string);";
@@ -75,4 +677,14 @@ mod tests {
 
         assert_eq!(SqlQuery::Create(expected), query);
     }
+
+    #[test]
+    fn test_comments() {
+        let query_raw = "-- all the users\nSELECT /* every column */ * FROM users -- done\n;";
+
+        let query = SqlQuery::parse_format_error(query_raw).unwrap();
+
+        assert!(matches!(query, SqlQuery::Select(select) if select.table == "users".into()));
+        assert!(SqlQuery::parse_format_error("select * /* from users;").is_err());
+    }
 }
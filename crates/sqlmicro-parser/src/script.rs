@@ -0,0 +1,164 @@
+use std::fmt;
+
+use nom::{error::context, multi::many0};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{format_parse_error, ParseErrors},
+    parse::{spanned, Parse, ParseResult, Span},
+    query::{statement, SqlQuery},
+    token::{Symbol, Token, Tokens},
+};
+
+/// Statement of a script and the position of its text, from its first
+/// keyword to the closing `;`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct ScriptStatement {
+    pub query: SqlQuery,
+    pub span: Span,
+}
+
+/// Any number of `;` terminated statements, with comments allowed wherever
+/// whitespace is
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Script {
+    pub statements: Vec<ScriptStatement>,
+}
+
+/// One statement per line
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statement in &self.statements {
+            writeln!(f, "{}", statement.query)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Parse<'a> for Script {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
+        let (rem, statements) = context("Script", many0(spanned(statement)))(input)?;
+
+        let statements = statements
+            .into_iter()
+            .map(|(query, span)| ScriptStatement { query, span })
+            .collect();
+
+        Ok((rem, Script { statements }))
+    }
+}
+
+impl Script {
+    /// Parse every statement of `input`, a statement that does not parse is
+    /// skipped up to its `;` so the statements after it are still checked,
+    /// and all of the errors are reported together
+    pub fn parse_recovering(input: &str) -> Result<Script, ParseErrors<'_>> {
+        let mut tokens = Tokens::new(input);
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+
+        while tokens.first().is_some() {
+            match spanned(statement)(tokens.clone()) {
+                Ok((rest, (query, span))) => {
+                    statements.push(ScriptStatement { query, span });
+                    tokens = rest;
+                }
+                Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                    errors.push(format_parse_error(input, e));
+                    tokens = skip_statement(tokens);
+                }
+                Err(nom::Err::Incomplete(_)) => unreachable!("the tokens are all read up front"),
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(Script { statements }),
+            false => Err(ParseErrors { errors }),
+        }
+    }
+}
+
+/// The input after the next `;`, or nothing when there is none
+fn skip_statement(tokens: Tokens<'_>) -> Tokens<'_> {
+    let mut rest = tokens;
+    while let Some(next) = rest.first() {
+        let end = next.token == Token::Symbol(Symbol::Semicolon);
+        rest = rest.advance(1);
+        if end {
+            break;
+        }
+    }
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::Diagnostic;
+
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let script_raw = "/* setup */\n\
+            create table foo (a int); -- first\n\
+            insert into foo values 1;\n\
+            \n\
+            select a from foo;";
+
+        let script = Script::parse_format_error(script_raw).unwrap();
+
+        let texts: Vec<&str> = script
+            .statements
+            .iter()
+            .map(|statement| &script_raw[statement.span.offset..][..statement.span.len])
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "create table foo (a int);",
+                "insert into foo values 1;",
+                "select a from foo;"
+            ]
+        );
+        assert!(matches!(script.statements[2].query, SqlQuery::Select(_)));
+
+        assert!(Script::parse_format_error("  -- nothing here\n")
+            .unwrap()
+            .statements
+            .is_empty());
+        assert!(Script::parse_format_error("select a from foo; select").is_err());
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let script_raw = "selct a from foo;\n\
+            create table foo (a int);\n\
+            select a form foo;\n\
+            insert into foo values 1\n\
+            select a from foo;";
+
+        let errors = Script::parse_recovering(script_raw).unwrap_err().errors;
+
+        let labels: Vec<(usize, Option<String>)> = errors
+            .iter()
+            .map(|error| {
+                let help = error.help().map(|help| help.to_string());
+                (error.labels().unwrap().next().unwrap().offset(), help)
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                (0, Some("did you mean SELECT?".to_string())),
+                (53, Some("did you mean FROM?".to_string())),
+                (88, None),
+            ]
+        );
+
+        let script = Script::parse_recovering("select a from foo; ; insert into foo values 1;");
+        assert_eq!(script.unwrap_err().errors.len(), 1);
+
+        let script = Script::parse_recovering("create table foo (a int); select a from foo;");
+        assert_eq!(script.unwrap().statements.len(), 2);
+    }
+}
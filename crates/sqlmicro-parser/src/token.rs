@@ -0,0 +1,507 @@
+//! Lexer turning sql text into tokens.
+//!
+//! Whitespace and comments are dropped here, keywords are told apart from
+//! names and unquoted names are folded to lowercase, so the grammar only
+//! deals with [`Token`]s. The lexer never fails: text that is not a valid
+//! token becomes [`Token::Invalid`] and the grammar reports it where it is
+//! found.
+
+use std::{fmt, rc::Rc, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use derive_more::Display;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till, take_until, take_while},
+    character::complete::{char, digit0, digit1, multispace1, one_of, satisfy},
+    combinator::{consumed, cut, map, map_opt, map_res, opt, recognize, value},
+    sequence::{pair, preceded, tuple},
+    IResult, InputLength,
+};
+use nom_supreme::error::ErrorTree;
+
+use crate::{
+    expression::Parameter,
+    parse::{RawSpan, Span},
+};
+
+/// Words that can only be used as names when quoted
+pub const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "and",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "cast",
+    "create",
+    "desc",
+    "distinct",
+    "else",
+    "end",
+    "escape",
+    "except",
+    "exists",
+    "false",
+    "from",
+    "group",
+    "ilike",
+    "in",
+    "inner",
+    "insert",
+    "intersect",
+    "into",
+    "join",
+    "like",
+    "limit",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "recursive",
+    "returning",
+    "select",
+    "set",
+    "table",
+    "then",
+    "true",
+    "union",
+    "using",
+    "values",
+    "when",
+    "where",
+    "with",
+];
+
+/// Displays a name the way the lexer reads it back: as is when it is a
+/// lowercase word that is not a keyword, between `"` otherwise
+#[derive(Debug, Clone, Copy)]
+pub struct Ident<'a>(pub &'a str);
+
+impl fmt::Display for Ident<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.0;
+        let plain = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(is_identifier_char)
+            && name.to_lowercase() == name
+            && !RESERVED_KEYWORDS.contains(&name);
+        if plain {
+            f.write_str(name)
+        } else {
+            write!(f, "\"{}\"", name.replace('"', "\"\""))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Display)]
+pub enum Symbol {
+    #[display(fmt = ",")]
+    Comma,
+    #[display(fmt = ";")]
+    Semicolon,
+    #[display(fmt = "(")]
+    LeftParen,
+    #[display(fmt = ")")]
+    RightParen,
+    #[display(fmt = ".")]
+    Dot,
+    #[display(fmt = "*")]
+    Star,
+    #[display(fmt = "+")]
+    Plus,
+    #[display(fmt = "-")]
+    Minus,
+    #[display(fmt = "/")]
+    Slash,
+    #[display(fmt = "%")]
+    Percent,
+    #[display(fmt = "=")]
+    Eq,
+    /// `<>` or `!=`
+    #[display(fmt = "<>")]
+    NotEq,
+    #[display(fmt = "<")]
+    Lt,
+    #[display(fmt = "<=")]
+    LtEq,
+    #[display(fmt = ">")]
+    Gt,
+    #[display(fmt = ">=")]
+    GtEq,
+    /// `::`, the cast operator
+    #[display(fmt = "::")]
+    DoubleColon,
+    /// `||`, string concatenation
+    #[display(fmt = "||")]
+    Concat,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Token {
+    /// One of [`RESERVED_KEYWORDS`]
+    Keyword(&'static str),
+    /// Unquoted name, folded to lowercase
+    Identifier(String),
+    /// `"name"` or `` `name` ``, kept as written
+    QuotedIdentifier(String),
+    /// `'text'`
+    String(String),
+    /// Unsigned number, the sign is a [`Symbol::Minus`] of its own
+    Number(BigDecimal),
+    Parameter(Parameter),
+    Symbol(Symbol),
+    /// Text that is not a valid token, with the reason
+    Invalid(&'static str),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+type LexResult<'a, T> = IResult<RawSpan<'a>, T, ErrorTree<RawSpan<'a>>>;
+
+/// `-- line` or `/* block */` comment
+fn comment(input: RawSpan) -> LexResult<RawSpan> {
+    alt((
+        recognize(pair(tag("--"), take_till(|c| c == '\n'))),
+        recognize(tuple((tag("/*"), cut(take_until("*/")), tag("*/")))),
+    ))(input)
+}
+
+/// Text between two `quote` characters, a doubled quote stands for the
+/// quote itself
+fn quoted<'a>(quote: char) -> impl FnMut(RawSpan<'a>) -> LexResult<'a, String> {
+    move |input| {
+        let (mut rem, _) = char(quote)(input)?;
+        let mut text = String::new();
+        loop {
+            let (next, part) = take_till(|c| c == quote)(rem)?;
+            text.push_str(part.fragment());
+            let (next, _) = cut(char(quote))(next)?;
+            match char::<_, ErrorTree<RawSpan>>(quote)(next) {
+                Ok((next, _)) => {
+                    text.push(quote);
+                    rem = next;
+                }
+                Err(_) => return Ok((next, text)),
+            }
+        }
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `digits[.digits][e[+|-]digits]`, the integer part can be left out when
+/// there is a fraction, as in `.5`
+fn number(input: RawSpan) -> LexResult<Token> {
+    map_res(
+        recognize(pair(
+            alt((
+                recognize(pair(digit1, opt(pair(char('.'), digit0)))),
+                recognize(pair(char('.'), digit1)),
+            )),
+            opt(tuple((one_of("eE"), opt(one_of("+-")), cut(digit1)))),
+        )),
+        |number: RawSpan| BigDecimal::from_str(number.fragment()).map(Token::Number),
+    )(input)
+}
+
+fn word(input: RawSpan) -> LexResult<Token> {
+    map(
+        recognize(pair(
+            satisfy(|c: char| c.is_alphabetic() || c == '_'),
+            take_while(is_identifier_char),
+        )),
+        |word: RawSpan| {
+            let word = word.fragment().to_lowercase();
+            match RESERVED_KEYWORDS.iter().find(|kw| **kw == word) {
+                Some(kw) => Token::Keyword(kw),
+                None => Token::Identifier(word),
+            }
+        },
+    )(input)
+}
+
+fn parameter(input: RawSpan) -> LexResult<Parameter> {
+    alt((
+        value(Parameter::Anonymous, char('?')),
+        map_opt(preceded(char('$'), digit1), |n: RawSpan| {
+            let n: usize = n.fragment().parse().ok()?;
+            (n > 0).then_some(Parameter::Positional(n))
+        }),
+    ))(input)
+}
+
+fn symbol(input: RawSpan) -> LexResult<Symbol> {
+    alt((
+        value(Symbol::LtEq, tag("<=")),
+        value(Symbol::GtEq, tag(">=")),
+        value(Symbol::NotEq, tag("<>")),
+        value(Symbol::NotEq, tag("!=")),
+        value(Symbol::DoubleColon, tag("::")),
+        value(Symbol::Concat, tag("||")),
+        value(Symbol::Comma, char(',')),
+        value(Symbol::Semicolon, char(';')),
+        value(Symbol::LeftParen, char('(')),
+        value(Symbol::RightParen, char(')')),
+        value(Symbol::Dot, char('.')),
+        value(Symbol::Star, char('*')),
+        value(Symbol::Plus, char('+')),
+        value(Symbol::Minus, char('-')),
+        value(Symbol::Slash, char('/')),
+        value(Symbol::Percent, char('%')),
+        value(Symbol::Eq, char('=')),
+        value(Symbol::Lt, char('<')),
+        value(Symbol::Gt, char('>')),
+    ))(input)
+}
+
+fn token(input: RawSpan) -> LexResult<Token> {
+    alt((
+        map(quoted('\''), Token::String),
+        map(quoted('"'), Token::QuotedIdentifier),
+        map(quoted('`'), Token::QuotedIdentifier),
+        number,
+        word,
+        map(parameter, Token::Parameter),
+        map(symbol, Token::Symbol),
+    ))(input)
+}
+
+/// The text at the start of `input` that could not be read as a token, why,
+/// and what follows it
+fn invalid(input: RawSpan) -> (RawSpan, RawSpan, &'static str) {
+    let text = *input.fragment();
+    let (len, reason) = match text.chars().next() {
+        Some('\'' | '"' | '`') => (text.len(), "unterminated quoted text"),
+        Some('/') => (text.len(), "unterminated comment"),
+        Some(c) if c.is_ascii_digit() || c == '.' => (
+            text.find(|c: char| !is_identifier_char(c) && !"+-.".contains(c))
+                .unwrap_or(text.len()),
+            "invalid number",
+        ),
+        Some(c) => (c.len_utf8(), "unexpected character"),
+        None => (0, "unexpected end of input"),
+    };
+    let (rem, invalid) =
+        nom::bytes::complete::take::<_, _, ()>(len)(input).expect("length is within the input");
+    (rem, invalid, reason)
+}
+
+/// Split sql text into tokens, skipping whitespace and comments
+pub fn tokenize(source: &str) -> Vec<SpannedToken> {
+    let mut tokens = Vec::new();
+    let mut input = RawSpan::new(source);
+
+    loop {
+        loop {
+            match alt((multispace1, comment))(input) {
+                Ok((rem, _)) => input = rem,
+                Err(nom::Err::Error(_)) => break,
+                // an unterminated block comment
+                Err(_) => {
+                    let (rem, text, reason) = invalid(input);
+                    tokens.push(SpannedToken {
+                        token: Token::Invalid(reason),
+                        span: Span::of(text),
+                    });
+                    input = rem;
+                }
+            }
+        }
+        if input.fragment().is_empty() {
+            return tokens;
+        }
+
+        match consumed(token)(input) {
+            Ok((rem, (text, token))) => {
+                tokens.push(SpannedToken {
+                    token,
+                    span: Span::of(text),
+                });
+                input = rem;
+            }
+            Err(_) => {
+                let (rem, text, reason) = invalid(input);
+                tokens.push(SpannedToken {
+                    token: Token::Invalid(reason),
+                    span: Span::of(text),
+                });
+                input = rem;
+            }
+        }
+    }
+}
+
+/// Input of the grammar, the tokens of a source text not parsed yet
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    source: &'a str,
+    tokens: Rc<[SpannedToken]>,
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            tokens: tokenize(source).into(),
+            position: 0,
+        }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Next token, if any
+    pub fn first(&self) -> Option<&SpannedToken> {
+        self.tokens.get(self.position)
+    }
+
+    /// The input after the next `count` tokens
+    pub fn advance(&self, count: usize) -> Self {
+        Self {
+            position: (self.position + count).min(self.tokens.len()),
+            ..self.clone()
+        }
+    }
+
+    /// Offset in the source of the next token, the end of the source once
+    /// every token is parsed
+    pub fn offset(&self) -> usize {
+        self.first()
+            .map_or(self.source.len(), |token| token.span.offset)
+    }
+
+    /// Source text of the tokens parsed from `self` up to `rest`
+    pub fn span_to(&self, rest: &Tokens) -> Span {
+        let Some(last) = rest
+            .position
+            .checked_sub(1)
+            .filter(|last| *last >= self.position)
+            .map(|last| self.tokens[last].span)
+        else {
+            return Span {
+                offset: self.offset(),
+                len: 0,
+            };
+        };
+        let offset = self.offset();
+        Span {
+            offset,
+            len: last.offset + last.len - offset,
+        }
+    }
+}
+
+impl InputLength for Tokens<'_> {
+    fn input_len(&self) -> usize {
+        self.tokens.len() - self.position
+    }
+}
+
+impl fmt::Debug for Tokens<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.tokens[self.position..]
+                    .iter()
+                    .map(|token| &token.token),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<Token> {
+        tokenize(source)
+            .into_iter()
+            .map(|token| token.token)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let source = "SELECT a,b/* c */FROM \"T\" -- rest\nwhere x<>'it''s' and y >= 1.5e2;";
+
+        assert_eq!(
+            kinds(source),
+            vec![
+                Token::Keyword("select"),
+                Token::Identifier("a".into()),
+                Token::Symbol(Symbol::Comma),
+                Token::Identifier("b".into()),
+                Token::Keyword("from"),
+                Token::QuotedIdentifier("T".into()),
+                Token::Keyword("where"),
+                Token::Identifier("x".into()),
+                Token::Symbol(Symbol::NotEq),
+                Token::String("it's".into()),
+                Token::Keyword("and"),
+                Token::Identifier("y".into()),
+                Token::Symbol(Symbol::GtEq),
+                Token::Number(BigDecimal::from(150)),
+                Token::Symbol(Symbol::Semicolon),
+            ]
+        );
+
+        let spans: Vec<Span> = tokenize("a  bc").into_iter().map(|t| t.span).collect();
+        assert_eq!(
+            spans,
+            [Span { offset: 0, len: 1 }, Span { offset: 3, len: 2 }]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            kinds("a # 'open"),
+            vec![
+                Token::Identifier("a".into()),
+                Token::Invalid("unexpected character"),
+                Token::Invalid("unterminated quoted text"),
+            ]
+        );
+        assert_eq!(
+            kinds("1e+ /* open"),
+            vec![
+                Token::Invalid("invalid number"),
+                Token::Invalid("unterminated comment"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ident() {
+        for (name, printed) in [
+            ("users", "users"),
+            ("_tmp2", "_tmp2"),
+            ("Users", "\"Users\""),
+            ("from", "\"from\""),
+            ("a b", "\"a b\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+        ] {
+            assert_eq!(Ident(name).to_string(), printed);
+            match &kinds(printed)[..] {
+                [Token::Identifier(read) | Token::QuotedIdentifier(read)] => {
+                    assert_eq!(read, name)
+                }
+                other => panic!("{printed} lexed as {other:?}"),
+            }
+        }
+    }
+}
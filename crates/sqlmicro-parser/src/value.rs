@@ -1,75 +1,87 @@
-use std::str::FromStr;
-
 use bigdecimal::BigDecimal;
 use derive_more::Display;
-use nom::{
-    branch::alt,
-    bytes::complete::{take_until, take_while},
-    character::complete::multispace0,
-    error::context,
-    sequence::{preceded, terminated, tuple},
-    Parser,
-};
-use nom_supreme::tag::complete::tag;
+use nom::{branch::alt, combinator::map, error::context, sequence::preceded};
 use serde::{Deserialize, Serialize};
 
-use crate::parse::{peek_then_cut, Parse, ParseResult, RawSpan};
+use crate::{
+    parse::{symbol, token, Parse, ParseResult},
+    token::{Symbol, Token, Tokens},
+};
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize, Display)]
 pub enum Value {
     Number(BigDecimal),
     String(String),
+    Boolean(bool),
+    #[display(fmt = "NULL")]
+    Null,
 }
 
-fn parse_string_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
-    let (rem, (_, value, _)) = context(
-        "String Literal",
-        tuple((
-            tag("'"),
-            take_until("'").map(|s: RawSpan| Value::String(s.fragment().to_string())),
-            tag("'"),
-        )),
-    )(input)?;
-
-    Ok((rem, value))
+impl Value {
+    /// The value as a sql literal, unlike `Display` strings are quoted
+    pub fn to_sql(&self) -> String {
+        match self {
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+            Value::Boolean(true) => "TRUE".into(),
+            Value::Boolean(false) => "FALSE".into(),
+            Value::Null => "NULL".into(),
+        }
+    }
 }
 
-fn parse_number_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
-    let (rem, digits) = context("Number Literal", take_while(|c: char| c.is_numeric()))(input)?;
-
-    let digits = digits.fragment();
+fn parse_number_value(input: Tokens<'_>) -> ParseResult<'_, BigDecimal> {
+    context(
+        "Number Literal",
+        token("a number", |token| match token {
+            Token::Number(n) => Some(n.clone()),
+            _ => None,
+        }),
+    )(input)
+}
 
-    Ok((rem, Value::Number(BigDecimal::from_str(digits).unwrap())))
+/// Parse a string, number, `TRUE`, `FALSE` or `NULL` literal
+pub(crate) fn parse_literal(input: Tokens<'_>) -> ParseResult<'_, Value> {
+    token("a literal", |token| match token {
+        Token::String(s) => Some(Value::String(s.clone())),
+        Token::Number(n) => Some(Value::Number(n.clone())),
+        Token::Keyword("true") => Some(Value::Boolean(true)),
+        Token::Keyword("false") => Some(Value::Boolean(false)),
+        Token::Keyword("null") => Some(Value::Null),
+        _ => None,
+    })(input)
 }
 
+/// A literal, numbers can be negative
 impl<'a> Parse<'a> for Value {
-    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+    fn parse(input: Tokens<'a>) -> ParseResult<'a, Self> {
         context(
             "Value",
-            preceded(
-                multispace0,
-                terminated(
-                    alt((peek_then_cut("'", parse_string_value), parse_number_value)),
-                    multispace0,
-                ),
-            ),
+            alt((
+                map(preceded(symbol(Symbol::Minus), parse_number_value), |n| {
+                    Value::Number(-n)
+                }),
+                parse_literal,
+            )),
         )(input)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use super::*;
 
     #[test]
     fn test_string() {
         let expected = Value::String("123abc new".into());
-        let expected_rem = "fart '123'";
+        let expected_rem = Token::Identifier("fart".into());
 
         let (rem, val) = Value::parse_from_raw("'123abc new' fart '123'").unwrap();
 
         assert_eq!(val, expected);
-        assert_eq!(expected_rem, rem.fragment().to_string());
+        assert_eq!(rem.first().map(|next| &next.token), Some(&expected_rem));
     }
 
     #[test]
@@ -79,4 +91,73 @@ mod tests {
 
         assert_eq!(Value::parse_from_raw("123456").unwrap().1, expected);
     }
+
+    #[test]
+    fn test_escaped_string() {
+        let expected = Value::String("it's ''fine''".into());
+
+        let val = Value::parse_format_error("'it''s ''''fine'''''").unwrap();
+
+        assert_eq!(val, expected);
+        assert_eq!(
+            Value::parse_format_error("''").unwrap(),
+            Value::String("".into())
+        );
+        assert!(Value::parse_format_error("'it''s").is_err());
+    }
+
+    #[test]
+    fn test_number_formats() {
+        for (raw, expected) in [
+            ("-42", "-42"),
+            ("3.25", "3.25"),
+            ("-0.5", "-0.5"),
+            (".5", "0.5"),
+            ("7.", "7"),
+            ("1e3", "1000"),
+            ("2.5E-2", "0.025"),
+            ("-1e+2", "-100"),
+        ] {
+            let num = BigDecimal::from_str(expected).unwrap();
+            assert_eq!(
+                Value::parse_format_error(raw).unwrap(),
+                Value::Number(num),
+                "{raw}"
+            );
+        }
+
+        for invalid in ["", "-", ".", "1e", "1.2.3", "--1", "1e+"] {
+            assert!(Value::parse_format_error(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_to_sql() {
+        for value in [
+            Value::String("it's".into()),
+            Value::String("".into()),
+            Value::Number(BigDecimal::from_str("-2.5").unwrap()),
+            Value::Number(BigDecimal::from_str("1e3").unwrap()),
+            Value::Boolean(false),
+            Value::Null,
+        ] {
+            let sql = value.to_sql();
+            assert_eq!(Value::parse_format_error(&sql).unwrap(), value, "{sql}");
+        }
+        assert_eq!(Value::String("it's".into()).to_sql(), "'it''s'");
+    }
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(
+            Value::parse_format_error("TRUE").unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            Value::parse_format_error("false").unwrap(),
+            Value::Boolean(false)
+        );
+        assert_eq!(Value::parse_format_error("Null").unwrap(), Value::Null);
+        assert!(Value::parse_format_error("nullable").is_err());
+    }
 }
@@ -0,0 +1,673 @@
+//! Traversal of the AST.
+//!
+//! [`Visitor`] walks a statement by reference and [`VisitorMut`] by mutable
+//! reference. Every method has a default that keeps walking into the node's
+//! children through the matching `walk_*` function, so an implementation only
+//! overrides the nodes it cares about, and calls the `walk_*` function itself
+//! when it still wants the children of an overridden node.
+//!
+//! Subqueries are children too: the walk goes into the select of a common
+//! table expression, a derived table, a set operation or a subquery
+//! expression through `visit_select`.
+
+use crate::{
+    expression::{Expression, Parameter},
+    parse::Spanned,
+    query::SqlQuery,
+    Column, ConflictAction, CreateStatement, Distinct, InsertSource, InsertStatement, OnConflict,
+    SelectItem, SelectStatement, SetStatement, TableRef, With,
+};
+
+pub trait Visitor {
+    fn visit_query(&mut self, query: &SqlQuery) {
+        walk_query(self, query)
+    }
+
+    fn visit_create(&mut self, create: &CreateStatement) {
+        walk_create(self, create)
+    }
+
+    fn visit_insert(&mut self, insert: &InsertStatement) {
+        walk_insert(self, insert)
+    }
+
+    fn visit_select(&mut self, select: &SelectStatement) {
+        walk_select(self, select)
+    }
+
+    /// The common table expressions of a select, in the order they are written
+    fn visit_with(&mut self, with: &With) {
+        walk_with(self, with)
+    }
+
+    fn visit_set(&mut self, _set: &SetStatement) {}
+
+    /// A table or derived table of `FROM` or `JOIN`
+    fn visit_table_ref(&mut self, table: &TableRef) {
+        walk_table_ref(self, table)
+    }
+
+    /// A table named by a statement, not the qualifier of a column
+    fn visit_table(&mut self, _name: &Spanned<String>) {}
+
+    /// A column definition of `CREATE TABLE`
+    fn visit_column_def(&mut self, _column: &Column) {}
+
+    /// A column named outside an expression: in the column list of a common
+    /// table expression, an insert or a key, the target of `ON CONFLICT` or
+    /// the column an assignment sets
+    fn visit_column_name(&mut self, _name: &Spanned<String>) {}
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        walk_expression(self, expr)
+    }
+
+    /// A column referenced by an expression
+    fn visit_column(&mut self, _table: Option<&Spanned<String>>, _name: &Spanned<String>) {}
+
+    fn visit_parameter(&mut self, _parameter: &Parameter) {}
+}
+
+pub fn walk_query<V: Visitor + ?Sized>(visitor: &mut V, query: &SqlQuery) {
+    match query {
+        SqlQuery::Create(create) => visitor.visit_create(create),
+        SqlQuery::Insert(insert) => visitor.visit_insert(insert),
+        SqlQuery::Select(select) => visitor.visit_select(select),
+        SqlQuery::Set(set) => visitor.visit_set(set),
+    }
+}
+
+pub fn walk_create<V: Visitor + ?Sized>(visitor: &mut V, create: &CreateStatement) {
+    visitor.visit_table(&create.table);
+    for column in &create.columns {
+        visitor.visit_column_def(column);
+    }
+    for name in create.keys.iter().flat_map(|key| &key.columns) {
+        visitor.visit_column_name(name);
+    }
+    if let Some(query) = &create.query {
+        visitor.visit_select(query);
+    }
+}
+
+pub fn walk_insert<V: Visitor + ?Sized>(visitor: &mut V, insert: &InsertStatement) {
+    visitor.visit_table(&insert.table);
+    for name in &insert.columns {
+        visitor.visit_column_name(name);
+    }
+    match &insert.source {
+        InsertSource::Values(values) => {
+            for value in values {
+                visitor.visit_expression(value);
+            }
+        }
+        InsertSource::Select(select) => visitor.visit_select(select),
+    }
+    if let Some(OnConflict { target, action }) = &insert.on_conflict {
+        for name in target {
+            visitor.visit_column_name(name);
+        }
+        if let ConflictAction::Update(assignments) = action {
+            for assignment in assignments {
+                visitor.visit_column_name(&assignment.column);
+                visitor.visit_expression(&assignment.value);
+            }
+        }
+    }
+    for item in &insert.returning {
+        if let SelectItem::Expression { expr, .. } = item {
+            visitor.visit_expression(expr);
+        }
+    }
+}
+
+/// Visits the parts of the select in the order they are written
+pub fn walk_select<V: Visitor + ?Sized>(visitor: &mut V, select: &SelectStatement) {
+    if let Some(with) = &select.with {
+        visitor.visit_with(with);
+    }
+    if let Some(Distinct::On(exprs)) = &select.distinct {
+        for expr in exprs {
+            visitor.visit_expression(expr);
+        }
+    }
+    for item in &select.fields {
+        if let SelectItem::Expression { expr, .. } = item {
+            visitor.visit_expression(expr);
+        }
+    }
+    visitor.visit_table_ref(&select.table);
+    for join in &select.joins {
+        visitor.visit_table_ref(&join.table);
+        visitor.visit_expression(&join.on);
+    }
+    if let Some(filter) = &select.filter {
+        visitor.visit_expression(filter);
+    }
+    for expr in &select.group_by {
+        visitor.visit_expression(expr);
+    }
+    for operation in &select.compound {
+        visitor.visit_select(&operation.query);
+    }
+    for order in &select.order_by {
+        visitor.visit_expression(&order.expr);
+    }
+}
+
+pub fn walk_with<V: Visitor + ?Sized>(visitor: &mut V, with: &With) {
+    for cte in &with.ctes {
+        for name in &cte.columns {
+            visitor.visit_column_name(name);
+        }
+        visitor.visit_select(&cte.query);
+    }
+}
+
+pub fn walk_table_ref<V: Visitor + ?Sized>(visitor: &mut V, table: &TableRef) {
+    match table {
+        TableRef::Table { name, .. } => visitor.visit_table(name),
+        TableRef::Subquery { query, .. } => visitor.visit_select(query),
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::Column { table, name } => visitor.visit_column(table.as_ref(), name),
+        Expression::Parameter(parameter) => visitor.visit_parameter(&parameter.node),
+        Expression::Binary { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::Unary { operand, .. } => visitor.visit_expression(operand),
+        Expression::Function { args, .. } => {
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+        }
+        Expression::Subquery(subquery) | Expression::Exists(subquery) => {
+            visitor.visit_select(subquery)
+        }
+        Expression::InSubquery { expr, subquery, .. } => {
+            visitor.visit_expression(expr);
+            visitor.visit_select(subquery);
+        }
+        Expression::InList { expr, list, .. } => {
+            visitor.visit_expression(expr);
+            for item in list {
+                visitor.visit_expression(item);
+            }
+        }
+        Expression::Between {
+            expr, low, high, ..
+        } => {
+            visitor.visit_expression(expr);
+            visitor.visit_expression(low);
+            visitor.visit_expression(high);
+        }
+        Expression::Like {
+            expr,
+            pattern,
+            escape,
+            ..
+        } => {
+            visitor.visit_expression(expr);
+            visitor.visit_expression(pattern);
+            if let Some(escape) = escape {
+                visitor.visit_expression(escape);
+            }
+        }
+        Expression::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            if let Some(operand) = operand {
+                visitor.visit_expression(operand);
+            }
+            for (when, then) in branches {
+                visitor.visit_expression(when);
+                visitor.visit_expression(then);
+            }
+            if let Some(else_result) = else_result {
+                visitor.visit_expression(else_result);
+            }
+        }
+        Expression::Cast { expr, .. } => visitor.visit_expression(expr),
+        Expression::Window { args, over, .. } => {
+            for expr in args.iter().chain(&over.partition_by) {
+                visitor.visit_expression(expr);
+            }
+            for order in &over.order_by {
+                visitor.visit_expression(&order.expr);
+            }
+        }
+        Expression::Literal(_) | Expression::Wildcard => {}
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_query_mut(&mut self, query: &mut SqlQuery) {
+        walk_query_mut(self, query)
+    }
+
+    fn visit_create_mut(&mut self, create: &mut CreateStatement) {
+        walk_create_mut(self, create)
+    }
+
+    fn visit_insert_mut(&mut self, insert: &mut InsertStatement) {
+        walk_insert_mut(self, insert)
+    }
+
+    fn visit_select_mut(&mut self, select: &mut SelectStatement) {
+        walk_select_mut(self, select)
+    }
+
+    /// The common table expressions of a select, in the order they are written
+    fn visit_with_mut(&mut self, with: &mut With) {
+        walk_with_mut(self, with)
+    }
+
+    fn visit_set_mut(&mut self, _set: &mut SetStatement) {}
+
+    /// A table or derived table of `FROM` or `JOIN`
+    fn visit_table_ref_mut(&mut self, table: &mut TableRef) {
+        walk_table_ref_mut(self, table)
+    }
+
+    /// A table named by a statement, not the qualifier of a column
+    fn visit_table_mut(&mut self, _name: &mut Spanned<String>) {}
+
+    /// A column definition of `CREATE TABLE`
+    fn visit_column_def_mut(&mut self, _column: &mut Column) {}
+
+    /// A column named outside an expression: in the column list of a common
+    /// table expression, an insert or a key, the target of `ON CONFLICT` or
+    /// the column an assignment sets
+    fn visit_column_name_mut(&mut self, _name: &mut Spanned<String>) {}
+
+    fn visit_expression_mut(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr)
+    }
+
+    /// A column referenced by an expression
+    fn visit_column_mut(
+        &mut self,
+        _table: &mut Option<Spanned<String>>,
+        _name: &mut Spanned<String>,
+    ) {
+    }
+
+    fn visit_parameter_mut(&mut self, _parameter: &mut Parameter) {}
+}
+
+pub fn walk_query_mut<V: VisitorMut + ?Sized>(visitor: &mut V, query: &mut SqlQuery) {
+    match query {
+        SqlQuery::Create(create) => visitor.visit_create_mut(create),
+        SqlQuery::Insert(insert) => visitor.visit_insert_mut(insert),
+        SqlQuery::Select(select) => visitor.visit_select_mut(select),
+        SqlQuery::Set(set) => visitor.visit_set_mut(set),
+    }
+}
+
+pub fn walk_create_mut<V: VisitorMut + ?Sized>(visitor: &mut V, create: &mut CreateStatement) {
+    visitor.visit_table_mut(&mut create.table);
+    for column in &mut create.columns {
+        visitor.visit_column_def_mut(column);
+    }
+    for name in create.keys.iter_mut().flat_map(|key| &mut key.columns) {
+        visitor.visit_column_name_mut(name);
+    }
+    if let Some(query) = &mut create.query {
+        visitor.visit_select_mut(query);
+    }
+}
+
+pub fn walk_insert_mut<V: VisitorMut + ?Sized>(visitor: &mut V, insert: &mut InsertStatement) {
+    visitor.visit_table_mut(&mut insert.table);
+    for name in &mut insert.columns {
+        visitor.visit_column_name_mut(name);
+    }
+    match &mut insert.source {
+        InsertSource::Values(values) => {
+            for value in values {
+                visitor.visit_expression_mut(value);
+            }
+        }
+        InsertSource::Select(select) => visitor.visit_select_mut(select),
+    }
+    if let Some(OnConflict { target, action }) = &mut insert.on_conflict {
+        for name in target {
+            visitor.visit_column_name_mut(name);
+        }
+        if let ConflictAction::Update(assignments) = action {
+            for assignment in assignments {
+                visitor.visit_column_name_mut(&mut assignment.column);
+                visitor.visit_expression_mut(&mut assignment.value);
+            }
+        }
+    }
+    for item in &mut insert.returning {
+        if let SelectItem::Expression { expr, .. } = item {
+            visitor.visit_expression_mut(expr);
+        }
+    }
+}
+
+/// Visits the parts of the select in the order they are written
+pub fn walk_select_mut<V: VisitorMut + ?Sized>(visitor: &mut V, select: &mut SelectStatement) {
+    if let Some(with) = &mut select.with {
+        visitor.visit_with_mut(with);
+    }
+    if let Some(Distinct::On(exprs)) = &mut select.distinct {
+        for expr in exprs {
+            visitor.visit_expression_mut(expr);
+        }
+    }
+    for item in &mut select.fields {
+        if let SelectItem::Expression { expr, .. } = item {
+            visitor.visit_expression_mut(expr);
+        }
+    }
+    visitor.visit_table_ref_mut(&mut select.table);
+    for join in &mut select.joins {
+        visitor.visit_table_ref_mut(&mut join.table);
+        visitor.visit_expression_mut(&mut join.on);
+    }
+    if let Some(filter) = &mut select.filter {
+        visitor.visit_expression_mut(filter);
+    }
+    for expr in &mut select.group_by {
+        visitor.visit_expression_mut(expr);
+    }
+    for operation in &mut select.compound {
+        visitor.visit_select_mut(&mut operation.query);
+    }
+    for order in &mut select.order_by {
+        visitor.visit_expression_mut(&mut order.expr);
+    }
+}
+
+pub fn walk_with_mut<V: VisitorMut + ?Sized>(visitor: &mut V, with: &mut With) {
+    for cte in &mut with.ctes {
+        for name in &mut cte.columns {
+            visitor.visit_column_name_mut(name);
+        }
+        visitor.visit_select_mut(&mut cte.query);
+    }
+}
+
+pub fn walk_table_ref_mut<V: VisitorMut + ?Sized>(visitor: &mut V, table: &mut TableRef) {
+    match table {
+        TableRef::Table { name, .. } => visitor.visit_table_mut(name),
+        TableRef::Subquery { query, .. } => visitor.visit_select_mut(query),
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expression) {
+    match expr {
+        Expression::Column { table, name } => visitor.visit_column_mut(table, name),
+        Expression::Parameter(parameter) => visitor.visit_parameter_mut(&mut parameter.node),
+        Expression::Binary { left, right, .. } => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        }
+        Expression::Unary { operand, .. } => visitor.visit_expression_mut(operand),
+        Expression::Function { args, .. } => {
+            for arg in args {
+                visitor.visit_expression_mut(arg);
+            }
+        }
+        Expression::Subquery(subquery) | Expression::Exists(subquery) => {
+            visitor.visit_select_mut(subquery)
+        }
+        Expression::InSubquery { expr, subquery, .. } => {
+            visitor.visit_expression_mut(expr);
+            visitor.visit_select_mut(subquery);
+        }
+        Expression::InList { expr, list, .. } => {
+            visitor.visit_expression_mut(expr);
+            for item in list {
+                visitor.visit_expression_mut(item);
+            }
+        }
+        Expression::Between {
+            expr, low, high, ..
+        } => {
+            visitor.visit_expression_mut(expr);
+            visitor.visit_expression_mut(low);
+            visitor.visit_expression_mut(high);
+        }
+        Expression::Like {
+            expr,
+            pattern,
+            escape,
+            ..
+        } => {
+            visitor.visit_expression_mut(expr);
+            visitor.visit_expression_mut(pattern);
+            if let Some(escape) = escape {
+                visitor.visit_expression_mut(escape);
+            }
+        }
+        Expression::Case {
+            operand,
+            branches,
+            else_result,
+        } => {
+            if let Some(operand) = operand {
+                visitor.visit_expression_mut(operand);
+            }
+            for (when, then) in branches {
+                visitor.visit_expression_mut(when);
+                visitor.visit_expression_mut(then);
+            }
+            if let Some(else_result) = else_result {
+                visitor.visit_expression_mut(else_result);
+            }
+        }
+        Expression::Cast { expr, .. } => visitor.visit_expression_mut(expr),
+        Expression::Window { args, over, .. } => {
+            for expr in args.iter_mut().chain(&mut over.partition_by) {
+                visitor.visit_expression_mut(expr);
+            }
+            for order in &mut over.order_by {
+                visitor.visit_expression_mut(&mut order.expr);
+            }
+        }
+        Expression::Literal(_) | Expression::Wildcard => {}
+    }
+}
+
+/// Stored tables a statement names, in the order they are written and
+/// without repeats. The names of common table expressions are left out
+/// where they are in scope
+pub fn referenced_tables(query: &SqlQuery) -> Vec<String> {
+    struct Tables {
+        tables: Vec<String>,
+        /// Common table expressions in scope, the innermost last
+        ctes: Vec<String>,
+    }
+
+    impl Visitor for Tables {
+        fn visit_select(&mut self, select: &SelectStatement) {
+            let scope = self.ctes.len();
+            walk_select(self, select);
+            self.ctes.truncate(scope);
+        }
+
+        // a recursive one reads itself, a plain one the table of its name
+        fn visit_with(&mut self, with: &With) {
+            for cte in &with.ctes {
+                if with.recursive {
+                    self.ctes.push(cte.name.to_string());
+                }
+                self.visit_select(&cte.query);
+                if !with.recursive {
+                    self.ctes.push(cte.name.to_string());
+                }
+            }
+        }
+
+        fn visit_table(&mut self, name: &Spanned<String>) {
+            if !self.ctes.contains(name) && !self.tables.contains(name) {
+                self.tables.push(name.to_string());
+            }
+        }
+    }
+
+    let mut tables = Tables {
+        tables: Vec::new(),
+        ctes: Vec::new(),
+    };
+    tables.visit_query(query);
+    tables.tables
+}
+
+/// Rename the column `from` to `to` in every expression that references it,
+/// whatever table qualifies it, in the definitions and keys of `CREATE TABLE`
+/// and wherever a statement names columns outside an expression
+pub fn rename_column(query: &mut SqlQuery, from: &str, to: &str) {
+    struct Rename<'a> {
+        from: &'a str,
+        to: &'a str,
+    }
+
+    impl VisitorMut for Rename<'_> {
+        fn visit_column_def_mut(&mut self, column: &mut Column) {
+            if column.name.node == self.from {
+                column.name.node = self.to.to_string();
+            }
+        }
+
+        fn visit_column_mut(
+            &mut self,
+            _table: &mut Option<Spanned<String>>,
+            name: &mut Spanned<String>,
+        ) {
+            self.visit_column_name_mut(name);
+        }
+
+        fn visit_column_name_mut(&mut self, name: &mut Spanned<String>) {
+            if name.node == self.from {
+                name.node = self.to.to_string();
+            }
+        }
+    }
+
+    Rename { from, to }.visit_query_mut(query);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parse;
+
+    #[test]
+    fn test_referenced_tables() {
+        let query = SqlQuery::parse_format_error(
+            "select users.name from users join posts on users.id = posts.author \
+             join users on 1 = 1 where posts.title = ?;",
+        )
+        .unwrap();
+
+        assert_eq!(referenced_tables(&query), ["users", "posts"]);
+
+        let nested = SqlQuery::parse_format_error(
+            "select (select max(id) from c) from (select * from a) as t \
+             where exists (select 1 from b where b.id = t.id);",
+        )
+        .unwrap();
+        assert_eq!(referenced_tables(&nested), ["c", "a", "b"]);
+
+        // an alias is not a table of its own
+        let aliased =
+            SqlQuery::parse_format_error("select u.id from users u join users as v on true;")
+                .unwrap();
+        assert_eq!(referenced_tables(&aliased), ["users"]);
+
+        let insert = SqlQuery::parse_format_error("insert into logs values 1;").unwrap();
+        assert_eq!(referenced_tables(&insert), ["logs"]);
+
+        let with = SqlQuery::parse_format_error(
+            "with x as (select * from x), y as (select * from x) \
+             select * from y join z on true where exists (select 1 from x);",
+        )
+        .unwrap();
+        assert_eq!(referenced_tables(&with), ["x", "z"]);
+
+        let recursive = SqlQuery::parse_format_error(
+            "with recursive n (i) as (select 1 from t union all select i + 1 from n) \
+             select i from n;",
+        )
+        .unwrap();
+        assert_eq!(referenced_tables(&recursive), ["t"]);
+    }
+
+    #[test]
+    fn test_rename_column() {
+        let mut query = SqlQuery::parse_format_error(
+            "select id, count(*) as total from t join u on t.id = u.other \
+             where -id > 1 group by id order by id;",
+        )
+        .unwrap();
+
+        rename_column(&mut query, "id", "key");
+
+        assert_eq!(
+            query.to_string(),
+            "SELECT key, count(*) AS total FROM t JOIN u ON t.key = u.other \
+             WHERE -key > 1 GROUP BY key ORDER BY key;"
+        );
+
+        let mut create =
+            SqlQuery::parse_format_error("create table t (id int, b int, primary key (id));")
+                .unwrap();
+        rename_column(&mut create, "id", "key");
+        assert_eq!(
+            create.to_string(),
+            "CREATE TABLE t (key INT, b INT, PRIMARY KEY (key));"
+        );
+
+        let mut with =
+            SqlQuery::parse_format_error("with x (id) as (select id from t) select id from x;")
+                .unwrap();
+        rename_column(&mut with, "id", "key");
+        assert_eq!(
+            with.to_string(),
+            "WITH x (key) AS (SELECT key FROM t) SELECT key FROM x;"
+        );
+
+        let mut insert = SqlQuery::parse_format_error(
+            "insert into t (id, b) select id, b from u \
+             on conflict (id) do update set id = excluded.id;",
+        )
+        .unwrap();
+        rename_column(&mut insert, "id", "key");
+        assert_eq!(
+            insert.to_string(),
+            "INSERT INTO t (key, b) SELECT key, b FROM u \
+             ON CONFLICT (key) DO UPDATE SET key = excluded.key;"
+        );
+    }
+
+    #[test]
+    fn test_visit_order() {
+        struct Columns(Vec<String>);
+
+        impl Visitor for Columns {
+            fn visit_column(&mut self, _table: Option<&Spanned<String>>, name: &Spanned<String>) {
+                self.0.push(name.to_string());
+            }
+        }
+
+        let query = SqlQuery::parse_format_error(
+            "select a, f(b, c + d) from t join u on e = f where g group by h order by i;",
+        )
+        .unwrap();
+        let mut columns = Columns(Vec::new());
+        columns.visit_query(&query);
+
+        assert_eq!(columns.0, ["a", "b", "c", "d", "e", "f", "g", "h", "i"]);
+    }
+}
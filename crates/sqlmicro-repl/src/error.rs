@@ -1,6 +1,6 @@
-use miette::Diagnostic;
+use miette::{Diagnostic, SourceSpan};
 use sqlmicro_execution::ExecutionError;
-use sqlmicro_parser::error::FormattedError;
+use sqlmicro_parser::error::{FormattedError, ParseErrors};
 use thiserror::Error;
 
 #[derive(Debug, Diagnostic, Error)]
@@ -10,6 +10,18 @@ pub enum SqlMicroError<'a> {
     ExecutionError(#[from] ExecutionError),
     #[diagnostic(transparent)]
     ParsingError(FormattedError<'a>),
+    #[diagnostic(transparent)]
+    ParsingErrors(ParseErrors<'a>),
+    #[error("Statement {} of the script failed", .index + 1)]
+    ScriptError {
+        #[source_code]
+        src: &'a str,
+        #[label("{error}")]
+        span: SourceSpan,
+        index: usize,
+        #[source]
+        error: ExecutionError,
+    },
 }
 
 impl<'a> From<FormattedError<'a>> for SqlMicroError<'a> {
@@ -17,3 +29,9 @@ impl<'a> From<FormattedError<'a>> for SqlMicroError<'a> {
         Self::ParsingError(value)
     }
 }
+
+impl<'a> From<ParseErrors<'a>> for SqlMicroError<'a> {
+    fn from(value: ParseErrors<'a>) -> Self {
+        Self::ParsingErrors(value)
+    }
+}
@@ -4,17 +4,31 @@ use error::SqlMicroError;
 use miette::GraphicalReportHandler;
 use response::display_response;
 use rustyline::{error::ReadlineError, DefaultEditor, Result};
-use sqlmicro_execution::executor::{ExecutionResponse, Executor};
-use sqlmicro_parser::{parse::Parse, query::SqlQuery};
+use sqlmicro_execution::executor::Executor;
+use sqlmicro_parser::script::Script;
 const HISTORY_FILE: &str = "./history.txt";
 
+/// Run every statement of the input, printing each response, and stop at
+/// the first error. Nothing runs when a statement does not parse, and every
+/// statement that does not is reported
 fn parse_and_run<'a>(
-    exec: &'a mut Executor,
-    query: &'a str,
-) -> std::result::Result<ExecutionResponse<'a>, SqlMicroError<'a>> {
-    let query: SqlQuery = SqlQuery::parse_format_error(query)?;
-    let res = exec.run(query)?;
-    Ok(res)
+    exec: &mut Executor,
+    input: &'a str,
+) -> std::result::Result<(), SqlMicroError<'a>> {
+    let script = Script::parse_recovering(input)?;
+    match exec.run_script(script) {
+        Ok(responses) => responses.into_iter().for_each(display_response),
+        Err(e) => {
+            e.responses.into_iter().for_each(display_response);
+            return Err(SqlMicroError::ScriptError {
+                src: input,
+                span: e.span,
+                index: e.index,
+                error: e.error,
+            });
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -32,20 +46,15 @@ fn main() -> Result<()> {
             Ok(line) => {
                 rl.add_history_entry(line.as_str())?;
 
-                let query: &str = line.as_ref();
-                let res = parse_and_run(&mut exec, query);
-
-                match res {
-                    Ok(res) => display_response(res),
-                    Err(e) => {
-                        let mut s = String::new();
-                        GraphicalReportHandler::new()
-                            .with_cause_chain()
-                            .with_context_lines(10)
-                            .render_report(&mut s, &e)
-                            .unwrap();
-                        println!("{s}");
-                    }
+                let input: &str = line.as_ref();
+                if let Err(e) = parse_and_run(&mut exec, input) {
+                    let mut s = String::new();
+                    GraphicalReportHandler::new()
+                        .with_cause_chain()
+                        .with_context_lines(10)
+                        .render_report(&mut s, &e)
+                        .unwrap();
+                    println!("{s}");
                 }
             }
             Err(ReadlineError::Eof) => break,
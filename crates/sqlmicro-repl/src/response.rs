@@ -6,7 +6,10 @@ pub fn display_response(res: ExecutionResponse) {
         ExecutionResponse::Select(rows) => {
             let mut builder = Builder::default();
 
-            let row = rows.get(0).expect("Table has no data");
+            let Some(row) = rows.first() else {
+                println!("(0 rows)");
+                return;
+            };
 
             let columns: Vec<String> = row
                 .columns()
@@ -16,8 +19,8 @@ pub fn display_response(res: ExecutionResponse) {
 
             builder.set_columns(&columns);
 
-            for row in rows.into_iter() {
-                builder.add_record(columns.iter().map(|col| row.get(col)));
+            for row in rows.iter() {
+                builder.add_record(row.values().iter().map(|val| val.to_string()));
             }
 
             println!("{}", builder.build());